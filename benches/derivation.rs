@@ -0,0 +1,65 @@
+//! Measure the derivation of all PINs with each algorithm, to weigh speed against the cost of guessing them.
+//! The binary derives the PINs of its built-in serial numbers for testing purposes, so no card reader is read;
+//!   each run includes starting the process, which `default-pin` alone measures, as it derives nothing.
+//! Run with `cargo bench`, the binary is built with the bench profile.
+
+use std::{
+  process::{
+    Command,
+    Stdio,
+  },
+  time::{
+    Duration,
+    Instant,
+  },
+};
+
+/// How often each algorithm is run, the median of these runs is reported.
+const RUNS: usize = 25;
+
+/// Each algorithm with the options selecting it, `--allow-root` as benchmarks might run as root, e.g. in a container.
+const ALGORITHMS: [(&str, &[&str]); 5] = [
+  ("default-pin",             &["--algorithm", "default-pin"]),
+  ("double-sha512",           &["--algorithm", "double-sha512"]),
+  ("double-sha512 stretched", &["--algorithm", "double-sha512", "--hash-iterations", "10000"]),
+  ("xor-sha512",              &["--algorithm", "xor-sha512"]),
+  ("argon2id",                &["--algorithm", "argon2id"]),
+];
+
+/// Run the binary once with these options and measure how long it took.
+fn run(options: &[&str]) -> Duration {
+  let start = Instant::now();
+  let status = Command::new(env!("CARGO_BIN_EXE_main"))
+  .args(options)
+  .arg("--allow-root")
+  .stdout(Stdio::null())
+  .status()
+  .expect("Cannot run the binary");
+  let elapsed = start.elapsed();
+  assert!(status.success(), "Deriving the PINs with {:?} failed: {}", options, status);
+  elapsed
+}
+
+fn main() {
+  let width = ALGORITHMS.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+  let measured: Vec<(&str, Duration, Duration)> = ALGORITHMS
+  .iter()
+  .map(|&(name, options)| {
+    let mut durations: Vec<Duration> = (0..RUNS).map(|_| run(options)).collect();
+    durations.sort();
+    (name, durations[0], durations[RUNS / 2])
+  })
+  .collect();
+  let (_, _, process) = measured[0];
+  measured
+  .iter()
+  .for_each(|(name, fastest, median)|
+    println!(
+      "{:width$}  median {:>10.3?}  fastest {:>10.3?}  without the process {:>10.3?}",
+      name,
+      median,
+      fastest,
+      median.saturating_sub(process)
+    )
+  );
+}
@@ -0,0 +1,312 @@
+use {
+  crate::{
+    blake2b::{
+      blake2b,
+      MAX_OUTPUT_LENGTH,
+    },
+    memory::Wiped,
+    Error,
+  },
+  std::{
+    array,
+    fmt::{
+      Display,
+      Formatter,
+      Result as FormatResult,
+    },
+    ptr,
+    str::FromStr,
+    sync::atomic::{
+      compiler_fence,
+      Ordering,
+    },
+  },
+};
+
+/// The version of Argon2 implemented, 1.3.
+const VERSION: u32 = 0x13;
+
+/// The type of Argon2 implemented, Argon2id: data-independent addressing in the first half of the first pass,
+///   data-dependent addressing afterwards.
+const TYPE: u32 = 2;
+
+/// The number of slices each lane is split into, the lanes are synchronised after each.
+const SYNC_POINTS: usize = 4;
+
+/// The number of 64 bit words of a block of 1 KiB.
+const BLOCK_WORDS: usize = 0x80;
+
+/// A block of the memory Argon2 fills.
+type Block = [u64; BLOCK_WORDS];
+
+/// The cost of Argon2id, see `--argon2-cost`: the memory in KiB, the number of passes over it and the number of lanes.
+/// The lanes are filled one after the other, not in parallel, so more lanes do not make the derivation faster.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Cost {
+  pub memory: u32,
+  pub passes: u32,
+  pub lanes:  u32,
+}
+
+impl Default for Cost {
+  /// 19 MiB, 2 passes and 1 lane, the minimum recommended by OWASP, which takes a fraction of a second.
+  fn default() -> Self {
+    Self { memory: 19 * 1024, passes: 2, lanes: 1 }
+  }
+}
+
+/// The cost as given to `--argon2-cost`, e.g. `19456,2,1`.
+impl Display for Cost {
+  fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+    write!(formatter, "{},{},{}", self.memory, self.passes, self.lanes)
+  }
+}
+
+impl FromStr for Cost {
+  type Err = Error;
+
+  /// Parse the memory in KiB, the passes and the lanes, separated by commas.
+  /// There must be at least one pass and one lane, and at least 8 KiB of memory for each lane, see RFC 9106, section 3.1.
+  fn from_str(cost: &str) -> Result<Self, Error> {
+    let [memory, passes, lanes] = cost
+    .split(',')
+    .map(|value| value.trim().parse().map_err(|_| Error::InvalidArguments))
+    .collect::<Result<Vec<u32>, Error>>()?
+    .try_into()
+    .map_err(|_| Error::InvalidArguments)?;
+    (passes >= 1 && (1..=0xff_ffff).contains(&lanes) && memory >= 8 * lanes)
+    .then_some(Self { memory, passes, lanes })
+    .ok_or(Error::InvalidArguments)
+  }
+}
+
+/// The memory of blocks, zeroed when dropped like `Wiped`, as it is derived from the password.
+struct Memory(Vec<Block>);
+
+impl Drop for Memory {
+  fn drop(&mut self) {
+    self.0
+    .iter_mut()
+    .flatten()
+    .for_each(|word| unsafe { ptr::write_volatile(word, 0) });
+    compiler_fence(Ordering::SeqCst);
+  }
+}
+
+/// Hash into a digest of any length, H' of RFC 9106, section 3.3:
+///   Up to 64 bytes, this is BLAKE2b of the length and the input, longer digests are the first halves of a chain of them.
+fn hash_long(length: usize, input: &[u8]) -> Vec<u8> {
+  let mut message = (length as u32).to_le_bytes().to_vec();
+  message.extend_from_slice(input);
+  if length <= MAX_OUTPUT_LENGTH {
+    return blake2b(length, &message);
+  }
+  let mut output = Vec::with_capacity(length);
+  let mut digest = blake2b(MAX_OUTPUT_LENGTH, &message);
+  loop {
+    output.extend_from_slice(&digest[..MAX_OUTPUT_LENGTH / 2]);
+    let remaining = length - output.len();
+    if remaining <= MAX_OUTPUT_LENGTH {
+      output.extend_from_slice(&blake2b(remaining, &digest));
+      return output;
+    }
+    digest = blake2b(MAX_OUTPUT_LENGTH, &digest);
+  }
+}
+
+/// Multiply the lower halves of two words and add them and twice the product, as BlaMka does.
+fn blamka(x: u64, y: u64) -> u64 {
+  x
+  .wrapping_add(y)
+  .wrapping_add(2u64.wrapping_mul(u64::from(x as u32)).wrapping_mul(u64::from(y as u32)))
+}
+
+/// Mix four of the 16 words of the permutation P.
+fn mix(words: &mut [u64; 16], [a, b, c, d]: [usize; 4]) {
+  words[a] = blamka(words[a], words[b]);
+  words[d] = (words[d] ^ words[a]).rotate_right(32);
+  words[c] = blamka(words[c], words[d]);
+  words[b] = (words[b] ^ words[c]).rotate_right(24);
+  words[a] = blamka(words[a], words[b]);
+  words[d] = (words[d] ^ words[a]).rotate_right(16);
+  words[c] = blamka(words[c], words[d]);
+  words[b] = (words[b] ^ words[c]).rotate_right(63);
+}
+
+/// Apply the permutation P to the 16 words of the block at these indices, i.e. a row or a column.
+fn permute(block: &mut Block, indices: [usize; 16]) {
+  let mut words = indices.map(|index| block[index]);
+  [[0, 4, 8, 12], [1, 5, 9, 13], [2, 6, 10, 14], [3, 7, 11, 15], [0, 5, 10, 15], [1, 6, 11, 12], [2, 7, 8, 13], [3, 4, 9, 14]]
+  .into_iter()
+  .for_each(|quarter| mix(&mut words, quarter));
+  indices
+  .into_iter()
+  .zip(words)
+  .for_each(|(index, word)| block[index] = word);
+}
+
+/// Compress the previous and the reference block into the next one, G of RFC 9106, section 3.5,
+///   XORed into the next block instead of replacing it with `xor`, i.e. in all passes but the first.
+fn fill_block(previous: &Block, reference: &Block, next: &mut Block, xor: bool) {
+  let mut block: Block = array::from_fn(|index| previous[index] ^ reference[index]);
+  let mut result = block;
+  if xor {
+    result
+    .iter_mut()
+    .zip(next.iter())
+    .for_each(|(word, next)| *word ^= next);
+  }
+  (0..8).for_each(|row| permute(&mut block, array::from_fn(|index| 16 * row + index)));
+  (0..8).for_each(|column| permute(&mut block, array::from_fn(|index| 16 * (index / 2) + 2 * column + index % 2)));
+  next
+  .iter_mut()
+  .zip(result.iter().zip(block.iter()))
+  .for_each(|(next, (result, block))| *next = result ^ block);
+  result.fill(0);
+}
+
+/// The layout of the memory and its position while it is filled.
+struct Position {
+  pass:           usize,
+  slice:          usize,
+  lane:           usize,
+  lanes:          usize,
+  segment_length: usize,
+  lane_length:    usize,
+}
+
+impl Position {
+  /// Map 32 pseudo-random bits to the index of the reference block within its lane, see RFC 9106, section 3.4.1.2:
+  ///   Only blocks already filled and not in the current segment of other lanes may be referenced,
+  ///   and recent blocks are more likely.
+  fn reference_index(&self, index: usize, pseudo_random: u32, same_lane: bool) -> usize {
+    let area = match (self.pass, same_lane) {
+      (0, _) if self.slice == 0 => index - 1,
+      (0, true)                 => self.slice * self.segment_length + index - 1,
+      (0, false)                => self.slice * self.segment_length - usize::from(index == 0),
+      (_, true)                 => self.lane_length - self.segment_length + index - 1,
+      (_, false)                => self.lane_length - self.segment_length - usize::from(index == 0),
+    };
+    let relative = (u64::from(pseudo_random) * u64::from(pseudo_random)) >> 32;
+    let relative = area - 1 - ((area as u64 * relative) >> 32) as usize;
+    let start = if self.pass == 0 || self.slice == SYNC_POINTS - 1 { 0 } else { (self.slice + 1) * self.segment_length };
+    (start + relative) % self.lane_length
+  }
+
+  /// Fill the segment of this slice of this lane.
+  /// The first half of the first pass takes the reference blocks from address blocks computed from the position,
+  ///   the rest from the previous block, which depends on the password.
+  fn fill_segment(&self, memory: &mut [Block], total_blocks: usize, passes: u32) {
+    let data_independent = self.pass == 0 && self.slice < SYNC_POINTS / 2;
+    let zero = [0u64; BLOCK_WORDS];
+    let mut input = [0u64; BLOCK_WORDS];
+    input[..6].copy_from_slice(&[self.pass, self.lane, self.slice, total_blocks, passes as usize, TYPE as usize].map(|word| word as u64));
+    let mut addresses = [0u64; BLOCK_WORDS];
+    let mut next_addresses = |addresses: &mut Block| {
+      input[6] += 1;
+      fill_block(&zero, &input, addresses, false);
+      let first = *addresses;
+      fill_block(&zero, &first, addresses, false);
+    };
+    let start = if self.pass == 0 && self.slice == 0 { 2 } else { 0 };
+    if data_independent && start != 0 {
+      next_addresses(&mut addresses);
+    }
+    (start..self.segment_length)
+    .for_each(|index| {
+      let current = self.lane * self.lane_length + self.slice * self.segment_length + index;
+      let previous = if current.is_multiple_of(self.lane_length) { current + self.lane_length - 1 } else { current - 1 };
+      if data_independent && index % BLOCK_WORDS == 0 {
+        next_addresses(&mut addresses);
+      }
+      let pseudo_random = if data_independent { addresses[index % BLOCK_WORDS] } else { memory[previous][0] };
+      let reference_lane = if self.pass == 0 && self.slice == 0 { self.lane } else { (pseudo_random >> 32) as usize % self.lanes };
+      let reference = reference_lane * self.lane_length
+        + self.reference_index(index, pseudo_random as u32, reference_lane == self.lane);
+      let (previous, reference) = (memory[previous], memory[reference]);
+      fill_block(&previous, &reference, &mut memory[current], self.pass > 0);
+    });
+  }
+}
+
+/// Get the bytes of a block, little endian word by word.
+fn block_bytes(block: &Block) -> Wiped<Vec<u8>> {
+  Wiped(block.iter().flat_map(|word| word.to_le_bytes()).collect())
+}
+
+/// Calculate the Argon2id tag of `tag_length` bytes of the password, see RFC 9106.
+/// Memory and time are spent by design, see `Cost`, so guessing the password is slow as well.
+pub fn argon2id(password: &[u8], salt: &[u8], secret: &[u8], associated_data: &[u8], cost: Cost, tag_length: usize) -> Vec<u8> {
+  let lanes = cost.lanes as usize;
+  let segment_length = cost.memory as usize / (SYNC_POINTS * lanes);
+  let lane_length = segment_length * SYNC_POINTS;
+  let total_blocks = lane_length * lanes;
+
+  let length = |bytes: &[u8]| (bytes.len() as u32).to_le_bytes();
+  let parameters = [cost.lanes, tag_length as u32, cost.memory, cost.passes, VERSION, TYPE].map(u32::to_le_bytes);
+  let initial = Wiped(
+    blake2b(
+      MAX_OUTPUT_LENGTH,
+      &Wiped(
+        [
+          parameters.as_flattened(),
+          &length(password), password,
+          &length(salt), salt,
+          &length(secret), secret,
+          &length(associated_data), associated_data,
+        ]
+        .concat()
+      ).0,
+    )
+  );
+
+  let mut memory = Memory(vec![[0u64; BLOCK_WORDS]; total_blocks]);
+  (0..lanes)
+  .flat_map(|lane| (0..2).map(move |column| (lane, column)))
+  .for_each(|(lane, column)| {
+    let input = Wiped([&initial.0[..], &(column as u32).to_le_bytes(), &(lane as u32).to_le_bytes()].concat());
+    let bytes = Wiped(hash_long(BLOCK_WORDS * 8, &input.0));
+    memory.0[lane * lane_length + column] = array::from_fn(|index|
+      u64::from_le_bytes(bytes.0[8 * index..8 * index + 8].try_into().unwrap())
+    );
+  });
+
+  (0..cost.passes as usize)
+  .flat_map(|pass| (0..SYNC_POINTS).map(move |slice| (pass, slice)))
+  .flat_map(|(pass, slice)| (0..lanes).map(move |lane| Position { pass, slice, lane, lanes, segment_length, lane_length }))
+  .for_each(|position| position.fill_segment(&mut memory.0, total_blocks, cost.passes));
+
+  let last = (0..lanes)
+  .map(|lane| memory.0[lane * lane_length + lane_length - 1])
+  .fold([0u64; BLOCK_WORDS], |last, block| array::from_fn(|index| last[index] ^ block[index]));
+  hash_long(tag_length, &block_bytes(&last).0)
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{
+      argon2id,
+      Cost,
+    },
+    crate::hex,
+  };
+
+  /// The test vector of Argon2id of RFC 9106, section 5.3, is reproduced.
+  #[test]
+  fn rfc_9106_vector() {
+    let cost = Cost { memory: 32, passes: 3, lanes: 4 };
+    let tag = argon2id(&[0x01; 32], &[0x02; 16], &[0x03; 8], &[0x04; 12], cost, 32);
+    assert_eq!(hex::encode(&tag), "0d640df58d78766c08c037a34a8b53c9d01ef0452d75b65eb52520e96b01e659");
+  }
+
+  /// The cost is given as memory, passes and lanes, round trips through its name and is refused below the minimums.
+  #[test]
+  fn cost_is_parsed() {
+    assert_eq!("32,3,4".parse(), Ok(Cost { memory: 32, passes: 3, lanes: 4 }));
+    assert_eq!(Cost::default().to_string().parse(), Ok(Cost::default()));
+    ["31,3,4", "32,0,4", "32,3,0", "32,3", "32,3,4,5", "32,3,x"]
+    .into_iter()
+    .for_each(|cost| assert!(cost.parse::<Cost>().is_err(), "{}", cost));
+  }
+}
@@ -0,0 +1,49 @@
+extern crate test;
+
+use {
+  crate::{
+    try_calculate_all_pins_with_algorithm,
+    Algorithm,
+    Parameters,
+    SerialNumbers,
+    TEST_SERIAL_NUMBERS,
+  },
+  test::{
+    black_box,
+    Bencher,
+  },
+};
+
+/// Derive all PINs of the test serial numbers with the algorithm and parameters, once per iteration.
+/// The serial numbers are given directly, so the card readers and the file system are not measured.
+fn derive(bencher: &mut Bencher, algorithm: Algorithm, parameters: Parameters) {
+  let parameters = Parameters { algorithm, ..parameters };
+  let serial_numbers = SerialNumbers::new(TEST_SERIAL_NUMBERS.to_vec(), parameters).unwrap();
+  bencher.iter(|| try_calculate_all_pins_with_algorithm(black_box(&serial_numbers), parameters, algorithm).unwrap());
+}
+
+#[bench]
+fn default_pin(bencher: &mut Bencher) {
+  derive(bencher, Algorithm::DefaultPin, Parameters::default());
+}
+
+#[bench]
+fn double_sha512(bencher: &mut Bencher) {
+  derive(bencher, Algorithm::DoubleSHA512, Parameters::default());
+}
+
+#[bench]
+fn xor_per_serial_sha512(bencher: &mut Bencher) {
+  derive(bencher, Algorithm::XorPerSerialSha512, Parameters::default());
+}
+
+#[bench]
+fn argon2id(bencher: &mut Bencher) {
+  derive(bencher, Algorithm::Argon2id, Parameters::default());
+}
+
+/// The cost of stretching the hash with `--hash-iterations`, on top of `double_sha512`.
+#[bench]
+fn double_sha512_stretched(bencher: &mut Bencher) {
+  derive(bencher, Algorithm::DoubleSHA512, Parameters { hash_iterations: 10_000, ..Parameters::default() });
+}
@@ -0,0 +1,112 @@
+use std::array;
+
+/// Size of the blocks BLAKE2b processes.
+const BLOCK_SIZE: usize = 0x80;
+
+/// The largest digest BLAKE2b yields.
+pub const MAX_OUTPUT_LENGTH: usize = 0x40;
+
+/// The initialisation vector, the same as the one of SHA-512.
+const IV: [u64; 8] = [
+  0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+  0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+/// The permutations of the message words of each round, the last two rounds repeat the first two.
+const SIGMA: [[usize; 16]; 10] = [
+  [  0,  1,  2,  3,  4,  5,  6,  7,  8,  9, 10, 11, 12, 13, 14, 15 ],
+  [ 14, 10,  4,  8,  9, 15, 13,  6,  1, 12,  0,  2, 11,  7,  5,  3 ],
+  [ 11,  8, 12,  0,  5,  2, 15, 13, 10, 14,  3,  6,  7,  1,  9,  4 ],
+  [  7,  9,  3,  1, 13, 12, 11, 14,  2,  6,  5, 10,  4,  0, 15,  8 ],
+  [  9,  0,  5,  7,  2,  4, 10, 15, 14,  1, 11, 12,  6,  8,  3, 13 ],
+  [  2, 12,  6, 10,  0, 11,  8,  3,  4, 13,  7,  5, 15, 14,  1,  9 ],
+  [ 12,  5,  1, 15, 14, 13,  4, 10,  0,  7,  6,  3,  9,  2,  8, 11 ],
+  [ 13, 11,  7, 14, 12,  1,  3,  9,  5,  0, 15,  4,  8,  6,  2, 10 ],
+  [  6, 15, 14,  9, 11,  3,  0,  8, 12,  2, 13,  7,  1,  4, 10,  5 ],
+  [ 10,  2,  8,  4,  7,  6,  1,  5, 15, 11,  9, 14,  3, 12, 13,  0 ],
+];
+
+/// Mix two message words into four words of the working vector.
+fn mix(vector: &mut [u64; 16], [a, b, c, d]: [usize; 4], x: u64, y: u64) {
+  vector[a] = vector[a].wrapping_add(vector[b]).wrapping_add(x);
+  vector[d] = (vector[d] ^ vector[a]).rotate_right(32);
+  vector[c] = vector[c].wrapping_add(vector[d]);
+  vector[b] = (vector[b] ^ vector[c]).rotate_right(24);
+  vector[a] = vector[a].wrapping_add(vector[b]).wrapping_add(y);
+  vector[d] = (vector[d] ^ vector[a]).rotate_right(16);
+  vector[c] = vector[c].wrapping_add(vector[d]);
+  vector[b] = (vector[b] ^ vector[c]).rotate_right(63);
+}
+
+/// Compress a block into the state, `counter` bytes in, the last block with `last`.
+fn compress(state: &mut [u64; 8], block: &[u8; BLOCK_SIZE], counter: u128, last: bool) {
+  let words: [u64; 16] = array::from_fn(|index|
+    u64::from_le_bytes(block[8 * index..8 * index + 8].try_into().unwrap())
+  );
+  let mut vector = [0u64; 16];
+  vector[..8].copy_from_slice(state);
+  vector[8..].copy_from_slice(&IV);
+  vector[12] ^= counter as u64;
+  vector[13] ^= (counter >> 64) as u64;
+  if last {
+    vector[14] = !vector[14];
+  }
+  (0..12)
+  .map(|round| &SIGMA[round % SIGMA.len()])
+  .for_each(|sigma| {
+    mix(&mut vector, [0, 4,  8, 12], words[sigma[ 0]], words[sigma[ 1]]);
+    mix(&mut vector, [1, 5,  9, 13], words[sigma[ 2]], words[sigma[ 3]]);
+    mix(&mut vector, [2, 6, 10, 14], words[sigma[ 4]], words[sigma[ 5]]);
+    mix(&mut vector, [3, 7, 11, 15], words[sigma[ 6]], words[sigma[ 7]]);
+    mix(&mut vector, [0, 5, 10, 15], words[sigma[ 8]], words[sigma[ 9]]);
+    mix(&mut vector, [1, 6, 11, 12], words[sigma[10]], words[sigma[11]]);
+    mix(&mut vector, [2, 7,  8, 13], words[sigma[12]], words[sigma[13]]);
+    mix(&mut vector, [3, 4,  9, 14], words[sigma[14]], words[sigma[15]]);
+  });
+  (0..8).for_each(|index| state[index] ^= vector[index] ^ vector[index + 8]);
+}
+
+/// Calculate the unkeyed BLAKE2b digest of `output_length` bytes, 1 to 64, of the message, see RFC 7693.
+/// Only used by Argon2, see `argon2::argon2id`, which hashes whole messages at once.
+pub fn blake2b(output_length: usize, message: &[u8]) -> Vec<u8> {
+  assert!((1..=MAX_OUTPUT_LENGTH).contains(&output_length));
+  let mut state = IV;
+  state[0] ^= 0x0101_0000 ^ output_length as u64;
+  let blocks = message.len().div_ceil(BLOCK_SIZE).max(1);
+  (0..blocks)
+  .for_each(|index| {
+    let chunk = &message[index * BLOCK_SIZE..message.len().min((index + 1) * BLOCK_SIZE)];
+    let mut block = [0u8; BLOCK_SIZE];
+    block[..chunk.len()].copy_from_slice(chunk);
+    compress(&mut state, &block, (index * BLOCK_SIZE + chunk.len()) as u128, index + 1 == blocks);
+  });
+  state
+  .iter()
+  .flat_map(|word| word.to_le_bytes())
+  .take(output_length)
+  .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::blake2b,
+    crate::hex,
+  };
+
+  /// The digests of RFC 7693, appendix A, and of the empty message are reproduced, also across block boundaries.
+  #[test]
+  fn known_answers() {
+    assert_eq!(
+      hex::encode(&blake2b(64, b"abc")),
+      "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d17d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+    );
+    assert_eq!(
+      hex::encode(&blake2b(64, b"")),
+      "786a02f742015903c6c6fd852552d272912f4740e15847618a86e217f71f5419d25e1031afee585313896444934eb04b903a685b1448b755d56f701afe9be2ce"
+    );
+    assert_eq!(hex::encode(&blake2b(32, b"abc")), "bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319");
+    assert!(hex::encode(&blake2b(64, &[0x61; 0x81])).starts_with("55e6e0eb418149a8af92fd9ddc992547"));
+    assert!(hex::encode(&blake2b(64, &[0x61; 0x100])).starts_with("0eee13d0c73a2710c5015a8b4be0a161"));
+  }
+}
@@ -0,0 +1,286 @@
+use {
+  crate::{
+    hex,
+    hmac::hmac_sha256,
+    memory::Wiped,
+    source::SerialSource,
+    verify::constant_time_eq,
+    Error,
+    SerialNumber,
+  },
+  sha2::{
+    Digest,
+    Sha256,
+  },
+  std::{
+    fs::{
+      File,
+      OpenOptions,
+      Permissions,
+    },
+    io::{
+      Read,
+      Write,
+    },
+    os::unix::fs::{
+      OpenOptionsExt,
+      PermissionsExt,
+    },
+    path::PathBuf,
+    str,
+    time::{
+      Duration,
+      SystemTime,
+      UNIX_EPOCH,
+    },
+  },
+};
+
+/// The serial numbers of another source, cached in a file so slow card readers are not read on every invocation,
+///   see `--use-cache`.
+/// The file consists of a single line
+/// ```text
+/// konnektor-serials-v2 <origin> <timestamp> <serial number>,<serial number>,… <mac>
+/// ```
+///   where the origin is the hex encoded SHA256 of the description of the source, see `origin`,
+///   the timestamp is the UNIX time in seconds the serial numbers were read, they are hex encoded
+///   and the MAC is the hex encoded HMAC-SHA256 of everything before the last space, keyed with `--key`.
+/// A cache older than the TTL, of another version or of another source is ignored and replaced by reading the source again,
+///   but one with a wrong MAC is refused, so nobody without the key can inject serial numbers.
+/// The file must be accessible by the owner only, otherwise it is ignored and its permission fixed when replaced.
+pub struct CachedSource<'a> {
+  pub source: Box<dyn SerialSource + 'a>,
+  pub origin: String,
+  pub file:   PathBuf,
+  pub key:    &'a [u8],
+  pub ttl:    Duration,
+}
+
+impl CachedSource<'_> {
+  /// The first field of the cache, changed whenever its format changes.
+  const VERSION:          &'static str  = "konnektor-serials-v2";
+  /// Age of the cache above which the serial numbers are read again, unless chosen with `--cache-ttl`.
+  pub const DEFAULT_TTL:  Duration      = Duration::from_secs(300);
+  /// Size of the cache above which it is refused, far more than the serial numbers of all card readers take.
+  const MAX_SIZE:         u64           = 0x10000;
+
+  /// Identify the source of the serial numbers by the hash of its description,
+  ///   so the cache of one source is never taken for the serial numbers of another one.
+  fn origin(&self) -> String {
+    hex::encode(&Sha256::digest(self.origin.as_bytes()))
+  }
+
+  /// Encode and authenticate the serial numbers read at the UNIX time `now`.
+  fn encode(&self, serial_numbers: &[SerialNumber], now: u64) -> String {
+    let authenticated = format!(
+      "{} {} {} {}",
+      Self::VERSION,
+      self.origin(),
+      now,
+      serial_numbers
+      .iter()
+      .map(|serial_number| hex::encode(&serial_number.0))
+      .collect::<Vec<String>>()
+      .join(","),
+    );
+    let mac = hex::encode(&hmac_sha256(self.key, authenticated.as_bytes()));
+    format!("{} {}\n", authenticated, mac)
+  }
+
+  /// Authenticate a cache at the UNIX time `now` and decode the serial numbers, unless it is stale.
+  pub fn decode(&self, contents: &str, now: u64) -> Result<Option<Vec<SerialNumber>>, Error> {
+    let (authenticated, mac) = contents.trim_end().rsplit_once(' ').ok_or(Error::InvalidCache)?;
+    let (version, fields) = authenticated.split_once(' ').ok_or(Error::InvalidCache)?;
+    if version != Self::VERSION {
+      eprintln!("Cache {} has version {}, reading the serial numbers again", self.file.display(), version);
+      return Ok(None);
+    }
+
+    let mac = hex::decode(mac).ok_or(Error::InvalidCache)?;
+    constant_time_eq(&hmac_sha256(self.key, authenticated.as_bytes()), &mac)
+    .then_some(())
+    .ok_or(Error::InvalidCache)
+    .inspect_err(|_| eprintln!("Cache {} has an invalid MAC, it might have been tampered with", self.file.display()))?;
+
+    let (origin, fields) = fields.split_once(' ').ok_or(Error::InvalidCache)?;
+    if origin != self.origin() {
+      eprintln!("Cache {} belongs to another source, reading the serial numbers again", self.file.display());
+      return Ok(None);
+    }
+
+    let (timestamp, serial_numbers) = fields.split_once(' ').ok_or(Error::InvalidCache)?;
+    let timestamp: u64 = timestamp.parse().map_err(|_| Error::InvalidCache)?;
+    if timestamp > now || now - timestamp > self.ttl.as_secs() {
+      eprintln!("Cache {} is stale, reading the serial numbers again", self.file.display());
+      return Ok(None);
+    }
+
+    serial_numbers
+    .split(',')
+    .map(|serial_number|
+      hex::decode(serial_number)
+      .map(Wiped)
+      .and_then(|bytes| bytes.0.as_slice().try_into().ok())
+      .map(SerialNumber)
+    )
+    .collect::<Option<Vec<SerialNumber>>>()
+    .ok_or(Error::InvalidCache)
+    .inspect_err(|_| eprintln!("Cache {} contains an invalid serial number", self.file.display()))
+    .map(Some)
+  }
+
+  /// Read the cache, if there is one the owner only can access, and zero its contents afterwards.
+  /// The contents are read into a buffer of the size of the file, so no reallocation leaves a copy behind,
+  ///   as do the bytes of each decoded serial number.
+  fn load(&self, now: u64) -> Result<Option<Vec<SerialNumber>>, Error> {
+    let Ok((mut file, metadata)) = File::open(&self.file)
+    .and_then(|file| file.metadata().map(|metadata| (file, metadata)))
+    else {
+      return Ok(None);
+    };
+    if metadata.permissions().mode() & 0o077 != 0 {
+      eprintln!("Warning: Cache {} can be accessed by others, reading the serial numbers again", self.file.display());
+      return Ok(None);
+    }
+    if metadata.len() > Self::MAX_SIZE {
+      eprintln!("Cache {} has {} bytes, more than {}", self.file.display(), metadata.len(), Self::MAX_SIZE);
+      return Err(Error::InvalidCache);
+    }
+    let mut contents = Wiped(vec![0; metadata.len() as usize]);
+    if file.read_exact(&mut contents.0).is_err() {
+      return Ok(None);
+    }
+    str::from_utf8(&contents.0)
+    .map_err(|_| Error::InvalidCache)
+    .and_then(|contents| self.decode(contents, now))
+  }
+
+  /// Write the cache, readable by the owner only, also if the file existed with another permission before.
+  /// Failing to do so is not fatal, the serial numbers are just read again next time.
+  fn store(&self, serial_numbers: &[SerialNumber], now: u64) {
+    OpenOptions::new()
+    .write(true)
+    .create(true)
+    .truncate(true)
+    .mode(0o600)
+    .open(&self.file)
+    .and_then(|file| file.set_permissions(Permissions::from_mode(0o600)).map(|_| file))
+    .and_then(|mut file| file.write_all(self.encode(serial_numbers, now).as_bytes()))
+    .unwrap_or_else(|error| eprintln!("Warning: Cannot write cache {}: {}", self.file.display(), error));
+  }
+}
+
+impl SerialSource for CachedSource<'_> {
+  fn read_serials(&self) -> Result<Vec<SerialNumber>, Error> {
+    let now = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |duration| duration.as_secs());
+    match self.load(now)? {
+      Some(serial_numbers) => Ok(serial_numbers),
+      None => {
+        let serial_numbers = self.source.read_serials()?;
+        self.store(&serial_numbers, now);
+        Ok(serial_numbers)
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::CachedSource,
+    crate::{
+      source::{
+        FixedSource,
+        SerialSource,
+      },
+      hex,
+      Error,
+      SerialNumber,
+    },
+    std::{
+      env,
+      fs::{
+        self,
+        Permissions,
+      },
+      os::unix::fs::PermissionsExt,
+      process,
+    },
+  };
+
+  const KEY: &[u8] = b"0123456789abcdef";
+
+  /// Get a cache in a temporary `file` of a fixed source, described as `origin`.
+  fn cache(origin: &str, file: &str) -> CachedSource<'static> {
+    CachedSource {
+      source: Box::new(FixedSource(vec![SerialNumber(*b"23421337")])),
+      origin: origin.to_owned(),
+      file:   env::temp_dir().join(format!("{}-{}", file, process::id())),
+      key:    KEY,
+      ttl:    CachedSource::DEFAULT_TTL,
+    }
+  }
+
+  /// The cache of one source is not taken for the serial numbers of another one.
+  #[test]
+  fn cache_is_bound_to_origin() {
+    let serial_numbers = [SerialNumber(*b"23421337")];
+    let contents = cache("manifest=a", "origin").encode(&serial_numbers, 1000);
+    assert_eq!(cache("manifest=a", "origin").decode(&contents, 1000).unwrap(), Some(serial_numbers.to_vec()));
+    assert_eq!(cache("manifest=b", "origin").decode(&contents, 1000).unwrap(), None);
+  }
+
+  /// A cache older than the TTL or from the future is ignored and replaced by reading the source again.
+  #[test]
+  fn stale_cache_is_read_again() {
+    let cache = cache("manifest=a", "stale");
+    let serial_numbers = [SerialNumber(*b"meowmeow")];
+    let contents = cache.encode(&serial_numbers, 1000);
+    assert_eq!(cache.decode(&contents, 1300).unwrap(), Some(serial_numbers.to_vec()));
+    assert_eq!(cache.decode(&contents, 1301).unwrap(), None);
+    assert_eq!(cache.decode(&contents, 999).unwrap(), None);
+
+    cache.store(&serial_numbers, 0);
+    let read = cache.read_serials();
+    let contents = fs::read_to_string(&cache.file).unwrap();
+    fs::remove_file(&cache.file).unwrap();
+    assert_eq!(read, Ok(vec![SerialNumber(*b"23421337")]));
+    assert!(contents.contains(&hex::encode(b"23421337")), "{}", contents);
+  }
+
+  /// A cache with injected serial numbers or a forged MAC is refused, not just ignored.
+  #[test]
+  fn tampered_cache_is_refused() {
+    let cache = cache("manifest=a", "tampered");
+    let contents = cache.encode(&[SerialNumber(*b"meowmeow")], 1000);
+    let injected = contents.replace(&hex::encode(b"meowmeow"), &hex::encode(b"*squeak*"));
+    let forged = format!("{}{}\n", &contents[..contents.len() - 2], if contents.ends_with("0\n") { '1' } else { '0' });
+    [injected, forged, contents[..contents.len() / 2].to_owned()]
+    .into_iter()
+    .for_each(|contents| assert_eq!(cache.decode(&contents, 1000), Err(Error::InvalidCache), "{}", contents));
+
+    cache.store(&[SerialNumber(*b"meowmeow")], 1000);
+    let contents = fs::read_to_string(&cache.file).unwrap();
+    fs::write(&cache.file, contents.replace(&hex::encode(b"meowmeow"), &hex::encode(b"*squeak*"))).unwrap();
+    let loaded = cache.load(1000);
+    fs::remove_file(&cache.file).unwrap();
+    assert_eq!(loaded, Err(Error::InvalidCache));
+  }
+
+  /// A cache others can access is not read, and its permission is fixed when it is replaced.
+  #[test]
+  fn cache_is_private() {
+    let cache = cache("manifest=a", "private");
+    cache.store(&[SerialNumber(*b"meowmeow")], 1000);
+    fs::set_permissions(&cache.file, Permissions::from_mode(0o644)).unwrap();
+    assert_eq!(cache.load(1000).unwrap(), None);
+    cache.store(&[SerialNumber(*b"meowmeow")], 1000);
+    let mode = fs::metadata(&cache.file).unwrap().permissions().mode();
+    let loaded = cache.load(1000);
+    fs::remove_file(&cache.file).unwrap();
+    assert_eq!(mode & 0o777, 0o600);
+    assert_eq!(loaded.unwrap(), Some(vec![SerialNumber(*b"meowmeow")]));
+  }
+}
@@ -0,0 +1,156 @@
+use std::fmt::{
+  Debug,
+  Display,
+  Formatter,
+  Result as FormatResult,
+};
+
+/// Everything that can go wrong while calculating the PINs.
+/// Details are reported on stderr where the error occurs, the variants only tell what failed,
+///   except for `InvalidConfig`, which carries all problems to report them together.
+#[derive(Clone, Eq, PartialEq)]
+pub enum Error {
+  /// The command line arguments are invalid.
+  InvalidArguments,
+  /// The options do not work together, for each of these reasons, see `Options::check`.
+  InvalidConfig { problems: Vec<String> },
+  /// The tool runs as root, but was told not to, see `--no-root`.
+  RunningAsRoot,
+  /// The connection to the syslog daemon cannot be established, see `--syslog`.
+  CannotConnectToSyslog,
+  /// A file given on the command line cannot be read.
+  CannotReadFile,
+  /// The manifest is not a valid list of serial numbers.
+  InvalidManifest,
+  /// A card reader is unplugged or disabled.
+  CardReaderUnavailable,
+  /// The path of a card reader is a symbolic link loop or no regular file.
+  BadReaderPath,
+  /// A card reader cannot be opened.
+  CannotOpenCardReader,
+  /// A card reader cannot be opened, because the user lacks the permission to.
+  CardReaderPermissionDenied,
+  /// No card reader matches the pattern given on the command line.
+  NoMatchingCardReaders,
+  /// The ID of the host is missing or malformed, see `--bind-machine-id`.
+  CannotReadMachineId,
+  /// The serial number cannot be read from a card reader.
+  CannotReadSerialNumber,
+  /// Some of the card readers failed, while others could be read.
+  SomeReadersFailed,
+  /// The serial numbers cannot be received from the network.
+  CannotReceiveSerialNumbers,
+  /// The cache of serial numbers is malformed or not authentic, see `--use-cache`.
+  InvalidCache,
+  /// A message with serial numbers is malformed.
+  InvalidMessage,
+  /// A message with serial numbers has an invalid MAC.
+  UnauthenticatedMessage,
+  /// A message with serial numbers does not answer the challenge just sent.
+  ReplayedMessage,
+  /// There are no serial numbers to derive the PINs from.
+  NoSerials,
+  /// Fewer distinct serial numbers were read than required, see `--min-distinct-serials`.
+  InsufficientDistinctSerials,
+  /// A serial number was read which must not be used, e.g. one for testing purposes.
+  ForbiddenSerial,
+  /// A group refers to a card reader that does not exist.
+  ReaderIndexOutOfRange,
+  /// The PINs of some of the cards could not be calculated, see `--all-or-nothing`.
+  SomeGroupsFailed,
+  /// The randomness is implausibly skewed, see `--check-entropy`.
+  WeakRandomness,
+  /// The seed given with `--seed-from-file` is shorter than the randomness it replaces.
+  SeedTooShort,
+  /// The randomness cannot be locked into memory, see `--require-mlock`.
+  CannotLockMemory,
+  /// The randomness ran out before all PINs were derived.
+  EndOfRandomness,
+  /// The index of a PIN is out of range.
+  PinIndexOutOfRange,
+  /// A number does not fit into the digits of a PIN.
+  InvalidPinNumber,
+  /// The PINs would be written to a terminal, but must not, see `--require-pipe`.
+  StdoutIsTerminal,
+  /// The PINs cannot be written to the output.
+  CannotWritePins,
+  /// The summary of the run cannot be written, see `--summary-json`.
+  CannotWriteSummary,
+  /// The verification file contains a malformed entry.
+  MalformedVerificationFile,
+  /// At least one of the PINs to verify did not match.
+  VerificationFailed,
+  /// The PINs of the known answer test differ from the expected ones.
+  SelfTestFailed,
+}
+
+impl Error {
+  /// Get the exit status of the tool for this error, see the README:
+  ///   2 if only some of the card readers failed, as the others could be read, and 1 for any other failure.
+  pub fn exit_code(&self) -> u8 {
+    match self {
+      Self::SomeReadersFailed => 2,
+      _                       => 1,
+    }
+  }
+}
+
+impl Display for Error {
+  fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+    formatter.write_str(
+      match self {
+        Self::InvalidArguments            => "Invalid command line arguments",
+        Self::InvalidConfig { .. }        => "Invalid combination of options:",
+        Self::RunningAsRoot               => "Running as root, refusing to calculate PINs",
+        Self::CannotConnectToSyslog       => "Cannot connect to syslog",
+        Self::CannotReadFile              => "Cannot read file",
+        Self::InvalidManifest             => "Invalid manifest",
+        Self::CardReaderUnavailable       => "Smart card reader is unplugged or disabled",
+        Self::BadReaderPath               => "Path of smart card reader is a link loop or no regular file",
+        Self::CannotOpenCardReader        => "Cannot open smart card readers",
+        Self::CardReaderPermissionDenied  => "Permission denied to open smart card reader, add the user to the group owning it or adjust the udev rules",
+        Self::NoMatchingCardReaders       => "No matching smart card readers",
+        Self::CannotReadMachineId         => "Cannot read the machine ID",
+        Self::CannotReadSerialNumber      => "Cannot read serial number from card reader",
+        Self::SomeReadersFailed           => "Cannot read the serial numbers of some card readers",
+        Self::CannotReceiveSerialNumbers  => "Cannot receive serial numbers",
+        Self::InvalidCache                => "Invalid or tampered cache of serial numbers",
+        Self::InvalidMessage              => "Invalid message with serial numbers",
+        Self::UnauthenticatedMessage      => "Message with serial numbers is not authentic",
+        Self::ReplayedMessage             => "Message with serial numbers was replayed",
+        Self::NoSerials                   => "No serial numbers",
+        Self::InsufficientDistinctSerials => "Too few distinct serial numbers, refusing to calculate PINs",
+        Self::ForbiddenSerial             => "Forbidden serial number, refusing to calculate PINs",
+        Self::ReaderIndexOutOfRange       => "Card reader index out of range",
+        Self::SomeGroupsFailed            => "Cannot calculate the PINs of some cards",
+        Self::WeakRandomness              => "Randomness is implausibly skewed, refusing to calculate PINs",
+        Self::SeedTooShort                => "Seed is shorter than the randomness, refusing to calculate PINs",
+        Self::CannotLockMemory            => "Cannot lock the randomness into memory, refusing to calculate PINs",
+        Self::EndOfRandomness             => "End of randomness, calculate fewer PINs or extend it with --auto-extend",
+        Self::PinIndexOutOfRange          => "PIN index out of range",
+        Self::InvalidPinNumber            => "Number does not fit into a PIN",
+        Self::StdoutIsTerminal            => "Standard output is a terminal, refusing to print PINs",
+        Self::CannotWritePins             => "Cannot write PINs",
+        Self::CannotWriteSummary          => "Cannot write summary",
+        Self::MalformedVerificationFile   => "Malformed verification file",
+        Self::VerificationFailed          => "PIN verification failed",
+        Self::SelfTestFailed              => "Self test failed, the PINs on this platform differ",
+      }
+    )?;
+    match self {
+      Self::InvalidConfig { problems }
+      =>  problems
+          .iter()
+          .try_for_each(|problem| write!(formatter, "\n  {}", problem)),
+      _
+      =>  Ok(()),
+    }
+  }
+}
+
+/// An error is printed with `Debug`, e.g. by `unwrap`, so it should read like `Display`.
+impl Debug for Error {
+  fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+    Display::fmt(self, formatter)
+  }
+}
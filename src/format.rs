@@ -0,0 +1,1004 @@
+use {
+  crate::{
+    hex,
+    template::{
+      Template,
+      TemplateFormatter,
+    },
+    Algorithm,
+    Error,
+    Parameters,
+    Pin,
+    Puk,
+    SerialNumber,
+    SerialNumbers,
+  },
+  sha2::{
+    Digest,
+    Sha256,
+  },
+  std::{
+    cell::Cell,
+    env,
+    fmt::Display,
+    iter,
+    io::{
+      self,
+      IsTerminal,
+      Result as IoResult,
+      Write,
+    },
+    str::FromStr,
+  },
+};
+
+/// What is known about how the PINs were derived, without revealing any secrets,
+///   except the serial numbers, which only `TableFormatter` shows.
+#[derive(Clone, Debug)]
+pub struct DerivationMeta {
+  /// The algorithm used to derive the PINs.
+  pub algorithm:  Algorithm,
+  /// The number of PINs derived.
+  pub count:      usize,
+  /// The number of digits of each PIN.
+  pub length:     u8,
+  /// The card the PINs belong to, if they were derived for several cards, see `--group`.
+  pub card:       Option<usize>,
+  /// The fingerprint of the serial numbers the PINs were derived from, see `serials_fingerprint`.
+  pub serials_fp: Option<[u8; 32]>,
+  /// The serial numbers themselves, only shown by `TableFormatter`, masked like the PINs.
+  pub serials:    Vec<SerialNumber>,
+  /// Each option the PINs depend on with its value, see `Parameters::derivation_inputs`.
+  pub inputs:     Vec<(&'static str, String)>,
+}
+
+impl DerivationMeta {
+  /// Describe a derivation of `count` PINs with these parameters.
+  pub(crate) fn new(parameters: Parameters, count: usize) -> Self {
+    Self {
+      algorithm:  parameters.algorithm,
+      count,
+      length:     parameters.pin_length(),
+      card:       None,
+      serials_fp: None,
+      serials:    Vec::new(),
+      inputs:     parameters
+                  .derivation_inputs()
+                  .into_iter()
+                  .map(|input| (input.option, input.value))
+                  .collect(),
+    }
+  }
+
+  /// Describe a derivation from these serial numbers, by their fingerprint.
+  pub fn with_serials(self, serials: &SerialNumbers) -> Self {
+    Self {
+      serials_fp: Some(serials.fingerprint()),
+      serials:    serials.iter().copied().collect(),
+      ..self
+    }
+  }
+
+  /// Describe the derivation of the PINs of one of several cards.
+  pub fn with_card(self, card: usize) -> Self {
+    Self { card: Some(card), ..self }
+  }
+}
+
+/// Identify the parameters of a derivation without revealing the PINs or serial numbers:
+///   A hash over the version of this tool, every option the PINs depend on with its value,
+///   the number of PINs and the fingerprint of the serial numbers.
+/// Auditors can tell whether two runs used the same parameters and serial numbers,
+///   but as the fingerprint does not depend on their order, this does not prove identical PINs.
+pub fn provenance_hash(meta: &DerivationMeta, serials_fp: &[u8; 32]) -> String {
+  let digest = Sha256::new()
+  .chain_update(env!("CARGO_PKG_NAME"))
+  .chain_update([0])
+  .chain_update(env!("CARGO_PKG_VERSION"))
+  .chain_update([0])
+  .chain_update(
+    meta
+    .inputs
+    .iter()
+    .map(|(option, value)| format!("{}={}\0", option, value))
+    .collect::<String>()
+  )
+  .chain_update((meta.count as u64).to_be_bytes())
+  .chain_update(serials_fp)
+  .finalize();
+  hex::encode(&digest)
+}
+
+/// Get the JSON member of the provenance, if the serial numbers are known.
+fn json_provenance_member(meta: &DerivationMeta) -> String {
+  meta
+  .serials_fp
+  .map_or_else(String::new, |serials_fp| format!("\"provenance\":\"{}\",", provenance_hash(meta, &serials_fp)))
+}
+
+/// How much of each PIN is shown.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Redaction {
+  /// Show all digits.
+  #[default]
+  None,
+  /// Show only this many of the last digits and mask the others with `*`.
+  KeepLast(usize),
+  /// Show only the first and the last digit, with a `.` for each digit between, see `--preview`.
+  Preview,
+}
+
+impl Redaction {
+  /// Get the digits of a PIN as a string, masked according to the redaction.
+  pub fn apply(self, pin: &Pin) -> String {
+    match self {
+      Self::Preview => pin.preview(),
+      _             => self.mask(pin.to_numeric_string()),
+    }
+  }
+
+  /// Get the digits of a PUK as a string, masked according to the redaction.
+  pub fn apply_puk(self, puk: &Puk) -> String {
+    self.mask(puk.to_numeric_string())
+  }
+
+  /// Get a serial number as a string, masked according to the redaction, see `--by-serial`.
+  pub fn apply_serial(self, serial_number: &SerialNumber) -> String {
+    self.mask(String::from_utf8_lossy(&serial_number.0).into_owned())
+  }
+
+  /// Mask the characters according to the redaction.
+  /// Serial numbers may contain characters of several bytes, so they are counted as characters.
+  fn mask(self, digits: String) -> String {
+    match self {
+      Self::None => digits,
+      Self::KeepLast(count)
+      =>  {
+            let masked = digits.chars().count().saturating_sub(count);
+            digits
+            .chars()
+            .enumerate()
+            .map(|(position, digit)| if position < masked { '*' } else { digit })
+            .collect()
+          },
+      Self::Preview => preview(&digits),
+    }
+  }
+}
+
+/// Keep only the first and the last character and replace each one between with a `.`,
+///   e.g. `1..........3` for the default PIN.
+pub fn preview(digits: &str) -> String {
+  let last = digits.chars().count().saturating_sub(1);
+  digits
+  .chars()
+  .enumerate()
+  .map(|(position, digit)| if position == 0 || position == last { digit } else { '.' })
+  .collect()
+}
+
+/// Quote a string for JSON, escaping quotes, backslashes and control characters, e.g. the line feeds of an error.
+pub fn json_string(value: &str) -> String {
+  let escaped: String = value
+  .chars()
+  .map(|char|
+    match char {
+      '"'   => "\\\"".to_owned(),
+      '\\'  => "\\\\".to_owned(),
+      '\n'  => "\\n".to_owned(),
+      char if char.is_control()
+      =>  format!("\\u{:04x}", u32::from(char)),
+      char  => char.to_string(),
+    }
+  )
+  .collect();
+  format!("\"{}\"", escaped)
+}
+
+/// Options shared by all formatters.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderOptions {
+  pub redaction:    Redaction,
+  /// Highlight with ANSI escape sequences, see `Color`.
+  pub color:        bool,
+  /// Pad the indices of the text output with zeros to this width,
+  ///   instead of the width of the largest index.
+  pub index_width:  Option<usize>,
+  /// Render the PINs last to first, keeping their indices, see `Reversed`.
+  pub reverse:      bool,
+  /// Render the PINs in ascending order of their digits, keeping their indices, see `SortedByPin`.
+  pub sort_by_pin:  bool,
+}
+
+/// Control whether the human readable output is colored.
+pub struct Color;
+
+impl Color {
+  /// Decide whether output to stdout may be colored:
+  ///   Not if disabled with `--no-color` or with a non-empty `NO_COLOR`, see <https://no-color.org>,
+  ///   and not if stdout is no terminal, so output captured by scripts never contains escape sequences.
+  pub fn enabled(disabled: bool) -> bool {
+    !disabled
+    && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+    && io::stdout().is_terminal()
+  }
+
+  /// Highlight some text in bold, if coloring is enabled.
+  pub fn bold(text: impl Display, enabled: bool) -> String {
+    if enabled {
+      format!("\x1b[1m{}\x1b[0m", text)
+    } else {
+      text.to_string()
+    }
+  }
+}
+
+/// A writer which holds back a trailing line feed and leaves it out at the end, see `--no-trailing-newline`.
+/// All formatters end each line with exactly one line feed and never write a byte order mark,
+///   so with this writer the output ends with the last digit instead.
+pub struct WithoutTrailingNewline<W: Write> {
+  inner:    W,
+  pending:  bool,
+}
+
+impl<W: Write> WithoutTrailingNewline<W> {
+  pub fn new(inner: W) -> Self {
+    Self { inner, pending: false }
+  }
+}
+
+impl<W: Write> Write for WithoutTrailingNewline<W> {
+  fn write(&mut self, buffer: &[u8]) -> IoResult<usize> {
+    if buffer.is_empty() {
+      return Ok(0);
+    }
+    if self.pending {
+      self.inner.write_all(b"\n")?;
+    }
+    let (body, pending) = match buffer.strip_suffix(b"\n") {
+      Some(body) => (body, true),
+      None       => (buffer, false),
+    };
+    self.inner.write_all(body)?;
+    self.pending = pending;
+    Ok(buffer.len())
+  }
+
+  fn flush(&mut self) -> IoResult<()> {
+    self.inner.flush()
+  }
+}
+
+/// A PIN together with its index, which is kept when only some of the PINs are rendered.
+pub type IndexedPin = (usize, Pin);
+
+/// Render a list of PINs in a specific output format.
+pub trait PinFormatter {
+  /// Write all PINs and, if the format has a place for it, their metadata.
+  /// The PUKs are either empty or one for each PIN, in the same order.
+  fn render(&self, pins: &[IndexedPin], puks: &[Puk], meta: &DerivationMeta, writer: &mut dyn Write) -> IoResult<()>;
+}
+
+/// The output formats selectable with `--format`, or a template given with `--template`.
+#[derive(Clone, Debug, Default)]
+pub enum Format {
+  #[default]
+  Text,
+  Json,
+  NdJson,
+  Csv,
+  Base64,
+  Compact,
+  Apdu,
+  Table,
+  RawDigits,
+  Template(Template),
+}
+
+impl Format {
+  /// Whether the output is binary, so it must not be written to a terminal, see `check_pipe`.
+  pub fn is_binary(&self) -> bool {
+    matches!(self, Self::RawDigits)
+  }
+
+  /// Get the formatter of this output format.
+  pub fn formatter(&self, options: RenderOptions) -> Box<dyn PinFormatter> {
+    let formatter: Box<dyn PinFormatter> = match self {
+      Self::Text    => Box::new(TextFormatter(options)),
+      Self::Json    => Box::new(JsonFormatter(options)),
+      Self::NdJson  => Box::new(NdJsonFormatter(options)),
+      Self::Csv     => Box::new(CsvFormatter(options, Cell::new(false))),
+      Self::Base64  => Box::new(Base64Formatter(options)),
+      Self::Compact => Box::new(CompactFormatter(options)),
+      Self::Apdu    => Box::new(ApduFormatter(options)),
+      Self::Table   => Box::new(TableFormatter(options)),
+      Self::RawDigits
+      =>  Box::new(RawDigitsFormatter),
+      Self::Template(template)
+      =>  Box::new(TemplateFormatter(template.clone(), options)),
+    };
+    let formatter: Box<dyn PinFormatter> = if options.reverse {
+      Box::new(Reversed(formatter))
+    } else {
+      formatter
+    };
+    if options.sort_by_pin {
+      Box::new(SortedByPin(formatter))
+    } else {
+      formatter
+    }
+  }
+}
+
+/// Render the PINs and PUKs of another formatter last to first, e.g. for tools consuming them bottom-up.
+/// Each PIN keeps its index and its PUK.
+pub struct Reversed(Box<dyn PinFormatter>);
+
+impl PinFormatter for Reversed {
+  fn render(&self, pins: &[IndexedPin], puks: &[Puk], meta: &DerivationMeta, writer: &mut dyn Write) -> IoResult<()> {
+    let pins: Vec<IndexedPin> = pins.iter().rev().copied().collect();
+    let puks: Vec<Puk> = puks.iter().rev().cloned().collect();
+    self.0.render(&pins, &puks, meta, writer)
+  }
+}
+
+/// Render the PINs and PUKs of another formatter in ascending order of the PINs, see `Pin::cmp`,
+///   so duplicate or clustered PINs are easy to spot; with `Reversed` inside, they are descending.
+/// Each PIN keeps its index and its PUK, equal PINs keep their order.
+pub struct SortedByPin(Box<dyn PinFormatter>);
+
+impl PinFormatter for SortedByPin {
+  fn render(&self, pins: &[IndexedPin], puks: &[Puk], meta: &DerivationMeta, writer: &mut dyn Write) -> IoResult<()> {
+    let mut order: Vec<usize> = (0..pins.len()).collect();
+    order.sort_by_key(|&position| pins[position].1);
+    let pins: Vec<IndexedPin> = order.iter().map(|&position| pins[position]).collect();
+    let puks: Vec<Puk> = order.iter().filter_map(|&position| puks.get(position).cloned()).collect();
+    self.0.render(&pins, &puks, meta, writer)
+  }
+}
+
+impl FromStr for Format {
+  type Err = Error;
+
+  fn from_str(name: &str) -> Result<Self, Error> {
+    match name {
+      "text"    => Ok(Self::Text),
+      "json"    => Ok(Self::Json),
+      "ndjson"  => Ok(Self::NdJson),
+      "csv"     => Ok(Self::Csv),
+      "base64"  => Ok(Self::Base64),
+      "compact" => Ok(Self::Compact),
+      "apdu"    => Ok(Self::Apdu),
+      "table"   => Ok(Self::Table),
+      "raw-digits"
+      =>  Ok(Self::RawDigits),
+      _         => Err(Error::InvalidArguments),
+    }
+  }
+}
+
+/// Write a heading for the PINs of a card, if they were derived for several cards.
+pub fn write_card_heading(meta: &DerivationMeta, writer: &mut dyn Write) -> IoResult<()> {
+  meta.card.map_or(Ok(()), |card| writeln!(writer, "Card {}:", card))
+}
+
+/// Write a heading with the serial numbers the following PINs were derived from,
+///   which replaces the number of the card, see `--by-serial`.
+/// The serial numbers are masked like the PINs, as they are somewhat sensitive.
+pub fn write_serials_heading(serials: &SerialNumbers, redaction: Redaction, writer: &mut dyn Write) -> IoResult<()> {
+  writeln!(
+    writer,
+    "Serial numbers {}:",
+    serials
+    .iter()
+    .map(|serial_number| redaction.apply_serial(serial_number))
+    .collect::<Vec<String>>()
+    .join(", ")
+  )
+}
+
+/// One line per PIN with its frame and its digits, meant to be read by humans.
+/// If redacted, the frame is left out and only the masked digits are shown.
+pub struct TextFormatter(pub RenderOptions);
+
+impl PinFormatter for TextFormatter {
+  fn render(&self, pins: &[IndexedPin], puks: &[Puk], meta: &DerivationMeta, writer: &mut dyn Write) -> IoResult<()> {
+    write_card_heading(meta, writer)?;
+    let width = self.0.index_width.unwrap_or_else(||
+      pins
+      .iter()
+      .map(|(index, _)| index.to_string().len())
+      .max()
+      .unwrap_or(1)
+    );
+    pins
+    .iter()
+    .enumerate()
+    .try_for_each(|(position, (index, pin))| {
+      match self.0.redaction {
+        Redaction::None => write!(writer, "PIN {:0width$}: {}", index, Color::bold(pin, self.0.color)),
+        redaction       => write!(writer, "PIN {:0width$}: {}", index, Color::bold(redaction.apply(pin), self.0.color)),
+      }?;
+      match puks.get(position) {
+        Some(puk) => writeln!(writer, ", PUK: {}", Color::bold(self.0.redaction.apply_puk(puk), self.0.color)),
+        None      => writeln!(writer),
+      }
+    })
+  }
+}
+
+/// Print the PINs derived with several algorithms grouped by their index, one line per algorithm,
+///   so the old and new PINs can be compared during a migration, see `--compare-algorithms`.
+/// Redacted PINs are masked as by `TextFormatter`.
+pub fn write_comparison(comparison: &[(Algorithm, Vec<Pin>)], options: RenderOptions, writer: &mut dyn Write) -> IoResult<()> {
+  let count = comparison.iter().map(|(_, pins)| pins.len()).max().unwrap_or(0);
+  let index_width = options.index_width.unwrap_or_else(|| count.saturating_sub(1).to_string().len());
+  let width = comparison.iter().map(|(algorithm, _)| algorithm.to_string().len()).max().unwrap_or(0);
+  (0..count)
+  .try_for_each(|index| {
+    writeln!(writer, "PIN {:0index_width$}:", index)?;
+    comparison
+    .iter()
+    .filter_map(|(algorithm, pins)| pins.get(index).map(|pin| (algorithm, pin)))
+    .try_for_each(|(algorithm, pin)|
+      match options.redaction {
+        Redaction::None => writeln!(writer, "  {:width$} {}", algorithm, Color::bold(pin, options.color)),
+        redaction       => writeln!(writer, "  {:width$} {}", algorithm, Color::bold(redaction.apply(pin), options.color)),
+      }
+    )
+  })
+}
+
+/// Get the JSON member of the card the PINs belong to, if there are several cards.
+fn json_card_member(meta: &DerivationMeta) -> String {
+  meta.card.map_or_else(String::new, |card| format!("\"card\":{},", card))
+}
+
+/// Get the JSON member of the PUK at a position, if there are PUKs.
+fn json_puk_member(puks: &[Puk], position: usize, redaction: Redaction) -> String {
+  puks
+  .get(position)
+  .map_or_else(String::new, |puk| format!(",\"puk\":\"{}\"", redaction.apply_puk(puk)))
+}
+
+/// A single JSON object with the metadata and all PINs, one line per card.
+pub struct JsonFormatter(pub RenderOptions);
+
+impl PinFormatter for JsonFormatter {
+  fn render(&self, pins: &[IndexedPin], puks: &[Puk], meta: &DerivationMeta, writer: &mut dyn Write) -> IoResult<()> {
+    write!(
+      writer,
+      "{{\"algorithm\":\"{}\",{}\"count\":{},\"length\":{},{}\"pins\":[",
+      meta.algorithm,
+      json_card_member(meta),
+      meta.count,
+      meta.length,
+      json_provenance_member(meta),
+    )?;
+    pins
+    .iter()
+    .enumerate()
+    .try_for_each(|(position, (index, pin))|
+      write!(
+        writer,
+        "{}{{\"index\":{},\"pin\":\"{}\"{}}}",
+        if position == 0 { "" } else { "," },
+        index,
+        self.0.redaction.apply(pin),
+        json_puk_member(puks, position, self.0.redaction),
+      )
+    )?;
+    writeln!(writer, "]}}")
+  }
+}
+
+/// One JSON object per line and PIN, to be processed as a stream.
+pub struct NdJsonFormatter(pub RenderOptions);
+
+impl PinFormatter for NdJsonFormatter {
+  fn render(&self, pins: &[IndexedPin], puks: &[Puk], meta: &DerivationMeta, writer: &mut dyn Write) -> IoResult<()> {
+    pins
+    .iter()
+    .enumerate()
+    .try_for_each(|(position, (index, pin))|
+      writeln!(
+        writer,
+        "{{\"algorithm\":\"{}\",{}\"index\":{},\"pin\":\"{}\"{}}}",
+        meta.algorithm,
+        json_card_member(meta),
+        index,
+        self.0.redaction.apply(pin),
+        json_puk_member(puks, position, self.0.redaction),
+      )
+    )
+  }
+}
+
+/// A header and one row per PIN, separated by commas.
+/// If there are several cards, the rows of all cards share the header and start with the card.
+pub struct CsvFormatter(pub RenderOptions, Cell<bool>);
+
+impl PinFormatter for CsvFormatter {
+  fn render(&self, pins: &[IndexedPin], puks: &[Puk], meta: &DerivationMeta, writer: &mut dyn Write) -> IoResult<()> {
+    if !self.1.replace(true) {
+      writeln!(
+        writer,
+        "{}index,pin{}",
+        meta.card.map_or("", |_| "card,"),
+        if puks.is_empty() { "" } else { ",puk" },
+      )?;
+    }
+    let card = meta.card.map_or_else(String::new, |card| format!("{},", card));
+    pins
+    .iter()
+    .enumerate()
+    .try_for_each(|(position, (index, pin))|
+      writeln!(
+        writer,
+        "{}{},{}{}",
+        card,
+        index,
+        self.0.redaction.apply(pin),
+        puks.get(position).map_or_else(String::new, |puk| format!(",{}", self.0.redaction.apply_puk(puk))),
+      )
+    )
+  }
+}
+
+/// One line per PIN with the base64 encoded frame as sent to the card.
+/// If redacted, the masked digits are encoded instead, because the frame would reveal them.
+/// A PUK has no frame, so its digits are encoded after the PIN.
+pub struct Base64Formatter(pub RenderOptions);
+
+impl PinFormatter for Base64Formatter {
+  fn render(&self, pins: &[IndexedPin], puks: &[Puk], meta: &DerivationMeta, writer: &mut dyn Write) -> IoResult<()> {
+    write_card_heading(meta, writer)?;
+    pins
+    .iter()
+    .enumerate()
+    .try_for_each(|(position, (index, pin))| {
+      match self.0.redaction {
+        Redaction::None => write!(writer, "{}: {}", index, base64(&pin.frame_bytes())),
+        redaction       => write!(writer, "{}: {}", index, base64(redaction.apply(pin).as_bytes())),
+      }?;
+      match puks.get(position) {
+        Some(puk) => writeln!(writer, " {}", base64(self.0.redaction.apply_puk(puk).as_bytes())),
+        None      => writeln!(writer),
+      }
+    })
+  }
+}
+
+/// One line per PIN with the frame as sent to the card, as space separated hex bytes, see `Pin::frame_bytes`.
+/// If redacted, the bytes with digits are masked, because they would reveal them.
+/// A PUK has no frame, so its digits are appended to the PIN.
+pub struct ApduFormatter(pub RenderOptions);
+
+impl PinFormatter for ApduFormatter {
+  fn render(&self, pins: &[IndexedPin], puks: &[Puk], meta: &DerivationMeta, writer: &mut dyn Write) -> IoResult<()> {
+    write_card_heading(meta, writer)?;
+    pins
+    .iter()
+    .enumerate()
+    .try_for_each(|(position, (index, pin))| {
+      write!(writer, "{}: {}", index, frame_hex(pin, self.0.redaction))?;
+      match puks.get(position) {
+        Some(puk) => writeln!(writer, " {}", self.0.redaction.apply_puk(puk)),
+        None      => writeln!(writer),
+      }
+    })
+  }
+}
+
+/// An aligned table of the PINs with their indices and serial numbers, to review many PINs at once.
+/// A PIN with a run of `TableFormatter::WEAK_RUN` identical digits is flagged as weak, see `--forbid-run`.
+/// The serial numbers are masked like the PINs, see `Redaction::apply_serial`.
+pub struct TableFormatter(pub RenderOptions);
+
+impl TableFormatter {
+  /// Length of a run of identical digits which makes a PIN weak.
+  const WEAK_RUN: usize = 3;
+
+  /// Write a row of cells, each padded to the width of its column.
+  fn write_row(writer: &mut dyn Write, cells: &[String], widths: &[usize]) -> IoResult<()> {
+    cells
+    .iter()
+    .zip(widths)
+    .try_for_each(|(cell, &width)| write!(writer, "| {:width$} ", cell))
+    .and_then(|_| writeln!(writer, "|"))
+  }
+}
+
+impl PinFormatter for TableFormatter {
+  fn render(&self, pins: &[IndexedPin], puks: &[Puk], meta: &DerivationMeta, writer: &mut dyn Write) -> IoResult<()> {
+    write_card_heading(meta, writer)?;
+    let serials = meta.serials
+    .iter()
+    .map(|serial_number| self.0.redaction.apply_serial(serial_number))
+    .collect::<Vec<String>>()
+    .join(", ");
+    let index_width = self.0.index_width.unwrap_or(1);
+    let header: Vec<String> = ["Index", "Serial(s)", "PIN"]
+    .into_iter()
+    .chain((!puks.is_empty()).then_some("PUK"))
+    .chain(iter::once("Weak"))
+    .map(String::from)
+    .collect();
+    let rows: Vec<Vec<String>> = pins
+    .iter()
+    .enumerate()
+    .map(|(position, (index, pin))|
+      [format!("{:0index_width$}", index), serials.clone(), self.0.redaction.apply(pin)]
+      .into_iter()
+      .chain((!puks.is_empty()).then(|| puks.get(position).map_or_else(String::new, |puk| self.0.redaction.apply_puk(puk))))
+      .chain(iter::once(if pin.has_run(Self::WEAK_RUN) { "yes" } else { "no" }.to_string()))
+      .collect()
+    )
+    .collect();
+    let widths: Vec<usize> = (0..header.len())
+    .map(|column|
+      iter::once(&header)
+      .chain(&rows)
+      .map(|row| row[column].chars().count())
+      .max()
+      .unwrap_or(0)
+    )
+    .collect();
+    let rule = format!(
+      "+{}+",
+      widths
+      .iter()
+      .map(|width| "-".repeat(width + 2))
+      .collect::<Vec<String>>()
+      .join("+")
+    );
+
+    writeln!(writer, "{}", rule)?;
+    Self::write_row(writer, &header, &widths)?;
+    writeln!(writer, "{}", rule)?;
+    rows
+    .iter()
+    .try_for_each(|row| Self::write_row(writer, row, &widths))?;
+    writeln!(writer, "{}", rule)
+  }
+}
+
+/// Get the frame of a PIN as space separated hex bytes.
+/// If redacted in any way, even by `--preview`, the bytes with digits are masked as `**`, because they would reveal them.
+pub fn frame_hex(pin: &Pin, redaction: Redaction) -> String {
+  pin
+  .frame_bytes()
+  .iter()
+  .enumerate()
+  .map(|(offset, byte)|
+    match redaction {
+      Redaction::None                               => format!("{:02x}", byte),
+      _ if Pin::FRAME_DIGITS.contains(&offset)      => "**".to_string(),
+      _                                             => format!("{:02x}", byte),
+    }
+  )
+  .collect::<Vec<String>>()
+  .join(" ")
+}
+
+/// All PINs on a single line as `index:digits`, separated by spaces, to be embedded in other output.
+/// A PUK is appended to its PIN as another `:digits`.
+pub struct CompactFormatter(pub RenderOptions);
+
+impl PinFormatter for CompactFormatter {
+  fn render(&self, pins: &[IndexedPin], puks: &[Puk], meta: &DerivationMeta, writer: &mut dyn Write) -> IoResult<()> {
+    write_card_heading(meta, writer)?;
+    let line = pins
+    .iter()
+    .enumerate()
+    .map(|(position, (index, pin))|
+      format!(
+        "{}:{}{}",
+        index,
+        self.0.redaction.apply(pin),
+        puks.get(position).map_or_else(String::new, |puk| format!(":{}", self.0.redaction.apply_puk(puk))),
+      )
+    )
+    .collect::<Vec<String>>()
+    .join(" ");
+    writeln!(writer, "{}", line)
+  }
+}
+
+/// The digits of all PINs, one byte each, e.g. `[1, 2, 3, 4, 5, 6, 7, 8, 9, 1, 2, 3]` for the default PIN,
+///   without indices, separators or line feeds, see `Pin::digits`.
+/// This is binary, so it is never written to a terminal, see `--require-pipe`, and has no place for PUKs or headings.
+pub struct RawDigitsFormatter;
+
+impl PinFormatter for RawDigitsFormatter {
+  fn render(&self, pins: &[IndexedPin], _: &[Puk], _: &DerivationMeta, writer: &mut dyn Write) -> IoResult<()> {
+    pins
+    .iter()
+    .try_for_each(|(_, pin)| writer.write_all(&pin.digits()))
+  }
+}
+
+/// Encode bytes as base64 with padding, see RFC 4648.
+fn base64(bytes: &[u8]) -> String {
+  const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+  bytes
+  .chunks(3)
+  .flat_map(|chunk| {
+    let group = chunk
+    .iter()
+    .enumerate()
+    .fold(0u32, |group, (index, &byte)| group | (byte as u32) << (16 - 8 * index));
+    (0..4)
+    .map(move |index|
+      if index <= chunk.len() {
+        ALPHABET[(group >> (18 - 6 * index) & 0x3f) as usize] as char
+      } else {
+        '='
+      }
+    )
+  })
+  .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{
+      frame_hex,
+      ApduFormatter,
+      Color,
+      CompactFormatter,
+      DerivationMeta,
+      Format,
+      IndexedPin,
+      PinFormatter,
+      Redaction,
+      RenderOptions,
+      WithoutTrailingNewline,
+    },
+    crate::{
+      options::Options,
+      Parameters,
+      Pin,
+      TEST_SERIAL_NUMBERS,
+    },
+  };
+
+  /// No redaction, neither `--redact` nor `--preview`, leaks a digit through the frame.
+  #[test]
+  fn redacted_frame_hides_digits() {
+    let pin = Pin::default();
+    assert_eq!(frame_hex(&pin, Redaction::None), "2c 12 34 56 78 91 23 ff");
+    [Redaction::KeepLast(2), Redaction::Preview]
+    .into_iter()
+    .for_each(|redaction| assert_eq!(frame_hex(&pin, redaction), "2c ** ** ** ** ** ** ff"));
+  }
+
+  /// The output of `--format apdu --preview` has no byte of the digits of the PIN.
+  #[test]
+  fn apdu_preview_hides_digits() {
+    let options = RenderOptions { redaction: Redaction::Preview, ..RenderOptions::default() };
+    let mut output = Vec::new();
+    ApduFormatter(options)
+    .render(&[(0, Pin::default())], &[], &DerivationMeta::new(Parameters::default(), 1), &mut output)
+    .unwrap();
+    let output = String::from_utf8(output).unwrap();
+    ["12", "34", "56", "78", "91", "23"]
+    .into_iter()
+    .for_each(|byte| assert!(!output.contains(byte), "{} leaks {}", output, byte));
+  }
+
+  /// Each built-in format renders the same PINs as expected, including the name of the algorithm as given to `--algorithm`.
+  #[test]
+  fn every_format_renders_fixed_pins() {
+    let pins = [(0, Pin::default()), (1, Pin::new(&[0x00, 0x11, 0x22, 0x33, 0x44, 0x05]))];
+    let render = |format: &str| {
+      let mut output = Vec::new();
+      format
+      .parse::<Format>()
+      .unwrap()
+      .formatter(RenderOptions::default())
+      .render(&pins, &[], &DerivationMeta::new(Parameters::default(), pins.len()), &mut output)
+      .unwrap();
+      output
+    };
+    [
+      ("text",    "PIN 0: [2c, 12, 34, 56, 78, 91, 23, ff]: 1 2 3 4 5 6 7 8 9 1 2 3\nPIN 1: [2c, 00, 11, 22, 33, 44, 05, ff]: 0 0 1 1 2 2 3 3 4 4 0 5\n"),
+      ("json",    "{\"algorithm\":\"double-sha512\",\"count\":2,\"length\":12,\"pins\":[{\"index\":0,\"pin\":\"123456789123\"},{\"index\":1,\"pin\":\"001122334405\"}]}\n"),
+      ("ndjson",  "{\"algorithm\":\"double-sha512\",\"index\":0,\"pin\":\"123456789123\"}\n{\"algorithm\":\"double-sha512\",\"index\":1,\"pin\":\"001122334405\"}\n"),
+      ("csv",     "index,pin\n0,123456789123\n1,001122334405\n"),
+      ("base64",  "0: LBI0VniRI/8=\n1: LAARIjNEBf8=\n"),
+      ("compact", "0:123456789123 1:001122334405\n"),
+      ("apdu",    "0: 2c 12 34 56 78 91 23 ff\n1: 2c 00 11 22 33 44 05 ff\n"),
+      (
+        "table",
+        "+-------+-----------+--------------+------+\n\
+        | Index | Serial(s) | PIN          | Weak |\n\
+        +-------+-----------+--------------+------+\n\
+        | 0     |           | 123456789123 | no   |\n\
+        | 1     |           | 001122334405 | no   |\n\
+        +-------+-----------+--------------+------+\n",
+      ),
+    ]
+    .into_iter()
+    .for_each(|(format, expected)| assert_eq!(String::from_utf8(render(format)).unwrap(), expected, "{}", format));
+    assert_eq!(render("raw-digits"), [1, 2, 3, 4, 5, 6, 7, 8, 9, 1, 2, 3, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 0, 5]);
+  }
+
+  /// `--redact` leaves only the last two digits of each PIN visible, in every format which shows them,
+  ///   and the frame of `apdu` has none left, see `redacted_frame_hides_digits`.
+  #[test]
+  fn default_redaction_keeps_last_two() {
+    let options = Options::parse(["--redact".to_owned()].into_iter()).unwrap();
+    assert!(matches!(options.render.redaction, Redaction::KeepLast(2)));
+    assert_eq!(options.render.redaction.apply(&Pin::default()), "**********23");
+
+    let pins = [(0, Pin::default())];
+    ["text", "json", "ndjson", "csv", "compact", "apdu", "table"]
+    .into_iter()
+    .for_each(|format| {
+      let mut output = Vec::new();
+      format
+      .parse::<Format>()
+      .unwrap()
+      .formatter(options.render)
+      .render(&pins, &[], &DerivationMeta::new(Parameters::default(), pins.len()), &mut output)
+      .unwrap();
+      let output = String::from_utf8(output).unwrap();
+      assert!(!output.contains("1234567891"), "{}: {}", format, output);
+      assert!(format == "apdu" || output.contains("**********23"), "{}: {}", format, output);
+    });
+  }
+
+  /// Without color, e.g. with `--no-color`, the text output contains no escape sequences, but with color the PINs are bold.
+  #[test]
+  fn no_color_has_no_escapes() {
+    assert!(!Options::parse(["--no-color".to_owned()].into_iter()).unwrap().render.color);
+    assert!(!Color::enabled(true));
+
+    let pins = [(0, Pin::default())];
+    let render = |color: bool| {
+      let mut output = Vec::new();
+      Format::Text
+      .formatter(RenderOptions { color, ..RenderOptions::default() })
+      .render(&pins, &[], &DerivationMeta::new(Parameters::default(), pins.len()), &mut output)
+      .unwrap();
+      String::from_utf8(output).unwrap()
+    };
+    assert!(!render(false).contains('\x1b'), "{:?}", render(false));
+    assert!(render(true).contains("\x1b[1m"), "{:?}", render(true));
+  }
+
+  /// A batch of two PINs is a single line of `index:pin` pairs separated by single spaces.
+  #[test]
+  fn compact_is_single_line() {
+    let pins = [(0, Pin::default()), (1, Pin::new(&[0x98, 0x76, 0x54, 0x32, 0x19, 0x87]))];
+    let meta = DerivationMeta::new(Parameters::default(), pins.len());
+    let mut output = Vec::new();
+    CompactFormatter(RenderOptions::default()).render(&pins, &[], &meta, &mut output).unwrap();
+    assert_eq!(output, b"0:123456789123 1:987654321987\n");
+  }
+
+  /// The text and compact output end with exactly one line feed and start without a byte order mark,
+  ///   and with `--no-trailing-newline` they end with the last digit.
+  #[test]
+  fn trailing_newline_is_controlled() {
+    let pins = [(0, Pin::default()), (1, Pin::new(&[0x98, 0x76, 0x54, 0x32, 0x19, 0x87]))];
+    let meta = DerivationMeta::new(Parameters::default(), pins.len());
+    [Format::Text, Format::Compact]
+    .into_iter()
+    .for_each(|format| {
+      let formatter = format.formatter(RenderOptions::default());
+      let mut output = Vec::new();
+      formatter.render(&pins, &[], &meta, &mut output).unwrap();
+      assert!(output.ends_with(b"7\n") && !output.starts_with(b"\xef\xbb\xbf"), "{:?}", output);
+
+      let mut without = WithoutTrailingNewline::new(Vec::new());
+      formatter.render(&pins, &[], &meta, &mut without).unwrap();
+      assert_eq!(without.inner, output[..output.len() - 1]);
+    });
+  }
+
+  /// The indices of a batch of 12 PINs are padded to two digits, so the PINs line up, or to the width of `--index-width`.
+  #[test]
+  fn indices_are_aligned() {
+    let pins: Vec<IndexedPin> = (0..12).map(|index| (index, Pin::default())).collect();
+    let meta = DerivationMeta::new(Parameters::default(), pins.len());
+    let render = |index_width| {
+      let mut output = Vec::new();
+      Format::Text
+      .formatter(RenderOptions { redaction: Redaction::KeepLast(2), index_width, ..RenderOptions::default() })
+      .render(&pins, &[], &meta, &mut output)
+      .unwrap();
+      String::from_utf8(output).unwrap()
+    };
+    let lines: Vec<String> = render(None).lines().map(String::from).collect();
+    assert_eq!(lines.len(), 12);
+    assert_eq!((lines[0].as_str(), lines[11].as_str()), ("PIN 00: **********23", "PIN 11: **********23"));
+    assert!(lines.iter().all(|line| line.find(':') == Some(6)), "{:?}", lines);
+    assert!(render(Some(3)).starts_with("PIN 000: **********23\n"));
+  }
+
+  /// `--reverse` renders a batch of three PINs last to first, each with its own index.
+  #[test]
+  fn reverse_keeps_indices() {
+    let pins = [
+      (0, Pin::default()),
+      (1, Pin::new(&[0x98, 0x76, 0x54, 0x32, 0x19, 0x87])),
+      (2, Pin::new(&[0x00, 0x11, 0x22, 0x33, 0x44, 0x05])),
+    ];
+    let options = Options::parse(["--reverse", "--format", "compact"].map(String::from).into_iter()).unwrap();
+    let mut output = Vec::new();
+    options.format
+    .formatter(options.render)
+    .render(&pins, &[], &DerivationMeta::new(Parameters::default(), pins.len()), &mut output)
+    .unwrap();
+    assert_eq!(output, b"2:001122334405 1:987654321987 0:123456789123\n");
+  }
+
+  /// `--format table` has a header and aligned columns of index, masked serial numbers, redacted PIN and weakness,
+  ///   for a batch of PINs one of which has a run of three identical digits.
+  #[test]
+  fn table_columns_are_aligned() {
+    let pins = [
+      (0, Pin::default()),
+      (1, Pin::new(&[0x11, 0x12, 0x34, 0x56, 0x78, 0x90])),
+      (10, Pin::new(&[0x98, 0x76, 0x54, 0x32, 0x19, 0x87])),
+    ];
+    let meta = DerivationMeta { serials: TEST_SERIAL_NUMBERS[..2].to_vec(), ..DerivationMeta::new(Parameters::default(), pins.len()) };
+    let mut output = Vec::new();
+    Format::Table
+    .formatter(RenderOptions { redaction: Redaction::KeepLast(2), ..RenderOptions::default() })
+    .render(&pins, &[], &meta, &mut output)
+    .unwrap();
+    let table = String::from_utf8(output).unwrap();
+    assert_eq!(
+      table,
+      concat!(
+        "+-------+--------------------+--------------+------+\n",
+        "| Index | Serial(s)          | PIN          | Weak |\n",
+        "+-------+--------------------+--------------+------+\n",
+        "| 0     | ******37, ******ow | **********23 | no   |\n",
+        "| 1     | ******37, ******ow | **********90 | yes  |\n",
+        "| 10    | ******37, ******ow | **********87 | no   |\n",
+        "+-------+--------------------+--------------+------+\n",
+      )
+    );
+    let columns = |line: &str| line.match_indices(['|', '+']).map(|(position, _)| position).collect::<Vec<usize>>();
+    assert!(table.lines().all(|line| columns(line) == columns("+-------+--------------------+--------------+------+")));
+  }
+
+  /// `--sort-by-pin` renders a batch of PINs in ascending order of their digits, each with its own index,
+  ///   and equal PINs in the order of their indices.
+  #[test]
+  fn sort_by_pin_keeps_indices() {
+    let pins = [
+      (0, Pin::default()),
+      (1, Pin::new(&[0x98, 0x76, 0x54, 0x32, 0x19, 0x87])),
+      (2, Pin::new(&[0x00, 0x11, 0x22, 0x33, 0x44, 0x05])),
+      (3, Pin::default()),
+    ];
+    let options = Options::parse(["--sort-by-pin", "--format", "compact"].map(String::from).into_iter()).unwrap();
+    let mut output = Vec::new();
+    options.format
+    .formatter(options.render)
+    .render(&pins, &[], &DerivationMeta::new(Parameters::default(), pins.len()), &mut output)
+    .unwrap();
+    assert_eq!(output, b"2:001122334405 0:123456789123 3:123456789123 1:987654321987\n");
+    assert!(pins[2].1 < pins[0].1 && pins[0].1 < pins[1].1);
+  }
+
+  /// `--format raw-digits` writes each digit of the default PIN as its own byte, without any separator,
+  ///   and is binary, so it is refused for a terminal like with `--require-pipe`.
+  #[test]
+  fn raw_digits_of_default_pin() {
+    let options = Options::parse(["--format", "raw-digits"].map(String::from).into_iter()).unwrap();
+    let mut output = Vec::new();
+    options.format
+    .formatter(options.render)
+    .render(&[(0, Pin::default())], &[], &DerivationMeta::new(Parameters::default(), 1), &mut output)
+    .unwrap();
+    assert_eq!(output, [1, 2, 3, 4, 5, 6, 7, 8, 9, 1, 2, 3]);
+    assert!(options.format.is_binary() && !Format::Text.is_binary());
+  }
+}
@@ -0,0 +1,87 @@
+use std::{
+  fs,
+  io::Result as IoResult,
+  path::{
+    Component,
+    Path,
+    PathBuf,
+  },
+};
+
+/// Expand a pattern of paths, where `*` matches any run of characters and `?` any single character
+///   within a single component, e.g. `/sys/bus/usb/devices/1-*/serial`.
+/// The matching paths are sorted, so the order does not depend on the order of the directory entries.
+/// Directories which cannot be read do not match anything.
+/// A literal component is kept as long as the path exists as a link itself, even if the link is broken or loops,
+///   so such a card reader is refused by `reader::resolve_reader_path` instead of silently dropped.
+pub fn expand(pattern: &str) -> IoResult<Vec<PathBuf>> {
+  let mut paths = vec![PathBuf::new()];
+  for component in Path::new(pattern).components() {
+    paths = match component {
+      Component::Normal(name) if name.to_string_lossy().contains(['*', '?'])
+      =>  {
+            let name = name.to_string_lossy();
+            paths
+            .iter()
+            .filter_map(|path| fs::read_dir(if path.as_os_str().is_empty() { Path::new(".") } else { path }).ok())
+            .flatten()
+            .collect::<IoResult<Vec<_>>>()?
+            .into_iter()
+            .filter(|entry| matches(&name, &entry.file_name().to_string_lossy()))
+            .map(|entry| entry.path())
+            .collect()
+          },
+      component
+      =>  paths
+          .into_iter()
+          .map(|path| path.join(component))
+          .filter(|path| fs::symlink_metadata(path).is_ok())
+          .collect(),
+    };
+  }
+  paths.sort();
+  Ok(paths)
+}
+
+/// Check whether a name matches a pattern of a single component.
+fn matches(pattern: &str, name: &str) -> bool {
+  let pattern: Vec<char> = pattern.chars().collect();
+  let name: Vec<char> = name.chars().collect();
+  matches_chars(&pattern, &name)
+}
+
+/// Check whether the characters of a name match the characters of a pattern.
+fn matches_chars(pattern: &[char], name: &[char]) -> bool {
+  match pattern.split_first() {
+    None              => name.is_empty(),
+    Some(('*', rest)) => (0..=name.len()).any(|skipped| matches_chars(rest, &name[skipped..])),
+    Some(('?', rest)) => !name.is_empty() && matches_chars(rest, &name[1..]),
+    Some((first, rest))
+    =>  name.first() == Some(first) && matches_chars(rest, &name[1..]),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::expand,
+    std::{
+      env,
+      fs,
+      os::unix::fs::symlink,
+      process,
+    },
+  };
+
+  /// A broken link matches a literal component, so it is not silently dropped.
+  #[test]
+  fn broken_link_is_kept() {
+    let directory = env::temp_dir().join(format!("glob-{}", process::id()));
+    let device = directory.join("1-4");
+    fs::create_dir_all(&device).unwrap();
+    symlink(directory.join("missing"), device.join("serial")).unwrap();
+    let paths = expand(&format!("{}/1-*/serial", directory.display()));
+    fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(paths.unwrap(), vec![device.join("serial")]);
+  }
+}
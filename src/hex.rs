@@ -0,0 +1,35 @@
+/// Encode bytes as lowercase hexadecimal digits.
+pub fn encode(bytes: &[u8]) -> String {
+  bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decode hexadecimal digits into bytes, if there is an even number of them.
+/// Every character must be a digit, `u8::from_str_radix` alone would accept a sign like in `+f`.
+pub fn decode(digits: &str) -> Option<Vec<u8>> {
+  (digits.len().is_multiple_of(2) && digits.bytes().all(|digit| digit.is_ascii_hexdigit()))
+  .then(||
+    (0..digits.len())
+    .step_by(2)
+    .map(|index| u8::from_str_radix(&digits[index..index + 2], 16).ok())
+    .collect()
+  )
+  .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{
+    decode,
+    encode,
+  };
+
+  /// Only pairs of hexadecimal digits are decoded, neither signs nor other characters.
+  #[test]
+  fn only_digits_are_decoded() {
+    assert_eq!(decode("00af7F"), Some(vec![0x00, 0xaf, 0x7f]));
+    assert_eq!(decode(&encode(&[0x01, 0xfe])), Some(vec![0x01, 0xfe]));
+    ["+f", "-1", "0", "0g", " f", "ä0"]
+    .into_iter()
+    .for_each(|digits| assert_eq!(decode(digits), None, "{}", digits));
+  }
+}
@@ -0,0 +1,28 @@
+use sha2::{
+  Digest,
+  Sha256,
+};
+
+/// Size of the blocks SHA-256 processes, which is the size of the padded key.
+const BLOCK_SIZE: usize = 0x40;
+
+/// Calculate the HMAC-SHA256 of a message, see RFC 2104.
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+  let mut padded_key = [0u8; BLOCK_SIZE];
+  if key.len() > BLOCK_SIZE {
+    padded_key[..32].copy_from_slice(&Sha256::digest(key));
+  } else {
+    padded_key[..key.len()].copy_from_slice(key);
+  }
+
+  let inner = Sha256::new()
+  .chain_update(padded_key.map(|byte| byte ^ 0x36))
+  .chain_update(message)
+  .finalize();
+
+  Sha256::new()
+  .chain_update(padded_key.map(|byte| byte ^ 0x5c))
+  .chain_update(inner)
+  .finalize()
+  .into()
+}
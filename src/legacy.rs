@@ -51,9 +51,9 @@ fn get_smart_card_pins_by_algorithm(
       let mut pin_data = [ [ 0u8; 8 ]; 6 ];
       let mut offset = 0;
 
-      for pin in 0 ..= 5 {
-        pin_data[pin][0] = PIN_CONTROL | PIN_LENGTH;
-        pin_data[pin][7] = PIN_STOP;
+      for pin in &mut pin_data {
+        pin[0] = PIN_CONTROL | PIN_LENGTH;
+        pin[7] = PIN_STOP;
 
         let mut digit_pair = 1;
         loop {
@@ -61,7 +61,7 @@ fn get_smart_card_pins_by_algorithm(
             let byte = buffer[offset];
             offset += 1;
             if byte < 200 {
-              pin_data[pin][digit_pair]
+              pin[digit_pair]
               = ( ((byte % 100) / 10) << 4 ) & 0xf0 // most significant digit
               | (byte % 10);                        // least significant digit
               digit_pair += 1;
@@ -76,10 +76,7 @@ fn get_smart_card_pins_by_algorithm(
       }
       Ok(pin_data)
     })?
-    .map_err(|error| {
-      eprintln!("connector-ident: Could not get connector ident number!!!");
-      error
-    })
+    .inspect_err(|_| eprintln!("connector-ident: Could not get connector ident number!!!"))
   }
 }
 
@@ -89,7 +86,7 @@ fn connector_ident_number(
 ) -> Result<[ u8; 0x80 ], &'static str> {
   if algorithm == 3 {
     ids
-    .map(|ids| Ok(ids))
+    .map(Ok)
     .unwrap_or_else(read_smart_card_reader_info)
     .map(|reader_info| {
       let mut buffer = [ 0u8; 0x80 ];
@@ -103,16 +100,13 @@ fn connector_ident_number(
       buffer[..0x40].clone_from_slice(&array[..]);
 
       Sha512::new()
-      .chain_update(&array)
+      .chain_update(array)
       .finalize_into(&mut array);
       buffer[0x40..].clone_from_slice(&array[..]);
 
       buffer
     })
-    .map_err(|error| {
-      eprintln!("connector-ident: Could not read SC reader infos");
-      error
-    })
+    .inspect_err(|_| eprintln!("connector-ident: Could not read SC reader infos"))
   } else {
     eprintln!("connector-ident: ident_algo not / no longer supported");
     Err("Invalid algorithm")
@@ -0,0 +1,34 @@
+use {
+  crate::{
+    hex,
+    Error,
+  },
+  std::fs,
+};
+
+/// The ID of the host, as written by systemd or D-Bus: 32 hex digits, i.e. 16 bytes.
+pub type MachineId = [u8; 0x10];
+
+/// Where the ID of the host is looked up, in this order; older systems only have the one of D-Bus.
+const MACHINE_ID_PATHS: [&str; 2] = [
+  "/etc/machine-id",
+  "/var/lib/dbus/machine-id",
+];
+
+/// Read the ID of this host, see `--bind-machine-id`.
+/// Fails if none of the files exists or the first one found is malformed, e.g. the placeholder `uninitialized`.
+pub fn read() -> Result<MachineId, Error> {
+  let (path, content) = MACHINE_ID_PATHS
+  .iter()
+  .find_map(|&path| fs::read_to_string(path).ok().map(|content| (path, content)))
+  .ok_or(Error::CannotReadMachineId)
+  .inspect_err(|_| eprintln!("Cannot read the machine ID from any of {}", MACHINE_ID_PATHS.join(", ")))?;
+  parse(content.trim())
+  .ok_or(Error::CannotReadMachineId)
+  .inspect_err(|_| eprintln!("Machine ID in {} is not 32 hex digits", path))
+}
+
+/// Decode a machine ID from its 32 hex digits.
+fn parse(digits: &str) -> Option<MachineId> {
+  hex::decode(digits)?.try_into().ok()
+}
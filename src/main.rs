@@ -1,12 +1,12 @@
 #![feature(array_try_from_fn)]
 #![feature(array_try_map)]
-#![feature(result_option_inspect)]
+#![cfg_attr(test, feature(test))]
 
 /// Set the number of card readers.
 /// CARD_READERS and SERIAL_NUMBERS must have this many elements!
 const NUMBER_OF_CARD_READERS: usize = 3;
 
-/// Set the number of pins to calculate.
+/// Set the default number of pins to calculate, see `--count`.
 /// This value should be less than 16,
 ///    because the randomness buffer might not large enough.
 const NUMBER_OF_PINS:         usize = 6;
@@ -19,26 +19,98 @@ const CARD_READERS: ListOfCardReaders
     "/sys/bus/usb/devices/1-6/serial",
   ];
 
-/// Set some serial numbers for testing purposes.
-/// If None, the serial numbers will be read from the card-readers.
-const SERIAL_NUMBERS: MaybeSerialNumbers
-= Some([
+/// Some serial numbers for testing purposes.
+/// PINs derived from them are refused with `--forbid-test-serials`.
+const TEST_SERIAL_NUMBERS: ListOfSerialNumbers
+= [
     SerialNumber(*b"23421337"),
     SerialNumber(*b"meowmeow"),
     SerialNumber(*b"*squeak*"),
-  ]);
+  ];
+
+/// Set some serial numbers for testing purposes.
+/// If None, the serial numbers will be read from the card-readers.
+const SERIAL_NUMBERS: MaybeSerialNumbers = Some(TEST_SERIAL_NUMBERS);
+
+mod argon2;
+#[cfg(test)]
+mod bench;
+mod blake2b;
+mod cache;
+mod error;
+mod format;
+mod glob;
+mod hex;
+mod hmac;
+mod machine;
+mod manifest;
+mod memory;
+mod options;
+mod paginate;
+mod privileges;
+mod reader;
+mod selftest;
+mod socket;
+mod syslog;
+mod source;
+mod summary;
+mod template;
+mod verify;
+mod watch;
 
 use {
-  core::{
-    array::{
-      self,
-      IntoIter,
+  crate::{
+    argon2::argon2id,
+    cache::CachedSource,
+    error::Error,
+    format::{
+      provenance_hash,
+      write_comparison,
+      write_serials_heading,
+      DerivationMeta,
+      IndexedPin,
+      PinFormatter,
+      Redaction,
+      WithoutTrailingNewline,
+    },
+    machine::MachineId,
+    memory::{
+      MemoryLock,
+      Wiped,
+    },
+    options::Options,
+    reader::{
+      probe_reader,
+      ReaderStatus,
     },
+    socket::SocketSource,
+    source::{
+      CheckedSource,
+      DeviceSource,
+      EnvSource,
+      FixedSource,
+      GlobSource,
+      ManifestSource,
+      MultiSerialFileSource,
+      PrefixSource,
+      SerialSource,
+      UeventSource,
+      WhatIfSource,
+    },
+    syslog::LogSink,
+  },
+  core::{
+    array,
+    iter,
+    ops::Range,
     option::Option,
     result::Result,
+    str::FromStr,
   },
   sha2::{
+    digest::Output,
     Digest,
+    Sha256,
     Sha512,
   },
   std::{
@@ -47,41 +119,319 @@ use {
       Formatter,
       Result as FormatResult,
     },
-    fs::File,
-    io::Read,
+    env,
+    fs::{
+      self,
+      File,
+    },
+    io::{
+      self,
+      IsTerminal,
+      Read,
+      Seek,
+      SeekFrom,
+      Write,
+    },
+    mem,
+    path::Path,
+    process::ExitCode,
   },
 };
 
 const SHA512_HASH_LENGTH: usize = 0x40;
 
-type Error                = &'static str;
 type ListOfCardReaders    = [&'static str; NUMBER_OF_CARD_READERS];
-type ListOfPins           = [Pin; NUMBER_OF_PINS];
+type ListOfPins           = Vec<Pin>;
 type ListOfSerialNumbers  = [SerialNumber; NUMBER_OF_CARD_READERS];
 type MaybeSerialNumbers   = Option<ListOfSerialNumbers>;
 
 #[allow(dead_code)]
-#[derive(Debug)]
-enum Algorithm {
-  DefaultPin    = 0,
-  DoubleSHA512  = 3,
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Algorithm {
+  DefaultPin          = 0,
+  /// Hash the concatenated serial numbers, so their order matters.
+  #[default]
+  DoubleSHA512        = 3,
+  /// Hash each serial number on its own and XOR the hashes,
+  ///   so the PINs do not depend on the order of the serial numbers by construction.
+  XorPerSerialSha512  = 4,
+  /// Hash the concatenated serial numbers with Argon2id, which is slow by design, see `try_derive_argon2_prng`.
+  /// This gives other PINs than `DoubleSHA512` and takes as much memory and time as `--argon2-cost` asks for,
+  ///   so guessing the serial numbers from a PIN is just as slow.
+  Argon2id            = 5,
 }
 
-#[derive(Clone, Copy, Debug)]
-struct Pin([ u8; Self::SIZE]);
+impl FromStr for Algorithm {
+  type Err = Error;
+
+  fn from_str(name: &str) -> Result<Self, Error> {
+    match name {
+      "default-pin"   => Ok(Self::DefaultPin),
+      "double-sha512" => Ok(Self::DoubleSHA512),
+      "xor-sha512"    => Ok(Self::XorPerSerialSha512),
+      "argon2id"      => Ok(Self::Argon2id),
+      _               => Err(Error::InvalidArguments),
+    }
+  }
+}
+
+/// The name of the algorithm on the command line, see `Algorithm::from_str`.
+impl Display for Algorithm {
+  fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+    formatter.pad(
+      match self {
+        Self::DefaultPin          => "default-pin",
+        Self::DoubleSHA512        => "double-sha512",
+        Self::XorPerSerialSha512  => "xor-sha512",
+        Self::Argon2id            => "argon2id",
+      }
+    )
+  }
+}
+
+/// The digits a PIN consists of.
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+enum Alphabet {
+  /// The digits 0–9.
+  #[default]
+  Decimal,
+  /// The digits 0–9 and a–f, for keypads which accept them.
+  Hex,
+  /// Up to 16 symbols chosen with `--alphabet custom:…`, see `build_alphabet`.
+  Custom(Symbols),
+}
+
+/// The symbols of a custom alphabet, the digit stored in a nibble of the frame is the index of its symbol,
+///   and the threshold of the rejection sampling for them, see `Alphabet::digit_pair`.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct Symbols {
+  chars:      [u8; 0x10],
+  radix:      u8,
+  threshold:  u16,
+}
+
+/// Build an alphabet of distinct ASCII symbols, at most 16, as each digit is stored in a nibble.
+/// The symbols are restricted to letters and digits by `Alphabet::from_str`, so they are never special in JSON or CSV.
+/// Bytes are accepted below the largest multiple of the number of digit pairs up to 256,
+///   so each pair is hit by the same number of bytes, e.g. 200 for 10 and 256 for 16 symbols.
+fn build_alphabet(chars: &[u8]) -> Alphabet {
+  let mut symbols = Symbols {
+    chars:      [0; 0x10],
+    radix:      chars.len() as u8,
+    threshold:  0,
+  };
+  symbols.chars[..chars.len()].copy_from_slice(chars);
+  let pairs = u16::from(symbols.radix).pow(2);
+  symbols.threshold = 0x100 / pairs * pairs;
+  Alphabet::Custom(symbols)
+}
+
+impl Alphabet {
+  /// Number of distinct decimal digit pairs, 00–99.
+  const MODULO:           u8 = 100;
+  /// Bytes below this are accepted as decimal digit pairs, all others are rejected.
+  /// It is the largest multiple of `MODULO` below 256, so each pair is hit by exactly two bytes;
+  ///   accepting more bytes would make the pairs 00–55 more likely than the others.
+  const REJECT_THRESHOLD: u8 = 2 * Self::MODULO;
+  /// Base of a single decimal digit.
+  const RADIX:            u8 = 10;
+
+  /// Base of a single digit of this alphabet.
+  fn radix(self) -> u8 {
+    match self {
+      Self::Decimal         => Self::RADIX,
+      Self::Hex             => 0x10,
+      Self::Custom(symbols) => symbols.radix,
+    }
+  }
+
+  /// Map a random byte to a packed pair of digits or reject it,
+  ///   such that every pair is equally likely.
+  fn digit_pair(self, byte: u8) -> Option<DigitPair> {
+    // Checked at compile time, so a future edit cannot introduce a modulo bias.
+    const { assert!(Self::REJECT_THRESHOLD % Self::MODULO == 0) };
+    match self {
+      Self::Decimal
+      =>  (byte < Self::REJECT_THRESHOLD)
+          .then_some(
+            ( ((byte % Self::MODULO) / Self::RADIX) << 4 ) & 0xf0 // most significant digit
+            | (byte % Self::RADIX)                                // least significant digit
+          ),
+      // Each byte already is a pair of uniformly distributed hexadecimal digits.
+      Self::Hex => Some(byte),
+      Self::Custom(symbols)
+      =>  {
+            let (byte, radix) = (u16::from(byte), u16::from(symbols.radix));
+            (byte < symbols.threshold)
+            .then_some((((byte % radix.pow(2) / radix) << 4) | (byte % radix)) as u8)
+          },
+    }
+    .and_then(|packed| DigitPair::new(packed, self))
+  }
+
+  /// Get the symbol of a digit of this alphabet.
+  fn symbol(self, digit: u8) -> Option<char> {
+    match self {
+      Self::Decimal | Self::Hex
+      =>  char::from_digit(u32::from(digit), 0x10),
+      Self::Custom(symbols)
+      =>  (digit < symbols.radix).then(|| char::from(symbols.chars[usize::from(digit)])),
+    }
+  }
+
+  /// Get the digit of a symbol of this alphabet, hexadecimal digits in either case, see `verify::verify_entry`.
+  fn digit(self, symbol: char) -> Option<u8> {
+    match self {
+      Self::Decimal | Self::Hex
+      =>  symbol.to_digit(0x10).map(|digit| digit as u8),
+      Self::Custom(symbols)
+      =>  symbols.chars[..usize::from(symbols.radix)]
+          .iter()
+          .position(|&char| u32::from(char) == u32::from(symbol))
+          .map(|digit| digit as u8),
+    }
+  }
+}
+
+/// Two digits packed into the nibbles of a byte, most significant digit first, as stored in a `Pin`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct DigitPair(u8);
+
+impl DigitPair {
+  /// Check that both nibbles are digits of the alphabet, e.g. reject 0x0a and 0xf0 as decimal digits.
+  fn new(packed: u8, alphabet: Alphabet) -> Option<Self> {
+    (packed >> 4 < alphabet.radix() && packed & 0x0f < alphabet.radix())
+    .then_some(Self(packed))
+  }
+
+  /// Get the packed byte.
+  fn packed(self) -> u8 {
+    self.0
+  }
+
+  /// Get both digits, most significant first.
+  fn digits(self) -> [u8; 2] {
+    [self.0 >> 4, self.0 & 0x0f]
+  }
+}
+
+impl FromStr for Alphabet {
+  type Err = Error;
+
+  fn from_str(name: &str) -> Result<Self, Error> {
+    match name.strip_prefix("custom:") {
+      Some(chars)
+      if (2..=0x10).contains(&chars.len())
+      && chars.bytes().all(|char| char.is_ascii_alphanumeric())
+      && chars.bytes().enumerate().all(|(position, char)| !chars.as_bytes()[..position].contains(&char))
+      =>  return Ok(build_alphabet(chars.as_bytes())),
+      Some(_)
+      =>  {
+            eprintln!("A custom alphabet must have 2 to 16 distinct ASCII letters or digits, which need no escaping in any format");
+            return Err(Error::InvalidArguments);
+          },
+      None
+      =>  {},
+    }
+    match name {
+      "decimal" => Ok(Self::Decimal),
+      "hex"     => Ok(Self::Hex),
+      _         => Err(Error::InvalidArguments),
+    }
+  }
+}
+
+/// The name of the alphabet on the command line, see `Alphabet::from_str`.
+impl Display for Alphabet {
+  fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+    match self {
+      Self::Decimal
+      =>  formatter.pad("decimal"),
+      Self::Hex
+      =>  formatter.pad("hex"),
+      Self::Custom(symbols)
+      =>  formatter.pad(&format!("custom:{}", String::from_utf8_lossy(&symbols.chars[..usize::from(symbols.radix)]))),
+    }
+  }
+}
+
+/// The frame of a PIN, the number of digits the user actually types, see `--digits`,
+///   and the alphabet the digits are shown in, see `--alphabet`.
+/// PINs are ordered by their frames, i.e. by their number of digits in the header and then by their digits.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Pin([ u8; Self::SIZE], u8, Alphabet);
 
 impl Pin {
   const STOP:         u8      = 0xff;
   const LENGTH:       u8      = 12;
   const CONTROL:      u8      = 0x20;
   const DIGIT_PAIRS:  usize   = Self::LENGTH as usize / 2;
-  const SIZE:         usize   = 2 + Self::DIGIT_PAIRS;
+  /// Bytes of the frame before the digit pairs: control and length.
+  const HEADER_LEN:   usize   = 1;
+  /// Bytes of the frame after the digit pairs: stop.
+  const TRAILER_LEN:  usize   = 1;
+  const HEADER:       [u8; Self::HEADER_LEN]  = [ Self::CONTROL | Self::LENGTH ];
+  const TRAILER:      [u8; Self::TRAILER_LEN] = [ Self::STOP ];
+  const SIZE:         usize   = Self::HEADER_LEN + Self::DIGIT_PAIRS + Self::TRAILER_LEN;
+  /// The bytes of the frame with the digit pairs, between header and trailer.
+  const FRAME_DIGITS: Range<usize> = Self::HEADER_LEN..Self::HEADER_LEN + Self::DIGIT_PAIRS;
+  /// Nibble the unused digits of the frame are padded with, unless chosen with `--pin-filler`.
+  const FILLER:       u8      = 0x0f;
 
   /// Get a default PIN.
   fn new(digit_pairs: &[u8; Self::DIGIT_PAIRS]) -> Self {
-    let mut pin = [ ( Self::CONTROL | Self::LENGTH ), 0, 0, 0, 0, 0, 0, Self::STOP ];
-    (pin[1..=Self::DIGIT_PAIRS]).copy_from_slice(digit_pairs);
-    Self(pin)
+    // Checked at compile time, so an odd length cannot silently lose its last digit to `DIGIT_PAIRS`;
+    //   shorter PINs of odd length pad the half of their last pair, see `truncated`.
+    const { assert!(Self::LENGTH.is_multiple_of(2)) };
+    let mut pin = [0; Self::SIZE];
+    Self::lay_out(&mut pin, &Self::HEADER, digit_pairs, &Self::TRAILER);
+    Self(pin, Self::LENGTH, Alphabet::default())
+  }
+
+  /// Lay out a frame of header, digit pairs and trailer, each right after the other,
+  ///   so the digit pairs start at the length of the header, whatever framing it is.
+  fn lay_out(frame: &mut [u8], header: &[u8], digit_pairs: &[u8], trailer: &[u8]) {
+    let digits = header.len()..header.len() + digit_pairs.len();
+    (frame[..digits.start]).copy_from_slice(header);
+    (frame[digits.clone()]).copy_from_slice(digit_pairs);
+    (frame[digits.end..]).copy_from_slice(trailer);
+  }
+
+  /// Keep only the first `digits` digits of the PIN and pad the rest of the frame with the `filler` nibble.
+  /// The frame keeps its size, as the card expects it, but its header has the actual number of digits,
+  ///   e.g. a 6 digit PIN `123456` is framed as `[26, 12, 34, 56, ff, ff, ff, ff]` with the default filler.
+  fn truncated(self, digits: u8, filler: u8) -> Self {
+    let mut pin = self.0;
+    (digits..Self::LENGTH)
+    .map(usize::from)
+    .for_each(|index| Self::set_digit(&mut pin, index, filler));
+    Self::set_length(&mut pin, digits);
+    Self(pin, digits, self.2)
+  }
+
+  /// Append the ISO 7064 Mod 11,10 check digit of the digits as the next digit of the frame, see `iso7064_check`.
+  /// The PIN gets one digit longer, also in the header, so it must have fewer digits than fit into the frame.
+  fn with_check_digit(self) -> Self {
+    let mut pin = self.0;
+    Self::set_digit(&mut pin, usize::from(self.1), iso7064_check(&self.digits()));
+    Self::set_length(&mut pin, self.1 + 1);
+    Self(pin, self.1 + 1, self.2)
+  }
+
+  /// Show the digits of the PIN in the symbols of this alphabet, the frame is the same.
+  fn in_alphabet(self, alphabet: Alphabet) -> Self {
+    Self(self.0, self.1, alphabet)
+  }
+
+  /// Overwrite the length nibble of the header of a frame with the number of digits.
+  fn set_length(frame: &mut [u8; Self::SIZE], digits: u8) {
+    frame[0] = Self::CONTROL | digits;
+  }
+
+  /// Overwrite the nibble of a frame with the digit at `index`.
+  fn set_digit(frame: &mut [u8; Self::SIZE], index: usize, digit: u8) {
+    let digit_pair = &mut frame[Self::HEADER_LEN + index / 2];
+    *digit_pair = if index.is_multiple_of(2) { digit << 4 | *digit_pair & 0x0f } else { *digit_pair & 0xf0 | digit };
   }
 
   fn default() -> Self {
@@ -91,10 +441,141 @@ impl Pin {
     ])
   }
 
+  /// Get the frame of the PIN as sent to the card: control and length, the digit pairs, stop.
+  /// For the default PIN this is `[2c, 12, 34, 56, 78, 91, 23, ff]`.
+  pub fn frame_bytes(&self) -> [u8; Self::SIZE] {
+    self.0
+  }
+
+  /// Get the digits of the PIN, one per byte, without the padding of the frame.
+  fn digits(&self) -> Vec<u8> {
+    (0..usize::from(self.1))
+    .map(|index| {
+      let digit_pair = self.0[Self::HEADER_LEN + index / 2];
+      if index % 2 == 0 { digit_pair >> 4 } else { digit_pair & 0x0f }
+    })
+    .collect()
+  }
+
+  /// Get the digits of the PIN as a string of the symbols of its alphabet.
+  fn to_numeric_string(self) -> String {
+    self.digits()
+    .iter()
+    .filter_map(|&digit| self.2.symbol(digit))
+    .collect()
+  }
+
+  /// Get the first and the last digit of the PIN with dots between, see `format::preview`.
+  fn preview(&self) -> String {
+    format::preview(&self.to_numeric_string())
+  }
+
+  /// Get the digit of a symbol of the alphabet of the PIN, e.g. to compare a typed PIN with it.
+  fn digit(&self, symbol: char) -> Option<u8> {
+    self.2.digit(symbol)
+  }
+
+  /// Get the digits of the PIN as a single decimal number, e.g. to store it as an integer.
+  /// Leading zeros are lost, they are restored by `from_u64`.
+  /// None, if the PIN has hexadecimal digits, see `--alphabet`.
+  #[allow(dead_code)]
+  fn to_u64(self) -> Option<u64> {
+    self.digits()
+    .iter()
+    .try_fold(0u64, |number, &digit| (digit < 10).then(|| number * 10 + digit as u64))
+  }
+
+  /// Get the PIN of `length` decimal digits from a number, see `to_u64`,
+  ///   with the rest of the frame padded like `truncated` does with the default filler.
+  /// Fails, if the length does not fit into the frame or the number has more digits.
+  #[allow(dead_code)]
+  fn from_u64(number: u64, length: u8) -> Result<Self, Error> {
+    ((1..=Self::LENGTH).contains(&length) && number < 10u64.pow(u32::from(length)))
+    .then(|| {
+      let mut frame = Self::new(&[0; Self::DIGIT_PAIRS]).0;
+      (0..length)
+      .for_each(|index|
+        Self::set_digit(&mut frame, usize::from(index), (number / 10u64.pow(u32::from(length - 1 - index)) % 10) as u8)
+      );
+      Self(frame, Self::LENGTH, Alphabet::Decimal).truncated(length, Self::FILLER)
+    })
+    .ok_or(Error::InvalidPinNumber)
+  }
+
   /// Calculate a PIN from the pseudo-random number generator.
+  /// A whole frame is drawn even for fewer digits, see `Random::with_pin_digits`,
+  ///   so a shorter PIN consists of the first digits of the one of full length.
+  /// PINs rejected by `Random::allows` are discarded and another one is drawn.
   fn from_prng(prng: &mut Random) -> Result<Self, Error> {
-    array::try_from_fn(|_| prng.next())
-    .map(|digit_pairs| Self::new(&digit_pairs))
+    loop {
+      let digit_pairs: [DigitPair; Self::DIGIT_PAIRS] = array::try_from_fn(|_| prng.next())?;
+      let pin = Self::new(&digit_pairs.map(DigitPair::packed))
+      .truncated(prng.pin_digits, prng.pin_filler)
+      .in_alphabet(prng.alphabet);
+      let pin = if prng.check_digit { pin.with_check_digit() } else { pin };
+      if prng.allows(&pin) {
+        break Ok(pin);
+      }
+      prng.rejected_pins += 1;
+    }
+  }
+
+  /// Whether the PIN contains `run` identical consecutive digits.
+  fn has_run(&self, run: usize) -> bool {
+    self.digits()
+    .windows(run)
+    .any(|window| window.iter().all(|&digit| digit == window[0]))
+  }
+}
+
+/// Calculate the check digit of ISO 7064 Mod 11,10 of decimal digits,
+///   which detects every single wrong digit and almost every swap of adjacent digits.
+/// E.g. the check digit of `0794` is `5`.
+fn iso7064_check(digits: &[u8]) -> u8 {
+  let product = digits
+  .iter()
+  .fold(10, |product, &digit| {
+    let sum = match (product + digit) % 10 {
+      0   => 10,
+      sum => sum,
+    };
+    (2 * sum) % 11
+  });
+  (11 - product) % 10
+}
+
+/// A PIN unblocking key, a longer number to reset the PIN with.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Puk(Vec<u8>, Alphabet);
+
+impl Puk {
+  /// Number of digits of a PUK, unless chosen with `--puk-length`.
+  const DEFAULT_LENGTH: usize = 8;
+
+  /// Calculate a PUK of `length` digits from the pseudo-random number generator.
+  /// It takes half as many digit pairs, rounded up; of the last pair of an odd length, only the first digit is used.
+  fn from_prng(prng: &mut Random, length: usize) -> Result<Self, Error> {
+    (0..length.div_ceil(2))
+    .map(|_| prng.next())
+    .collect::<Result<Vec<DigitPair>, Error>>()
+    .map(|digit_pairs|
+      Self(
+        digit_pairs
+        .into_iter()
+        .flat_map(DigitPair::digits)
+        .take(length)
+        .collect(),
+        prng.alphabet,
+      )
+    )
+  }
+
+  /// Get the digits of the PUK as a string of the symbols of its alphabet.
+  fn to_numeric_string(&self) -> String {
+    self.0
+    .iter()
+    .filter_map(|&digit| self.1.symbol(digit))
+    .collect()
   }
 }
 
@@ -104,156 +585,2673 @@ impl Display for Pin {
     .and_then
     (
       |_|
-      self.0.iter().skip(1).take(Self::DIGIT_PAIRS)
-      .try_for_each(
-        |digit_pair|
-        write!(
-          formatter,
-          " {:x} {:x}",
-          digit_pair >> 4,
-          digit_pair & 0x0f,
-        )
-      )
+      self.digits()
+      .iter()
+      .filter_map(|&digit| self.2.symbol(digit))
+      .try_for_each(|symbol| write!(formatter, " {}", symbol))
     )
   }
 }
 
+/// How many SHA512 hashes the randomness is initially filled with, see `--hash-stages`.
+#[derive(Clone, Copy, Debug, Default)]
+enum HashStages {
+  /// Only the hash of the serial numbers, as older derivation schemes did.
+  /// These 64 bytes suffice for about 8 decimal PINs, unless extended with `--auto-extend`.
+  Single,
+  /// The hash of the serial numbers and the hash of that hash.
+  /// These 128 bytes suffice for about 16 decimal PINs.
+  #[default]
+  Double,
+}
+
+impl HashStages {
+  /// Get the number of hashes.
+  fn count(self) -> usize {
+    match self {
+      Self::Single => 1,
+      Self::Double => 2,
+    }
+  }
+}
+
+impl FromStr for HashStages {
+  type Err = Error;
+
+  fn from_str(stages: &str) -> Result<Self, Error> {
+    match stages {
+      "1" => Ok(Self::Single),
+      "2" => Ok(Self::Double),
+      _   => Err(Error::InvalidArguments),
+    }
+  }
+}
+
+/// Parameters of the derivation of the PINs.
+#[derive(Clone, Copy, Debug)]
+struct Parameters {
+  /// The algorithm to derive the PINs with.
+  algorithm:            Algorithm,
+  /// Number of PINs to calculate.
+  count:                usize,
+  /// Extend the randomness by more hash rounds instead of failing when it runs out.
+  auto_extend:          bool,
+  /// The digits of the PINs.
+  alphabet:             Alphabet,
+  /// Derive PINs even from no serial numbers at all, i.e. without any entropy.
+  allow_empty_serials:  bool,
+  /// The number of hashes the randomness is filled with.
+  hash_stages:          HashStages,
+  /// How often the first hash is hashed, 1 for just once, before the randomness is filled, see `Random::stretched`.
+  hash_iterations:      u32,
+  /// Test the randomness for bias before deriving PINs from it.
+  check_entropy:        bool,
+  /// Sort the serial numbers before hashing them, so the order of the card readers does not matter.
+  sort_serials:         bool,
+  /// Hash each serial number only once, even if read from several card readers.
+  dedup_serials:        bool,
+  /// Refuse to derive PINs from fewer different serial numbers than this, see `SerialNumbers::new`.
+  min_distinct_serials: Option<usize>,
+  /// Reject and redraw PINs with this many identical consecutive digits, as some keypads refuse them.
+  forbid_run:           Option<usize>,
+  /// Whether to lock the randomness into memory, see `--mlock`.
+  memory_lock:          MemoryLock,
+  /// Number of digits of each PIN the user types, the rest of the frame is padded.
+  pin_digits:           u8,
+  /// Nibble to pad the frame of a PIN with fewer digits.
+  pin_filler:           u8,
+  /// Append an ISO 7064 check digit to each PIN, so it has one digit more than `pin_digits`, see `iso7064_check`.
+  check_digit:          bool,
+  /// Prefix each serial number with its length when hashing them, see `try_derive_prng`.
+  length_bind:          bool,
+  /// Report how much randomness each PIN consumed on stderr.
+  explain:              bool,
+  /// Derive another set of PINs from the same serial numbers, e.g. to rotate them, 0 for the original ones.
+  epoch:                u64,
+  /// Bind the PINs to the host by hashing its ID, see `--bind-machine-id` and `chain_epoch_and_machine_id`.
+  machine_id:           Option<MachineId>,
+  /// Derive each PIN from its own hash of the first hash and its index instead of one stream, see `Random::for_index`.
+  per_index:            bool,
+  /// The memory, passes and lanes of `Algorithm::Argon2id`.
+  argon2_cost:          argon2::Cost,
+}
+
+/// A parameter the PINs depend on, see `Parameters::derivation_inputs`.
+struct DerivationInput {
+  /// The option setting the parameter.
+  option: &'static str,
+  /// How the parameter affects the PINs.
+  effect: &'static str,
+  /// The value of the parameter, as given to the option.
+  value:  String,
+}
+
+impl DerivationInput {
+  fn new(option: &'static str, effect: &'static str, value: impl Display) -> Self {
+    Self { option, effect, value: value.to_string() }
+  }
+}
+
+impl Parameters {
+  /// Get the number of digits of each PIN, including the check digit, if any.
+  fn pin_length(self) -> u8 {
+    self.pin_digits + u8::from(self.check_digit)
+  }
+
+  /// Get every parameter the PINs depend on, see `--help-derivation` and `provenance_hash`.
+  /// The parameters are destructured without `..`, so a new one does not compile until it is either listed here
+  ///   or explicitly ignored for only selecting or checking PINs.
+  fn derivation_inputs(self) -> Vec<DerivationInput> {
+    let Self {
+      algorithm,
+      count:                _,
+      auto_extend:          _,
+      alphabet,
+      allow_empty_serials:  _,
+      hash_stages,
+      hash_iterations,
+      check_entropy:        _,
+      sort_serials,
+      dedup_serials,
+      min_distinct_serials: _,
+      forbid_run,
+      memory_lock:          _,
+      pin_digits,
+      pin_filler,
+      check_digit,
+      length_bind,
+      explain:              _,
+      epoch,
+      machine_id,
+      per_index,
+      argon2_cost,
+    } = self;
+    let or_none = |value: Option<String>| value.unwrap_or_else(|| "none".to_owned());
+    vec![
+      DerivationInput::new("--sort-serials",    "Hashes the serial numbers sorted, so the order of the card readers does not matter", sort_serials),
+      DerivationInput::new("--dedup-serials",   "Hashes each serial number only once, otherwise duplicates are hashed again", dedup_serials),
+      DerivationInput::new("--algorithm",       "Selects how the serial numbers are hashed into the randomness", algorithm),
+      DerivationInput::new("--argon2-cost",     "The memory in KiB, passes and lanes of Argon2id, only used by --algorithm argon2id", argon2_cost),
+      DerivationInput::new("--length-bind",     "Prefixes each serial number with its length before hashing", length_bind),
+      DerivationInput::new("--epoch",           "Selects a fresh set of PINs for the same serial numbers, 0 is the original one", epoch),
+      DerivationInput::new("--bind-machine-id", "Hashes the ID of this host after the epoch, so other hosts derive other PINs", or_none(machine_id.map(|id| hex::encode(&id)))),
+      DerivationInput::new("--hash-iterations", "Hashes the first hash this many times, 1 yields the original PINs", hash_iterations),
+      DerivationInput::new("--per-index",       "Derives each PIN from its own hash of the first hash and its index", per_index),
+      DerivationInput::new("--hash-stages",     "The number of hashes the randomness consists of, changes PINs beyond the first hash", hash_stages.count()),
+      DerivationInput::new("--alphabet",        "Maps the randomness to decimal, hexadecimal or custom digits", alphabet),
+      DerivationInput::new("--forbid-run",      "Skips PINs with runs of identical digits, shifting all later PINs", or_none(forbid_run.map(|run| run.to_string()))),
+      DerivationInput::new("--digits",          "Keeps only the first digits of each PIN", pin_digits),
+      DerivationInput::new("--pin-filler",      "Pads the frame of shorter PINs", format!("{:x}", pin_filler)),
+      DerivationInput::new("--check-digit",     "Appends an ISO 7064 check digit to each PIN", check_digit),
+    ]
+  }
+}
+
+impl Default for Parameters {
+  fn default() -> Self {
+    Self {
+      algorithm:            Algorithm::DoubleSHA512,
+      count:                NUMBER_OF_PINS,
+      auto_extend:          false,
+      alphabet:             Alphabet::Decimal,
+      allow_empty_serials:  false,
+      hash_stages:          HashStages::Double,
+      hash_iterations:      1,
+      check_entropy:        false,
+      sort_serials:         false,
+      dedup_serials:        false,
+      min_distinct_serials: None,
+      forbid_run:           None,
+      memory_lock:          MemoryLock::Off,
+      pin_digits:           Pin::LENGTH,
+      pin_filler:           Pin::FILLER,
+      check_digit:          false,
+      length_bind:          false,
+      explain:              false,
+      epoch:                0,
+      machine_id:           None,
+      per_index:            false,
+      argon2_cost:          argon2::Cost::default(),
+    }
+  }
+}
+
 /// A pseudo-random number generator to calculate the PINs.
-struct Random(IntoIter<u8, {2*SHA512_HASH_LENGTH}>);
+/// The buffer is overwritten with zeros when dropped, see `memory::zeroize`.
+struct Random {
+  buffer:         Vec<u8>,
+  position:       usize,
+  auto_extend:    bool,
+  extensions:     usize,
+  alphabet:       Alphabet,
+  forbid_run:     Option<usize>,
+  pin_digits:     u8,
+  pin_filler:     u8,
+  check_digit:    bool,
+  /// Whether to lock the buffer, also of copies, see `try_clone`, and whether it is locked.
+  memory_lock:    MemoryLock,
+  locked:         bool,
+  /// Count the bytes rejected by the alphabet and the PINs rejected by `allows`, see `--explain`.
+  rejected_bytes: usize,
+  rejected_pins:  usize,
+  explain:        bool,
+  per_index:      bool,
+}
+
+impl Drop for Random {
+  fn drop(&mut self) {
+    memory::zeroize(&mut self.buffer);
+    if self.locked {
+      memory::unlock(self.buffer.as_ptr(), self.buffer.capacity());
+    }
+  }
+}
 
 impl Random {
+  /// Limit the number of hash rounds appended by `extend`.
+  const MAX_EXTENSIONS: usize = 0x40;
+
   /// Initialise a pseudo-random number generator.
-  fn new(buffer: [u8; 2*SHA512_HASH_LENGTH]) -> Self {
-    Self(buffer.into_iter())
+  fn new(buffer: Vec<u8>) -> Self {
+    Self {
+      buffer,
+      position:       0,
+      auto_extend:    false,
+      extensions:     0,
+      alphabet:       Alphabet::Decimal,
+      forbid_run:     None,
+      pin_digits:     Pin::LENGTH,
+      pin_filler:     Pin::FILLER,
+      check_digit:    false,
+      memory_lock:    MemoryLock::Off,
+      locked:         false,
+      rejected_bytes: 0,
+      rejected_pins:  0,
+      explain:        false,
+      per_index:      false,
+    }
   }
 
-  /// Try to obtain the next valid byte.
-  fn next(&mut self) -> Result<u8, Error> {
-    self.0
-    .find_map(
-      |byte| (
-        (byte < 200)
-        .then_some(
-          ( ((byte % 100) / 10) << 4 ) & 0xf0 // most significant digit
-          | (byte % 10)                       // least significant digit
-        )
+  /// Initialise a pseudo-random number generator from exactly these bytes instead of hashes,
+  ///   padded with zeros or truncated to the size of the hashes, so tests can drive `Pin::from_prng` directly.
+  #[cfg(test)]
+  pub(crate) fn from_seed_bytes(bytes: &[u8]) -> Self {
+    let mut buffer = bytes[..bytes.len().min(2 * SHA512_HASH_LENGTH)].to_vec();
+    buffer.resize(2 * SHA512_HASH_LENGTH, 0);
+    Self::new(buffer)
+  }
+
+  /// Initialise a pseudo-random number generator from randomness derived elsewhere, e.g. by an HSM,
+  ///   instead of from the hashes of the serial numbers, see `--seed-from-file`.
+  /// There must be at least `size` bytes, as much as the hashes would fill, so the PINs are not weaker.
+  pub fn from_bytes(bytes: &[u8], size: usize) -> Result<Self, Error> {
+    (bytes.len() >= size)
+    .then(|| Self::new(bytes.to_vec()))
+    .ok_or(Error::SeedTooShort)
+    .inspect_err(|_| eprintln!("Seed has {} bytes, expected at least {}", bytes.len(), size))
+  }
+
+  /// Extend the buffer instead of failing, when the randomness runs out.
+  fn with_auto_extend(mut self, auto_extend: bool) -> Self {
+    self.auto_extend = auto_extend;
+    self
+  }
+
+  /// Obtain digit pairs of this alphabet.
+  fn with_alphabet(mut self, alphabet: Alphabet) -> Self {
+    self.alphabet = alphabet;
+    self
+  }
+
+  /// Reject PINs with this many identical consecutive digits, see `Pin::from_prng`.
+  fn with_forbid_run(mut self, forbid_run: Option<usize>) -> Self {
+    self.forbid_run = forbid_run;
+    self
+  }
+
+  /// Draw PINs of this many digits, padded with the filler nibble, see `Pin::truncated`.
+  fn with_pin_digits(mut self, pin_digits: u8, pin_filler: u8) -> Self {
+    self.pin_digits = pin_digits;
+    self.pin_filler = pin_filler;
+    self
+  }
+
+  /// Append a check digit to each PIN, see `Pin::with_check_digit`.
+  fn with_check_digit(mut self, check_digit: bool) -> Self {
+    self.check_digit = check_digit;
+    self
+  }
+
+  /// Report how much randomness each PIN consumed, see `try_take_pins`.
+  fn with_explain(mut self, explain: bool) -> Self {
+    self.explain = explain;
+    self
+  }
+
+  /// Hash the first hash `iterations - 1` more times and refill the buffer with the hash chain of the result,
+  ///   to slow down guessing the serial numbers, see `--hash-iterations`.
+  /// With 1 iteration, the default, the buffer is kept as is, i.e. the first hash is the hash of the serial numbers,
+  ///   followed by the hash of that hash, so the PINs are the same as without this option.
+  /// The buffer is the same hash chain as with fewer iterations, only starting later,
+  ///   so PINs drawn from beyond the first hash with fewer iterations may recur with more.
+  fn stretched(mut self, iterations: u32) -> Self {
+    if iterations > 1 && self.buffer.len() >= SHA512_HASH_LENGTH {
+      let mut block = Wiped(Output::<Sha512>::clone_from_slice(&self.buffer[..SHA512_HASH_LENGTH]));
+      (1..iterations).for_each(|_| block.0 = Sha512::digest(block.0));
+      fill_hash_chain(block.0, &mut self.buffer);
+    }
+    self
+  }
+
+  /// Draw each PIN from its own generator, see `for_index`.
+  fn with_per_index(mut self, per_index: bool) -> Self {
+    self.per_index = per_index;
+    self
+  }
+
+  /// Derive the generator of the PIN at `index`, see `--per-index`:
+  ///   Its buffer is filled with the hash chain of `Sha512(first hash || index)`, the index as 64 bit little endian integer,
+  ///   so each PIN is derived independently of the others instead of being a slice of a single stream.
+  /// This changes all PINs; the PUKs are still drawn from the single stream, see `try_calculate_pins_and_puks`.
+  fn for_index(&self, index: usize) -> Result<Self, Error> {
+    let mut prng = self.try_clone()?;
+    let hasher = Sha512::new()
+    .chain_update(&self.buffer[..SHA512_HASH_LENGTH.min(self.buffer.len())])
+    .chain_update((index as u64).to_le_bytes());
+    fill_random_buffer(hasher, &mut prng.buffer);
+    prng.position = 0;
+    prng.extensions = 0;
+    prng.per_index = false;
+    Ok(prng)
+  }
+
+  /// Copy the generator, with the buffer locked into memory by the same policy as the original one, see `--mlock`.
+  /// The buffer is locked before the randomness is copied into it and has the same capacity,
+  ///   so neither the copy nor its extensions are ever in memory which might be swapped.
+  fn try_clone(&self) -> Result<Self, Error> {
+    let mut prng = Self {
+      buffer:         Vec::with_capacity(self.buffer.capacity()),
+      position:       self.position,
+      auto_extend:    self.auto_extend,
+      extensions:     self.extensions,
+      alphabet:       self.alphabet,
+      forbid_run:     self.forbid_run,
+      pin_digits:     self.pin_digits,
+      pin_filler:     self.pin_filler,
+      check_digit:    self.check_digit,
+      memory_lock:    self.memory_lock,
+      locked:         false,
+      rejected_bytes: self.rejected_bytes,
+      rejected_pins:  self.rejected_pins,
+      explain:        self.explain,
+      per_index:      self.per_index,
+    };
+    prng.locked = self.memory_lock.lock(prng.buffer.as_ptr(), prng.buffer.capacity())?;
+    prng.buffer.extend_from_slice(&self.buffer);
+    Ok(prng)
+  }
+
+  /// Lock the buffer into memory according to the policy, so the randomness is never swapped to disk.
+  /// The buffer is moved to an allocation large enough for all extensions first,
+  ///   so `extend` never moves it out of the locked memory, and the old one is zeroed.
+  fn with_memory_lock(mut self, policy: MemoryLock) -> Result<Self, Error> {
+    if policy != MemoryLock::Off {
+      self.reserve_extensions();
+      self.locked = policy.lock(self.buffer.as_ptr(), self.buffer.capacity())?;
+    }
+    self.memory_lock = policy;
+    Ok(self)
+  }
+
+  /// Move the buffer to an allocation large enough for all remaining extensions and zero the old one,
+  ///   so growing the buffer never leaves a copy of the randomness behind in freed memory.
+  fn reserve_extensions(&mut self) {
+    let mut buffer = Vec::with_capacity(
+      self.buffer.len() + (Self::MAX_EXTENSIONS - self.extensions) * SHA512_HASH_LENGTH
+    );
+    buffer.extend_from_slice(&self.buffer);
+    memory::zeroize(&mut self.buffer);
+    self.buffer = buffer;
+  }
+
+  /// Configure the generator according to the parameters of the derivation,
+  ///   testing the randomness for bias, if requested.
+  fn with_parameters(self, parameters: Parameters) -> Result<Self, Error> {
+    let prng = self
+    .with_auto_extend(parameters.auto_extend)
+    .with_alphabet(parameters.alphabet)
+    .with_forbid_run(parameters.forbid_run)
+    .with_pin_digits(parameters.pin_digits, parameters.pin_filler)
+    .with_check_digit(parameters.check_digit)
+    .with_explain(parameters.explain)
+    .with_per_index(parameters.per_index)
+    .with_memory_lock(parameters.memory_lock)?;
+    if parameters.check_entropy {
+      prng.check_entropy()?;
+    }
+    Ok(prng)
+  }
+
+  /// Whether the PIN may be used, i.e. it has no forbidden run of digits.
+  fn allows(&self, pin: &Pin) -> bool {
+    self.forbid_run.is_none_or(|run| !pin.has_run(run))
+  }
+
+  /// Append another hash round to the buffer:
+  ///   The SHA512 of the last hash block, continuing the chain the buffer was filled with.
+  /// Therefore PINs calculated from the extended buffer are still deterministic.
+  /// If the buffer has no room for the hash, it is moved first, see `reserve_extensions`,
+  ///   instead of letting the vector reallocate and free the old randomness without zeroing it.
+  fn extend(&mut self) -> Result<(), Error> {
+    (self.auto_extend && self.extensions < Self::MAX_EXTENSIONS)
+    .then(|| {
+      if self.buffer.capacity() < self.buffer.len() + SHA512_HASH_LENGTH {
+        self.reserve_extensions();
+      }
+      let block = Wiped(Sha512::digest(&self.buffer[self.buffer.len() - SHA512_HASH_LENGTH..]));
+      self.buffer.extend_from_slice(&block.0);
+      self.extensions += 1;
+      eprintln!(
+        "Randomness exhausted, extended by another hash round to {} bytes",
+        self.buffer.len()
+      );
+    })
+    .ok_or(Error::EndOfRandomness)
+  }
+
+  /// Critical values of the chi-square distribution at a significance of 0.0001,
+  ///   for 1 to 15 degrees of freedom, one less than the radix of the alphabet.
+  /// An intact hash exceeds them only once in 10,000 derivations.
+  const MAX_CHI_SQUARE: [f64; 15] = [
+    15.14, 18.42, 21.11, 23.51, 25.74, 27.86, 29.88, 31.83,
+    33.72, 35.56, 37.37, 39.13, 40.87, 42.58, 44.26,
+  ];
+
+  /// Fail if the digits of the buffer are implausibly skewed, indicating a broken hash or buffer,
+  ///   by a chi-square test of how often each digit occurs.
+  fn check_entropy(&self) -> Result<(), Error> {
+    let radix = usize::from(self.alphabet.radix());
+    let threshold = Self::MAX_CHI_SQUARE[radix - 2];
+    let mut counts = vec![0usize; radix];
+    self.buffer
+    .iter()
+    .filter_map(|&byte| self.alphabet.digit_pair(byte))
+    .flat_map(DigitPair::digits)
+    .for_each(|digit| counts[digit as usize] += 1);
+
+    let expected = counts.iter().sum::<usize>() as f64 / radix as f64;
+    let chi_square = counts
+    .iter()
+    .map(|&count| (count as f64 - expected).powi(2) / expected)
+    .sum::<f64>();
+    (expected > 0.0 && chi_square <= threshold)
+    .then_some(())
+    .ok_or(Error::WeakRandomness)
+    .inspect_err(|_|
+      eprintln!(
+        "Digits are implausibly skewed: chi-square {:.2} exceeds {:.2}, counts {:?}",
+        chi_square,
+        threshold,
+        counts
       )
     )
-    .ok_or("End of randomness")
+  }
+
+  /// Count how often each digit pair occurs in the whole buffer, by the value of the pair,
+  ///   and how many bytes the alphabet rejects, see `--rng-histogram`.
+  /// Each byte is counted exactly once, so the counts add up to the size of the buffer.
+  fn histogram(&self) -> (Vec<usize>, usize) {
+    let radix = usize::from(self.alphabet.radix());
+    let mut counts = vec![0usize; radix * radix];
+    let mut rejected = 0;
+    self.buffer
+    .iter()
+    .for_each(|&byte| match self.alphabet.digit_pair(byte) {
+      Some(digit_pair)
+      =>  {
+            let [most, least] = digit_pair.digits();
+            counts[usize::from(most) * radix + usize::from(least)] += 1;
+          },
+      None
+      =>  rejected += 1,
+    });
+    (counts, rejected)
+  }
+
+  /// Get the number of PINs the buffer suffices for, without extending it.
+  /// Rejected PINs consume randomness too, so with `forbid_run` the PINs are drawn from a copy.
+  fn max_pins(&self) -> Result<usize, Error> {
+    match self.forbid_run {
+      None
+      =>  Ok(
+            self.buffer
+            .iter()
+            .filter(|&&byte| self.alphabet.digit_pair(byte).is_some())
+            .count()
+            / Pin::DIGIT_PAIRS
+          ),
+      Some(_)
+      =>  {
+            let mut prng = self.try_clone()?.with_auto_extend(false);
+            prng.position = 0;
+            Ok(iter::from_fn(|| Pin::from_prng(&mut prng).ok()).count())
+          },
+    }
+  }
+
+  /// Skip this many PINs, including those rejected in between, see `Pin::from_prng`.
+  fn skip_pins(&mut self, count: usize) -> Result<(), Error> {
+    (0..count).try_for_each(|_| Pin::from_prng(self).map(|_| ()))
+  }
+
+  /// Try to obtain the next valid digit pair.
+  fn next(&mut self) -> Result<DigitPair, Error> {
+    loop {
+      match
+        self.buffer[self.position..]
+        .iter()
+        .enumerate()
+        .find_map(|(offset, &byte)| self.alphabet.digit_pair(byte).map(|digit_pair| (offset, digit_pair)))
+      {
+        Some((offset, digit_pair)) => {
+          self.position += offset + 1;
+          self.rejected_bytes += offset;
+          break Ok(digit_pair);
+        },
+        None => {
+          self.rejected_bytes += self.buffer.len() - self.position;
+          self.position = self.buffer.len();
+          self.extend()?;
+        },
+      }
+    }
   }
 }
 
-#[derive(Debug)]
-struct SerialNumber([u8; Self::LENGTH]);
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct SerialNumber([u8; Self::LENGTH]);
 
 impl SerialNumber {
   const LENGTH: usize = 8;
+  /// Placeholder for the serial number of a card reader which cannot be read, see `--missing-as-empty`.
+  /// Serial numbers have a fixed length, so the empty serial number is represented by eight zero bytes,
+  ///   which a card reader reporting them would be warned about, see `check_serial_number`.
+  /// None of these bytes are hashed, see `hashed`.
+  const MISSING: Self = Self([0; Self::LENGTH]);
+
+  /// Get the bytes of the serial number fed into the hashes of the derivation:
+  ///   None for `MISSING`, so a card reader which cannot be read contributes like an empty serial number.
+  fn hashed(&self) -> &[u8] {
+    if *self == Self::MISSING { &[] } else { &self.0 }
+  }
+}
+
+impl FromStr for SerialNumber {
+  type Err = Error;
+
+  fn from_str(value: &str) -> Result<Self, Error> {
+    value
+    .as_bytes()
+    .try_into()
+    .map(Self)
+    .map_err(|_| Error::InvalidArguments)
+  }
+}
+
+/// The validated serial numbers to derive the PINs from, in the order they are hashed.
+/// All policies of the set are applied by `new`, so a derivation cannot skip them.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SerialNumbers(Vec<SerialNumber>);
+
+impl SerialNumbers {
+  /// Validate the serial numbers according to the parameters:
+  ///   Reject an empty set, unless allowed, or one with too few distinct serial numbers, if requested,
+  ///   and sort them or remove duplicates, if requested.
+  /// Without `dedup_serials`, a serial number read from several card readers is hashed once for each of them,
+  ///   which is intended, but warned about, as it rarely is what two distinct cards look like.
+  pub(crate) fn new(mut serial_numbers: Vec<SerialNumber>, parameters: Parameters) -> Result<Self, Error> {
+    (!serial_numbers.is_empty() || parameters.allow_empty_serials)
+    .then_some(())
+    .ok_or(Error::NoSerials)
+    .inspect_err(|_| eprintln!("No serial numbers to derive PINs from, see --allow-empty-serials"))?;
+
+    if parameters.sort_serials {
+      serial_numbers.sort();
+    }
+    let mut seen = Vec::with_capacity(serial_numbers.len());
+    let mut duplicates = 0;
+    serial_numbers.retain(|serial_number| {
+      let duplicate = seen.contains(serial_number);
+      seen.push(*serial_number);
+      duplicates += usize::from(duplicate);
+      !(duplicate && parameters.dedup_serials)
+    });
+    if duplicates > 0 && !parameters.dedup_serials {
+      eprintln!(
+        "Warning: {} of {} serial numbers are duplicates, each is hashed again, see --dedup-serials",
+        duplicates,
+        seen.len()
+      );
+    }
+    let distinct = seen.len() - duplicates;
+    parameters.min_distinct_serials
+    .is_none_or(|minimum| distinct >= minimum)
+    .then_some(())
+    .ok_or(Error::InsufficientDistinctSerials)
+    .inspect_err(|_|
+      eprintln!(
+        "Only {} of {} serial numbers are distinct, fewer than required by --min-distinct-serials",
+        distinct,
+        seen.len()
+      )
+    )?;
+    Ok(Self(serial_numbers))
+  }
+
+  /// Get a stable identifier of the set, see `serials_fingerprint`.
+  pub fn fingerprint(&self) -> [u8; 32] {
+    serials_fingerprint(&self.0)
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = &SerialNumber> {
+    self.0.iter()
+  }
+
+  pub fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+}
+
+/// Get a stable identifier of a set of serial numbers, regardless of their order.
+pub fn serials_fingerprint(serials: &[SerialNumber]) -> [u8; 32] {
+  let mut sorted: Vec<&SerialNumber> = serials.iter().collect();
+  sorted.sort();
+  sorted
+  .iter()
+  .fold(
+    Sha256::new(),
+    |hasher, serial_number| hasher.chain_update(serial_number.0),
+  )
+  .finalize()
+  .into()
+}
+
+/// Prefix of the serial numbers hashed by `serials_digest`, which separates it from the randomness.
+const SERIALS_DIGEST_DOMAIN: &[u8] = b"konnektor-serials-digest-v1";
+
+/// Get the SHA512 of the concatenated serial numbers, in order or sorted, so two parties can confirm identical inputs,
+///   see `--print-serials-digest`.
+/// It is not the first hash of `try_derive_prng`, which is the randomness itself and would reveal the PINs,
+///   but the serial numbers are prefixed by `SERIALS_DIGEST_DOMAIN`.
+pub fn serials_digest(serials: &[SerialNumber], sort: bool) -> [u8; SHA512_HASH_LENGTH] {
+  let mut serials: Vec<&SerialNumber> = serials.iter().collect();
+  if sort {
+    serials.sort();
+  }
+  serials
+  .iter()
+  .fold(
+    Sha512::new().chain_update(SERIALS_DIGEST_DOMAIN),
+    |hasher, serial_number| hasher.chain_update(serial_number.0),
+  )
+  .finalize()
+  .into()
+}
+
+/// Check that an index refers to one of `count` PINs.
+/// Every index given by the user is checked with this, so all agree on the bounds.
+fn check_index(index: usize, count: usize) -> Result<(), Error> {
+  (index < count)
+  .then_some(())
+  .ok_or(Error::PinIndexOutOfRange)
+}
+
+/// Resolve an index given by the user against the number of PINs,
+///   where negative indices count from the end, i.e. -1 is the last PIN.
+fn resolve_index(index: isize, count: usize) -> Result<usize, Error> {
+  let resolved = if index < 0 {
+    count.checked_sub(index.unsigned_abs())
+  } else {
+    Some(index.unsigned_abs())
+  };
+  resolved
+  .ok_or(Error::PinIndexOutOfRange)
+  .and_then(|resolved| check_index(resolved, count).map(|_| resolved))
+  .inspect_err(|_| eprintln!("PIN index {} out of range ({}..{})", index, -(count as isize), count))
 }
 
 /// Get the PIN of a single smart card.
-#[allow(dead_code)]
 fn try_get_pin_by_id(
-  serial_numbers: MaybeSerialNumbers,
+  serial_numbers: &SerialNumbers,
+  parameters: Parameters,
   pin_index: usize,
 ) -> Result<Pin, Error> {
-  (pin_index < NUMBER_OF_PINS)
-  .then_some(
-    try_calculate_all_pins(serial_numbers)
-    .map(|pin_data| pin_data[pin_index])
-  )
-  .transpose()?
-  .ok_or("pin-index out of range")
+  try_calculate_pin_range(serial_numbers, parameters, pin_index..pin_index + 1)
+  .map(|pins| pins[0].1)
+}
+
+/// Get a contiguous range of the PINs of all smart cards,
+///   advancing the pseudo-random number generator past the skipped ones without building them,
+///   or, with `--per-index`, deriving the generator of each PIN on its own, see `Random::for_index`.
+fn try_calculate_pin_range(
+  serial_numbers: &SerialNumbers,
+  parameters: Parameters,
+  range: Range<usize>,
+) -> Result<Vec<IndexedPin>, Error> {
+  range
+  .end
+  .checked_sub(1)
+  .filter(|&last| range.start <= last)
+  .ok_or(Error::PinIndexOutOfRange)
+  .and_then(|last| check_index(last, parameters.count))
   .inspect_err(|_| eprintln!(
-      "Input parameter pin_index {} out of range (0–{})",
-      pin_index,
-      NUMBER_OF_PINS - 1
+      "PIN indices {}..{} out of range (0..{})",
+      range.start,
+      range.end,
+      parameters.count
     )
-  )
+  )?;
+
+  let mut prng = try_derive_prng_with_parameters(serial_numbers, parameters)?;
+  warn_about_pin_margin(&prng, parameters)?;
+  if !prng.per_index {
+    prng.skip_pins(range.start)?;
+  }
+  range
+  .map(|index| {
+    let mut own = prng.per_index.then(|| prng.for_index(index)).transpose()?;
+    explained_pin(own.as_mut().unwrap_or(&mut prng), index, &mut io::stderr()).map(|pin| (index, pin))
+  })
+  .collect()
+}
+
+/// The PINs together with the serial numbers they were derived from,
+///   so nothing is ambiguous about which inputs produced which outputs.
+#[derive(Clone, Debug)]
+pub struct CalculationResult {
+  pub serials:  SerialNumbers,
+  pub pins:     ListOfPins,
+  pub meta:     DerivationMeta,
+}
+
+/// Calculate all PINs of all smart cards and keep them together with the serial numbers.
+fn try_calculate(
+  serial_numbers: SerialNumbers,
+  parameters: Parameters,
+) -> Result<CalculationResult, Error> {
+  let pins = try_calculate_all_pins(&serial_numbers, parameters)?;
+  Ok(CalculationResult {
+    meta:     DerivationMeta::new(parameters, pins.len())
+              .with_serials(&serial_numbers),
+    serials:  serial_numbers,
+    pins,
+  })
 }
 
 /// Get all PINs of all smart cards.
-fn try_calculate_all_pins(serial_numbers: MaybeSerialNumbers) -> Result<ListOfPins, Error> {
-  try_calculate_all_pins_with_algorithm(serial_numbers, Algorithm::DoubleSHA512)
+fn try_calculate_all_pins(
+  serial_numbers: &SerialNumbers,
+  parameters: Parameters,
+) -> Result<ListOfPins, Error> {
+  try_calculate_all_pins_with_algorithm(serial_numbers, parameters, parameters.algorithm)
 }
 
 /// Obtain the PINs of the  Gerätespezifische Security Module Card Konnektor.
+/// The algorithm overrides the one of the parameters.
 fn try_calculate_all_pins_with_algorithm(
-  serial_numbers: MaybeSerialNumbers,
+  serial_numbers: &SerialNumbers,
+  parameters: Parameters,
   algorithm: Algorithm,
 ) -> Result<ListOfPins, Error> {
   match algorithm {
-    Algorithm::DefaultPin => Ok([Pin::default(); NUMBER_OF_PINS]),
-    Algorithm::DoubleSHA512
-    =>  try_derive_prng(serial_numbers)
-        .map(|mut prng| array::try_from_fn(|_| Pin::from_prng(&mut prng)))?
-        .inspect_err(|error|
-          eprintln!(
-            "connector-ident: Could not get connector ident number: {}",
-            error
-          )
+    Algorithm::DefaultPin
+    =>  {
+          let pin = Pin::default().truncated(parameters.pin_digits, parameters.pin_filler);
+          Ok(vec![if parameters.check_digit { pin.with_check_digit() } else { pin }; parameters.count])
+        },
+    Algorithm::DoubleSHA512 | Algorithm::XorPerSerialSha512 | Algorithm::Argon2id
+    =>  try_derive_prng_with_parameters(serial_numbers, Parameters { algorithm, ..parameters })
+        .and_then(|mut prng| {
+          warn_about_pin_margin(&prng, parameters)?;
+          try_take_pins(&mut prng, parameters.count)
+        }),
+  }
+}
+
+/// Get all PINs of all smart cards once for each algorithm, to compare them, see `--compare-algorithms`.
+fn try_compare_algorithms(
+  serial_numbers: &SerialNumbers,
+  parameters: Parameters,
+  algorithms: &[Algorithm],
+) -> Result<Vec<(Algorithm, ListOfPins)>, Error> {
+  algorithms
+  .iter()
+  .map(|&algorithm|
+    try_calculate_all_pins_with_algorithm(serial_numbers, parameters, algorithm)
+    .map(|pins| (algorithm, pins))
+    .inspect_err(|error| eprintln!("Cannot calculate the PINs with algorithm {}: {}", algorithm, error))
+  )
+  .collect()
+}
+
+/// Take the next `count` PINs from the pseudo-random number generator.
+/// With `--explain`, report on stderr how many bytes of randomness each PIN consumed,
+///   as rejection sampling makes this vary.
+fn try_take_pins(prng: &mut Random, count: usize) -> Result<ListOfPins, Error> {
+  (0..count)
+  .map(|index| {
+    let mut own = prng.per_index.then(|| prng.for_index(index)).transpose()?;
+    explained_pin(own.as_mut().unwrap_or(&mut *prng), index, &mut io::stderr())
+    .inspect_err(|error|
+      eprintln!(
+        "Could not derive PIN {} of {}: {}",
+        index,
+        count,
+        error
+      )
+    )
+  })
+  .collect()
+}
+
+/// Take the PIN of index `index` from the pseudo-random number generator,
+///   reporting to `report`, i.e. stderr, with `--explain` how many bytes of randomness it consumed.
+fn explained_pin(prng: &mut Random, index: usize, report: &mut dyn Write) -> Result<Pin, Error> {
+  let (position, rejected_bytes, rejected_pins) = (prng.position, prng.rejected_bytes, prng.rejected_pins);
+  let pin = Pin::from_prng(prng)?;
+  if prng.explain {
+    writeln!(
+      report,
+      "PIN {} consumed {} bytes, {} of them rejected, and {} rejected PINs",
+      index,
+      prng.position - position,
+      prng.rejected_bytes - rejected_bytes,
+      prng.rejected_pins - rejected_pins
+    )
+    .map_err(|_| Error::CannotWritePins)?;
+  }
+  Ok(pin)
+}
+
+/// Get all PINs of all smart cards and a PUK of `puk_length` digits for each of them.
+/// The PUKs are taken from the pseudo-random number generator after all PINs,
+///   i.e. PIN 0, …, PIN n-1, PUK 0, …, PUK n-1,
+///   so the PINs are the same with or without PUKs.
+fn try_calculate_pins_and_puks(
+  serial_numbers: &SerialNumbers,
+  parameters: Parameters,
+  puk_length: usize,
+) -> Result<(ListOfPins, Vec<Puk>), Error> {
+  let mut prng = try_derive_prng_with_parameters(serial_numbers, parameters)?;
+  warn_about_pin_margin(&prng, parameters)?;
+  let pins = try_take_pins(&mut prng, parameters.count)?;
+  let puks = (0..parameters.count)
+  .map(|index|
+    Puk::from_prng(&mut prng, puk_length)
+    .inspect_err(|error|
+      eprintln!(
+        "Could not derive PUK {} of {}: {}",
+        index,
+        parameters.count,
+        error
+      )
+    )
+  )
+  .collect::<Result<Vec<Puk>, Error>>()?;
+  Ok((pins, puks))
+}
+
+/// Derive an independent set of PINs for each card from the serial numbers of its readers,
+///   which are selected by their slots, before sorting or removing duplicates.
+/// Each card is derived on its own, so one failing does not prevent the others.
+fn try_calculate_grouped_pins(
+  serial_numbers: &[SerialNumber],
+  groups: &[Vec<usize>],
+  parameters: Parameters,
+) -> Vec<Result<CalculationResult, Error>> {
+  groups
+  .iter()
+  .enumerate()
+  .map(|(card, readers)|
+    readers
+    .iter()
+    .map(|&reader|
+      serial_numbers
+      .get(reader)
+      .copied()
+      .ok_or(Error::ReaderIndexOutOfRange)
+      .inspect_err(|_|
+        eprintln!(
+          "Reader {} of group {:?} out of range (0–{})",
+          reader,
+          readers,
+          serial_numbers.len().saturating_sub(1)
+        )
+      )
+    )
+    .collect::<Result<Vec<SerialNumber>, Error>>()
+    .and_then(|group| SerialNumbers::new(group, parameters))
+    .and_then(|group| try_calculate(group, parameters))
+    .map(|result| CalculationResult { meta: result.meta.with_card(card), ..result })
+  )
+  .collect()
+}
+
+/// Try to get an initialised pseudo-random number generator from the serial numbers,
+///   configured according to the parameters.
+fn try_derive_prng_with_parameters(
+  serial_numbers: &SerialNumbers,
+  parameters: Parameters,
+) -> Result<Random, Error> {
+  let prng = match parameters.algorithm {
+    Algorithm::XorPerSerialSha512
+    =>  try_derive_xor_prng(serial_numbers, parameters.hash_stages, parameters.epoch, parameters.machine_id),
+    Algorithm::Argon2id
+    =>  try_derive_argon2_prng(serial_numbers, parameters),
+    _
+    =>  try_derive_prng(
+          serial_numbers,
+          parameters.hash_stages,
+          parameters.length_bind,
+          parameters.epoch,
+          parameters.machine_id,
         ),
+  }?
+  .stretched(parameters.hash_iterations)
+  .with_parameters(parameters)?;
+
+  if parameters.check_entropy {
+    check_distinct_serials(serial_numbers)?;
   }
+  Ok(prng)
 }
 
-/// Try to get an initialised pseudo-random number generator from either given serial numbers or by reading them from the devices.
-fn try_derive_prng(serial_numbers: MaybeSerialNumbers) -> Result<Random, Error> {
-  let mut buffer = [0u8; {2*SHA512_HASH_LENGTH}];
+/// Calculate all PINs from the randomness of a file instead of the serial numbers, see `Random::from_bytes`.
+/// The file must hold at least as many bytes as the hash stages would fill.
+fn try_calculate_from_seed_file(file_name: &Path, parameters: Parameters) -> Result<CalculationResult, Error> {
+  let seed = Wiped(
+    fs::read(file_name)
+    .inspect_err(|error| eprintln!("Cannot read file {}: {}", file_name.display(), error))
+    .map_err(|_| Error::CannotReadFile)?
+  );
+  let mut prng = Random::from_bytes(&seed.0, parameters.hash_stages.count() * SHA512_HASH_LENGTH)?
+  .with_parameters(parameters)?;
+  let pins = try_take_pins(&mut prng, parameters.count)?;
+  Ok(CalculationResult {
+    serials:  SerialNumbers(Vec::new()),
+    meta:     DerivationMeta::new(parameters, pins.len()),
+    pins,
+  })
+}
 
-  let mut hasher = serial_numbers
-  .map(|ids| Ok(ids.into()))
-  .unwrap_or_else(|| try_read_serial_number_from_devices(None))
-  .inspect_err(|error|
-    eprintln!(
-      "Could not read serial numbers from card readers: {}",
-      error
+/// Print how often each digit pair occurs in the initial randomness and how many bytes are rejected,
+///   to spot degenerate serial numbers, see `Random::histogram`.
+fn print_rng_histogram(serial_numbers: &SerialNumbers, parameters: Parameters) -> Result<(), Error> {
+  let prng = try_derive_prng_with_parameters(serial_numbers, parameters)?;
+  let (counts, rejected) = prng.histogram();
+  let radix = usize::from(parameters.alphabet.radix());
+  counts
+  .iter()
+  .enumerate()
+  .for_each(|(pair, count)|
+    println!(
+      "{}{}: {}",
+      parameters.alphabet.symbol((pair / radix) as u8).unwrap_or('?'),
+      parameters.alphabet.symbol((pair % radix) as u8).unwrap_or('?'),
+      count
     )
-  )?
+  );
+  println!("rejected: {}", rejected);
+  println!("total: {} bytes", prng.buffer.len());
+  Ok(())
+}
+
+/// Share of the maximum number of PINs above which a warning suggests more hash rounds.
+const PIN_MARGIN_PERCENT: usize = 80;
+
+/// Get the number of PINs the initial randomness suffices for, see `--max-pins`.
+fn try_max_pins(serial_numbers: &SerialNumbers, parameters: Parameters) -> Result<usize, Error> {
+  try_derive_prng_with_parameters(serial_numbers, parameters).and_then(|prng| prng.max_pins())
+}
+
+/// Warn if the PINs use up most of the initial randomness of `prng`, unless it is extended anyway:
+///   That is fine, but little margin is left, so more hash rounds are suggested.
+/// Returns whether it warned.
+fn warn_about_pin_margin(prng: &Random, parameters: Parameters) -> Result<bool, Error> {
+  if parameters.auto_extend || parameters.per_index {
+    return Ok(false);
+  }
+  let max_pins = prng.max_pins()?;
+  let warn = parameters.count * 100 > max_pins * PIN_MARGIN_PERCENT;
+  if warn {
+    eprintln!(
+      "Warning: {} PINs use more than {} % of the randomness, which suffices for {} PINs, consider more hash rounds with --hash-stages or --auto-extend",
+      parameters.count,
+      PIN_MARGIN_PERCENT,
+      max_pins
+    );
+  }
+  Ok(warn)
+}
+
+/// Fail if several serial numbers are all the same, e.g. because every reader reports the same one.
+fn check_distinct_serials(serial_numbers: &SerialNumbers) -> Result<(), Error> {
+  let mut serials = serial_numbers.iter();
+  let first = serials.next();
+  (serial_numbers.len() < 2 || serials.any(|serial_number| Some(serial_number) != first))
+  .then_some(())
+  .ok_or(Error::WeakRandomness)
+  .inspect_err(|_| eprintln!("All {} serial numbers are the same", serial_numbers.len()))
+}
+
+/// Try to get an initialised pseudo-random number generator from the serial numbers.
+/// With `length_bind`, each serial number is prefixed by its length as 64 bit little endian,
+///   so no two different lists of serial numbers concatenate to the same bytes.
+/// The serial numbers currently all have the same length, but the PINs differ from those without it.
+/// Integers are always hashed with an explicit endianness, never the native one,
+///   so the PINs are the same on every architecture, see `selftest::run`.
+/// Any epoch but 0 and the machine ID are hashed after the serial numbers, see `chain_epoch_and_machine_id`.
+fn try_derive_prng(
+  serial_numbers: &SerialNumbers,
+  stages:         HashStages,
+  length_bind:    bool,
+  epoch:          u64,
+  machine_id:     Option<MachineId>,
+) -> Result<Random, Error> {
+  let mut buffer = Wiped(vec![0u8; stages.count() * SHA512_HASH_LENGTH]);
+  let hasher = serial_numbers
   .iter()
   .fold(
     Sha512::new(),
-    |hasher, serial_number| hasher.chain_update(serial_number.0),
+    |hasher, serial_number|
+      if length_bind {
+        hasher
+        .chain_update((serial_number.hashed().len() as u64).to_le_bytes())
+        .chain_update(serial_number.hashed())
+      } else {
+        hasher.chain_update(serial_number.hashed())
+      },
   );
+  fill_random_buffer(chain_epoch_and_machine_id(hasher, epoch, machine_id), &mut buffer.0);
+  Ok(Random::new(mem::take(&mut buffer.0)))
+}
 
-  hasher.finalize_into_reset((&mut buffer[..SHA512_HASH_LENGTH]).into());
-  hasher
-  .chain_update(&buffer[..SHA512_HASH_LENGTH])
-  .finalize_into((&mut buffer[SHA512_HASH_LENGTH..]).into());
+/// The salt of `Algorithm::Argon2id`.
+/// The serial numbers are the secret, so a fixed salt suffices to set these PINs apart from other uses of Argon2id.
+const ARGON2_SALT: &[u8] = b"konnektor-pin-argon2id-v1";
 
-  Ok(Random::new(buffer))
+/// Get an initialised pseudo-random number generator from the Argon2id tag of the serial numbers, see `Algorithm::Argon2id`:
+///   The password is the serial numbers concatenated as by `try_derive_prng`, also with `--length-bind`,
+///   the salt is `ARGON2_SALT`, and any epoch but 0 and the machine ID are the associated data, in this order.
+/// The tag of 64 bytes is the first block of the hash chain the buffer is filled with, see `fill_hash_chain`.
+fn try_derive_argon2_prng(serial_numbers: &SerialNumbers, parameters: Parameters) -> Result<Random, Error> {
+  let capacity = serial_numbers.iter().map(|serial_number| serial_number.hashed().len() + 8).sum();
+  let mut password = Wiped(Vec::with_capacity(capacity));
+  serial_numbers
+  .iter()
+  .for_each(|serial_number| {
+    if parameters.length_bind {
+      password.0.extend_from_slice(&(serial_number.hashed().len() as u64).to_le_bytes());
+    }
+    password.0.extend_from_slice(serial_number.hashed());
+  });
+  let associated_data: Vec<u8> = (parameters.epoch != 0)
+  .then(|| parameters.epoch.to_le_bytes())
+  .into_iter()
+  .flatten()
+  .chain(parameters.machine_id.into_iter().flatten())
+  .collect();
+  let tag = Wiped(argon2id(&password.0, ARGON2_SALT, &[], &associated_data, parameters.argon2_cost, SHA512_HASH_LENGTH));
+  let mut buffer = Wiped(vec![0u8; parameters.hash_stages.count() * SHA512_HASH_LENGTH]);
+  fill_hash_chain(Output::<Sha512>::clone_from_slice(&tag.0), &mut buffer.0);
+  Ok(Random::new(mem::take(&mut buffer.0)))
 }
 
-/// Read the serial numbers from the devices.
-fn try_read_serial_number_from_devices(card_readers: Option<ListOfCardReaders>) -> Result<ListOfSerialNumbers, Error> {
-  card_readers
-  .unwrap_or(CARD_READERS)
-  .try_map(
-    |file_name| {
-      let mut serial_number = [0u8; SerialNumber::LENGTH];
-      File::open(file_name)
-      .inspect_err(|error|
-        eprintln!(
-          "Cannot open file {}: {}",
-          file_name,
-          error
-        )
-      )
-      .map_err(|_| "Cannot open smart card readers")?
-      .read_exact(&mut serial_number)
-      .map(|_| SerialNumber(serial_number))
-      .inspect_err(|error|
-        eprintln!(
-          "Cannot read {} bytes from file {}: {}",
-          serial_number.len(),
-          file_name,
-          error
-        )
-      )
-      .map_err(|_| "Cannot read from file")
-    }
+/// Hash an epoch but 0 as little endian integer, see `--epoch`, and then the 16 bytes of the machine ID, if any,
+///   see `--bind-machine-id`, after everything else of the first hash.
+/// Epoch 0 without a machine ID therefore yields the original PINs.
+fn chain_epoch_and_machine_id(hasher: Sha512, epoch: u64, machine_id: Option<MachineId>) -> Sha512 {
+  let hasher = match epoch {
+    0     => hasher,
+    epoch => hasher.chain_update(epoch.to_le_bytes()),
+  };
+  match machine_id {
+    None              => hasher,
+    Some(machine_id)  => hasher.chain_update(machine_id),
+  }
+}
+
+/// Try to get an initialised pseudo-random number generator from the XOR of the hashes of each serial number.
+/// As XOR is commutative, the order of the serial numbers does not matter,
+///   unlike the concatenation hashed by `try_derive_prng`.
+/// But a serial number read twice cancels itself out, see `--dedup-serials`.
+/// Any epoch but 0 and the machine ID are hashed together with the XOR, as in `try_derive_prng`.
+fn try_derive_xor_prng(
+  serial_numbers: &SerialNumbers,
+  stages:         HashStages,
+  epoch:          u64,
+  machine_id:     Option<MachineId>,
+) -> Result<Random, Error> {
+  let mut buffer = Wiped(vec![0u8; stages.count() * SHA512_HASH_LENGTH]);
+  let seed = Wiped(serial_numbers
+  .iter()
+  .fold(
+    [0u8; SHA512_HASH_LENGTH],
+    |seed, serial_number| {
+      let digest = Sha512::digest(serial_number.hashed());
+      array::from_fn(|index| seed[index] ^ digest[index])
+    },
+  ));
+  let seed = match (epoch, machine_id) {
+    (0, None) => seed.0.into(),
+    _         => chain_epoch_and_machine_id(Sha512::new().chain_update(seed.0), epoch, machine_id).finalize(),
+  };
+  fill_hash_chain(seed, &mut buffer.0);
+  Ok(Random::new(mem::take(&mut buffer.0)))
+}
+
+/// Fill a buffer with a chain of hashes:
+///   The first block is the hash of everything fed into the hasher,
+///   each further block is the SHA512 of the previous one.
+/// The last block is truncated, if the buffer is not a multiple of the hash length.
+/// `Random::extend` continues this chain, so a buffer filled with more blocks yields the same bytes.
+fn fill_random_buffer(hasher: Sha512, buffer: &mut [u8]) {
+  fill_hash_chain(hasher.finalize(), buffer)
+}
+
+/// Fill a buffer with a chain of hashes starting with this block, see `fill_random_buffer`.
+/// The block after the buffer is zeroed afterwards, as it is the continuation of the randomness.
+fn fill_hash_chain(block: Output<Sha512>, buffer: &mut [u8]) {
+  let mut block = Wiped(block);
+  for chunk in buffer.chunks_mut(SHA512_HASH_LENGTH) {
+    chunk.copy_from_slice(&block.0[..chunk.len()]);
+    block.0 = Sha512::digest(block.0);
+  }
+}
+
+/// Warn about a serial number that could be read, but still looks wrong:
+///   It contains control characters or the file has more bytes after it.
+/// A single trailing line feed is expected, because sysfs terminates attributes with one.
+/// Returns whether it warned.
+fn check_serial_number(file_name: &str, serial_number: &[u8], rest: &mut impl Read) -> bool {
+  let control = serial_number.iter().any(u8::is_ascii_control);
+  if control {
+    eprintln!(
+      "Warning: Serial number read from file {} contains control characters",
+      file_name
+    );
+  }
+
+  let mut trailing_bytes = Vec::new();
+  let trailing = match rest.by_ref().take(0x100).read_to_end(&mut trailing_bytes) {
+    Ok(_) if trailing_bytes.is_empty() || trailing_bytes == b"\n" => false,
+    Ok(count)
+    =>  {
+          eprintln!(
+            "Warning: File {} has {} more bytes after the serial number",
+            file_name,
+            count
+          );
+          true
+        },
+    Err(error)
+    =>  {
+          eprintln!(
+            "Warning: Cannot check file {} for more bytes after the serial number: {}",
+            file_name,
+            error
+          );
+          true
+        },
+  };
+  control || trailing
+}
+
+/// Read the serial number from the sysfs attribute of a single card reader.
+/// Some reader nodes have a header before the serial number, which is skipped by `offset`, see `--serial-offset`.
+/// Reads interrupted by a signal are retried by `File::open`, `read_exact` and `read_to_end` themselves,
+///   so only genuine failures of the reader are reported, see `read_serial_number_at`.
+fn try_read_serial_number(file_name: &str, offset: u64) -> Result<SerialNumber, Error> {
+  match probe_reader(file_name.as_ref()) {
+    ReaderStatus::Present => Ok(()),
+    status
+    =>  {
+          eprintln!("Card reader of {} is {}", file_name, status);
+          Err(Error::CardReaderUnavailable)
+        },
+  }?;
+
+  let mut file = File::open(reader::resolve_reader_path(file_name.as_ref())?)
+  .inspect_err(|error|
+    eprintln!(
+      "Cannot open file {}: {}",
+      file_name,
+      error
+    )
+  )
+  .map_err(|error| reader::open_error(&error))?;
+
+  read_serial_number_at(file_name, &mut file, offset)
+  .inspect_err(|error|
+    eprintln!(
+      "Cannot read {} bytes at offset {} from file {}: {}",
+      SerialNumber::LENGTH,
+      offset,
+      file_name,
+      error
+    )
+  )
+  .map_err(|_| Error::CannotReadSerialNumber)
+}
+
+/// Read the serial number at `offset` of an opened attribute and check what follows it, see `check_serial_number`.
+/// The serial number must end within the size of the file, unless it reports none, like some pseudo files.
+/// A read interrupted by a signal, i.e. `ErrorKind::Interrupted`, is retried instead of failing.
+fn read_serial_number_at(file_name: &str, file: &mut (impl Read + Seek), offset: u64) -> io::Result<SerialNumber> {
+  let mut serial_number = [0u8; SerialNumber::LENGTH];
+  let size = file.seek(SeekFrom::End(0))?;
+  let end = offset.saturating_add(SerialNumber::LENGTH as u64);
+  if size != 0 && end > size {
+    return Err(io::Error::new(
+      io::ErrorKind::UnexpectedEof,
+      format!("the serial number would end at byte {}, but the file has {}", end, size),
+    ));
+  }
+  file
+  .seek(SeekFrom::Start(offset))
+  .and_then(|_| file.read_exact(&mut serial_number))
+  .map(|_| check_serial_number(file_name, &serial_number, file))
+  .map(|_| SerialNumber(serial_number))
+}
+
+/// Select where the serial numbers come from: The manifest, files, environment or socket given on the command line,
+///   otherwise the ones for testing purposes or the card readers.
+/// They are cached with `--use-cache`, and those of card readers given with `--what-if` are replaced after reading,
+///   then only those starting with `--serial-prefix` are kept.
+/// Serial numbers forbidden with `--forbid-serial` and, with `--forbid-test-serials`, the ones for testing purposes are refused.
+fn serial_source(options: &Options) -> Box<dyn SerialSource + '_> {
+  let mut forbidden = options.forbidden_serials.clone();
+  if options.forbid_test_serials {
+    forbidden.extend(TEST_SERIAL_NUMBERS);
+  }
+  let mut source = unchecked_serial_source(options);
+  if let Some(file) = &options.cache {
+    source = Box::new(CachedSource {
+      source,
+      origin: serial_source_origin(options),
+      file:   file.clone(),
+      key:    options.key.as_ref().map_or(&[], |key| &key.0),
+      ttl:    options.cache_ttl.unwrap_or(CachedSource::DEFAULT_TTL),
+    });
+  }
+  if !options.what_if.is_empty() {
+    source = Box::new(WhatIfSource {
+      source,
+      overrides:  options.what_if.clone(),
+    });
+  }
+  if let Some(prefix) = &options.serial_prefix {
+    source = Box::new(PrefixSource {
+      source,
+      prefix: prefix.clone(),
+    });
+  }
+  Box::new(CheckedSource {
+    source,
+    forbidden,
+  })
+}
+
+/// Describe the options `unchecked_serial_source` selects and configures the source with,
+///   so a cache is bound to them, see `CachedSource::origin`.
+fn serial_source_origin(options: &Options) -> String {
+  format!(
+    "manifest={:?} multi_serial_file={:?} reader_glob={:?} serial_env={} serial_socket={:?} uevent_key={:?} \
+      serial_offset={} missing_as_empty={} card_readers={:?} serial_numbers={:?}",
+    options.manifest,
+    options.multi_serial_file,
+    options.reader_glob,
+    options.serial_env,
+    options.serial_socket,
+    options.uevent_key,
+    options.serial_offset,
+    options.missing_as_empty,
+    CARD_READERS,
+    SERIAL_NUMBERS,
+  )
+}
+
+/// Select where the serial numbers come from, without refusing any.
+fn unchecked_serial_source(options: &Options) -> Box<dyn SerialSource + '_> {
+  match (&options.manifest, &options.multi_serial_file, &options.reader_glob, &options.serial_socket, SERIAL_NUMBERS) {
+    (Some(file_name), _, _, _, _)
+    =>  Box::new(ManifestSource(file_name.clone())),
+    (None, Some(file_name), _, _, _)
+    =>  Box::new(MultiSerialFileSource(file_name.clone())),
+    (None, None, Some(pattern), _, _)
+    =>  Box::new(GlobSource(pattern.clone(), options.serial_offset, options.missing_as_empty)),
+    _ if options.serial_env
+    =>  Box::new(EnvSource),
+    (None, None, None, Some(address), _)
+    =>  Box::new(SocketSource::new(address.clone(), options.key.as_ref().map_or(&[], |key| &key.0))),
+    (None, None, None, None, Some(serial_numbers))
+    =>  Box::new(FixedSource(serial_numbers.to_vec())),
+    (None, None, None, None, None)
+    =>  match &options.uevent_key {
+          Some(key)
+          =>  Box::new(UeventSource {
+                readers:          CARD_READERS,
+                offset:           options.serial_offset,
+                key:              key.clone(),
+                missing_as_empty: options.missing_as_empty,
+              }),
+          None
+          =>  Box::new(DeviceSource(CARD_READERS, options.serial_offset, options.missing_as_empty)),
+        },
+  }
+}
+
+/// Describe the constants of the derivation, one `name = value` per line.
+/// Any difference, e.g. in a fork, may lead to different PINs for the same serial numbers.
+fn algorithm_constants() -> String {
+  format!(
+    concat!(
+      "version = {}\n",
+      "hash = SHA-512\n",
+      "hash_length = {}\n",
+      "control = {:#04x}\n",
+      "stop = {:#04x}\n",
+      "length = {}\n",
+      "modulo = {}\n",
+      "reject_threshold = {}\n",
+      "max_extensions = {}\n",
+    ),
+    env!("CARGO_PKG_VERSION"),
+    SHA512_HASH_LENGTH,
+    Pin::CONTROL,
+    Pin::STOP,
+    Pin::LENGTH,
+    Alphabet::MODULO,
+    Alphabet::REJECT_THRESHOLD,
+    Random::MAX_EXTENSIONS,
   )
 }
 
-fn main() -> Result <(), Error> {
-  try_calculate_all_pins(SERIAL_NUMBERS)?.iter().enumerate()
-  .try_for_each(|(id, pin)| Ok(println!("PIN {}: {}", id, pin)))
+/// Get a short fingerprint of the constants of the derivation, see `algorithm_constants`.
+fn constants_fingerprint(constants: &str) -> String {
+  hex::encode(&Sha256::digest(constants)[..8])
+}
+
+/// Print the constants of the derivation and a short fingerprint of them,
+///   so operators can confirm that two machines derive the same PINs.
+fn print_version_info() {
+  let constants = algorithm_constants();
+  print!("{}", constants);
+  println!("fingerprint = {}", constants_fingerprint(&constants));
+}
+
+/// The inputs besides the parameters the PINs depend on, each with how it affects them, see `--help-derivation`.
+const SERIAL_INPUTS: [(&str, &str); 2] = [
+  ("serial numbers",  "The bytes of each serial number, in the order of the card readers, see --serial-offset"),
+  ("--what-if",       "Replaces the serial numbers of some card readers"),
+];
+
+/// List the inputs of the derivation, one per line, the parameters with their default values,
+///   see `Parameters::derivation_inputs`.
+fn derivation_inputs_help() -> String {
+  let inputs: Vec<(&str, String, &str)> = SERIAL_INPUTS
+  .into_iter()
+  .map(|(name, effect)| (name, String::new(), effect))
+  .chain(
+    Parameters::default()
+    .derivation_inputs()
+    .into_iter()
+    .map(|input| (input.option, format!("[{}]", input.value), input.effect))
+  )
+  .collect();
+  let name_width = inputs.iter().map(|(name, _, _)| name.len()).max().unwrap_or(0);
+  let value_width = inputs.iter().map(|(_, value, _)| value.len()).max().unwrap_or(0);
+  inputs
+  .iter()
+  .map(|(name, value, effect)| format!("  {:name_width$}  {:value_width$}  {}\n", name, value, effect))
+  .collect()
+}
+
+/// Explain that the PINs are a deterministic function of the inputs, which inputs these are,
+///   and the constants they are combined with, as printed by `--version-info`.
+fn print_help_derivation() {
+  println!("The PINs are a deterministic function of the following inputs and constants:");
+  println!("The same inputs yield the same PINs on every machine with the same fingerprint.");
+  println!("Other options, e.g. --count, --auto-extend or --format, only select or present PINs, but never change them.");
+  println!();
+  println!("Inputs:");
+  print!("{}", derivation_inputs_help());
+  println!();
+  println!("Constants:");
+  let constants = algorithm_constants();
+  constants
+  .lines()
+  .for_each(|line| println!("  {}", line));
+  println!("  fingerprint = {}", constants_fingerprint(&constants));
+}
+
+/// Label the PINs with their index.
+fn index_pins(pins: ListOfPins) -> Vec<IndexedPin> {
+  pins.into_iter().enumerate().collect()
+}
+
+/// Write the PINs to the output.
+fn write_pins(
+  formatter: &dyn PinFormatter,
+  pins: &[IndexedPin],
+  puks: &[Puk],
+  meta: &DerivationMeta,
+  writer: &mut dyn Write,
+) -> Result<(), Error> {
+  formatter
+  .render(pins, puks, meta, writer)
+  .inspect_err(|error| eprintln!("Cannot write PINs: {}", error))
+  .map_err(|_| Error::CannotWritePins)
+}
+
+/// Write the PINs of a calculation, with `by_serial` headed by the serial numbers they were derived from,
+///   masked according to the redaction, instead of the number of the card.
+fn write_result(
+  formatter: &dyn PinFormatter,
+  result: CalculationResult,
+  by_serial: bool,
+  redaction: Redaction,
+  writer: &mut dyn Write,
+) -> Result<(), Error> {
+  let meta = if by_serial {
+    write_serials_heading(&result.serials, redaction, writer)
+    .inspect_err(|error| eprintln!("Cannot write PINs: {}", error))
+    .map_err(|_| Error::CannotWritePins)?;
+    DerivationMeta { card: None, ..result.meta }
+  } else {
+    result.meta
+  };
+  write_pins(formatter, &index_pins(result.pins), &[], &meta, writer)
+}
+
+/// Calculate the PINs, or print why not and exit with the status of the error, see `Error::exit_code`.
+fn main() -> ExitCode {
+  match try_main() {
+    Ok(())
+    =>  ExitCode::SUCCESS,
+    Err(error)
+    =>  {
+          eprintln!("Error: {}", error);
+          ExitCode::from(error.exit_code())
+        },
+  }
+}
+
+/// Parse the options, run and report the outcome to syslog and the summary, if asked for.
+fn try_main() -> Result <(), Error> {
+  let arguments: Vec<String> = env::args().skip(1).collect();
+  parse_run_and_log(&arguments, syslog::DevLog::connect)
+}
+
+/// Parse the options and run, logging the outcome to the sink `connect` returns with `--syslog`.
+/// Options which cannot be parsed are logged too, as long as `--syslog` is one of the arguments.
+fn parse_run_and_log<Sink: LogSink>(arguments: &[String], connect: impl FnOnce() -> io::Result<Sink>) -> Result<(), Error> {
+  let parsed = Options::parse(arguments.iter().cloned());
+  let sink = parsed
+  .as_ref()
+  .map_or_else(|_| arguments.iter().any(|argument| argument == "--syslog"), |options| options.syslog)
+  .then(connect)
+  .transpose()
+  .inspect_err(|error| eprintln!("Cannot connect to syslog: {}", error))
+  .map_err(|_| Error::CannotConnectToSyslog)?;
+
+  let outcome = parsed.and_then(run_with_summary);
+  if let Some(sink) = &sink {
+    syslog::log_outcome(sink, &outcome);
+  }
+  outcome
+}
+
+/// Run and report the outcome to the summary, if asked for.
+fn run_with_summary(options: Options) -> Result<(), Error> {
+  let summary = options.summary_json
+  .clone()
+  .map(|file| summary::Summary::start(file, options.parameters.algorithm, options.parameters.count));
+
+  let outcome = run(options);
+  summary.map_or(Ok(()), |summary| summary.finish(&outcome))?;
+  outcome
+}
+
+/// Refuse to print PINs to a terminal, if required, so they only go to a file or pipe,
+///   see `--require-pipe` and `Format::is_binary`.
+/// Whether the standard output is a terminal is passed in, so it can be checked without one.
+fn check_pipe(require_pipe: bool, is_terminal: bool) -> Result<(), Error> {
+  (!require_pipe || !is_terminal)
+  .then_some(())
+  .ok_or(Error::StdoutIsTerminal)
+  .inspect_err(|_| eprintln!("Standard output is a terminal, redirect it to a file or pipe, as required by --require-pipe or a binary --format"))
+}
+
+/// Do whatever the options ask for.
+fn run(options: Options) -> Result<(), Error> {
+  privileges::check(options.allow_root, options.no_root)?;
+  if options.self_test {
+    return selftest::run();
+  }
+  if options.version_info {
+    print_version_info();
+    return Ok(());
+  }
+  if options.help_derivation {
+    print_help_derivation();
+    return Ok(());
+  }
+  if options.list_readers {
+    return reader::list_readers(options.reader_glob.as_deref());
+  }
+  check_pipe(options.require_pipe || options.format.is_binary(), io::stdout().is_terminal())?;
+
+  let formatter = options.format.formatter(options.render);
+  let mut writer: Box<dyn Write> = if options.no_trailing_newline {
+    Box::new(WithoutTrailingNewline::new(io::stdout().lock()))
+  } else {
+    Box::new(io::stdout().lock())
+  };
+  if let Some(file_name) = &options.seed_file {
+    let result = try_calculate_from_seed_file(file_name, options.parameters)?;
+    return present_result(formatter.as_ref(), result, &options, &mut writer);
+  }
+
+  let source = serial_source(&options);
+  if let Some(interval) = options.watch {
+    return watch::watch(&options, source.as_ref(), interval);
+  }
+
+  let read_serials = source.read_serials()?;
+  let serial_numbers = SerialNumbers::new(read_serials.clone(), options.parameters)?;
+
+  if options.fingerprint {
+    let fingerprint = serial_numbers.fingerprint();
+    println!(
+      "Fingerprint of serial numbers: {}",
+      hex::encode(&fingerprint)
+    );
+    return Ok(());
+  }
+
+  if options.print_serials_digest {
+    println!(
+      "Digest of serial numbers: {}",
+      hex::encode(&serials_digest(&serial_numbers.0, options.parameters.sort_serials))
+    );
+    return Ok(());
+  }
+
+  if options.max_pins {
+    println!("Maximum number of PINs: {}", try_max_pins(&serial_numbers, options.parameters)?);
+    return Ok(());
+  }
+
+  if options.rng_histogram {
+    return print_rng_histogram(&serial_numbers, options.parameters);
+  }
+  if options.provenance {
+    let meta = DerivationMeta::new(options.parameters, options.parameters.count);
+    println!(
+      "Provenance: {}",
+      provenance_hash(&meta, &serial_numbers.fingerprint())
+    );
+    return Ok(());
+  }
+
+  if !options.compare_algorithms.is_empty() {
+    let comparison = try_compare_algorithms(&serial_numbers, options.parameters, &options.compare_algorithms)?;
+    return write_comparison(&comparison, options.render, &mut io::stdout().lock())
+    .inspect_err(|error| eprintln!("Cannot write PINs: {}", error))
+    .map_err(|_| Error::CannotWritePins);
+  }
+
+  if !options.groups.is_empty() {
+    let results = try_calculate_grouped_pins(&read_serials, &options.groups, options.parameters);
+    return write_grouped_results(formatter.as_ref(), results, &options, &mut writer);
+  }
+
+  if let Some(range) = options.range.clone() {
+    let pins = try_calculate_pin_range(&serial_numbers, options.parameters, range)?;
+    let meta = DerivationMeta::new(options.parameters, pins.len())
+    .with_serials(&serial_numbers);
+    return write_pins(formatter.as_ref(), &pins, &[], &meta, &mut writer);
+  }
+
+  if let Some(index) = options.index {
+    let index = resolve_index(index, options.parameters.count)?;
+    let pin = try_get_pin_by_id(&serial_numbers, options.parameters, index)?;
+    let meta = DerivationMeta::new(options.parameters, 1)
+    .with_serials(&serial_numbers);
+    return write_pins(formatter.as_ref(), &[(index, pin)], &[], &meta, &mut writer);
+  }
+
+  if let Some(puk_length) = options.puk_length {
+    let (pins, puks) = try_calculate_pins_and_puks(&serial_numbers, options.parameters, puk_length)?;
+    let meta = DerivationMeta::new(options.parameters, pins.len())
+    .with_serials(&serial_numbers);
+    return write_pins(formatter.as_ref(), &index_pins(pins), &puks, &meta, &mut writer);
+  }
+
+  let result = try_calculate(serial_numbers, options.parameters)?;
+  present_result(formatter.as_ref(), result, &options, &mut writer)
+}
+
+/// Write the PINs of each card which could be calculated and report the others, failing if any could not be.
+/// With `--all-or-nothing`, nothing is written unless the PINs of all cards could be calculated.
+fn write_grouped_results(
+  formatter: &dyn PinFormatter,
+  results: Vec<Result<CalculationResult, Error>>,
+  options: &Options,
+  writer: &mut dyn Write,
+) -> Result<(), Error> {
+  if options.all_or_nothing {
+    return results
+    .into_iter()
+    .collect::<Result<Vec<CalculationResult>, Error>>()?
+    .into_iter()
+    .try_for_each(|result| write_result(formatter, result, options.by_serial, options.render.redaction, writer));
+  }
+
+  let count = results.len();
+  let mut failed = 0;
+  for (card, result) in results.into_iter().enumerate() {
+    match result {
+      Ok(result)
+      =>  write_result(formatter, result, options.by_serial, options.render.redaction, writer)?,
+      Err(error)
+      =>  {
+            eprintln!("Cannot calculate the PINs of card {}: {}", card, error);
+            failed += 1;
+          },
+    }
+  }
+  (failed == 0)
+  .then_some(())
+  .ok_or(Error::SomeGroupsFailed)
+  .inspect_err(|_| eprintln!("The PINs of {} of {} cards could not be calculated", failed, count))
+}
+
+/// Page through the PINs, verify them or write them, as the options ask for.
+fn present_result(
+  formatter: &dyn PinFormatter,
+  result: CalculationResult,
+  options: &Options,
+  writer: &mut dyn Write,
+) -> Result<(), Error> {
+  if options.paginate {
+    paginate::check_terminal()?;
+    return paginate::paginate(
+      formatter,
+      &index_pins(result.pins),
+      &result.meta,
+      &mut io::stdin().lock(),
+      &mut io::stdout().lock(),
+    );
+  }
+
+  match (&options.verify_all, options.verify_stdin) {
+    (Some(file_name), _)
+    =>  verify::verify_all(&result.pins, file_name),
+    (None, true)
+    =>  verify::verify_stream(&result.pins, io::stdin().lock(), &mut io::stdout().lock()),
+    (None, false)
+    =>  write_result(formatter, result, options.by_serial, options.render.redaction, writer),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    std::{
+      cell::RefCell,
+      process,
+    },
+  };
+
+  /// Get the test serial numbers, validated with the parameters.
+  fn test_serial_numbers(parameters: Parameters) -> SerialNumbers {
+    SerialNumbers::new(TEST_SERIAL_NUMBERS.to_vec(), parameters).unwrap()
+  }
+
+  /// A reader which is interrupted by a signal before each of the first reads, like a slow card reader.
+  struct Interrupting(usize, io::Cursor<&'static [u8]>);
+
+  impl Read for Interrupting {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+      if self.0 > 0 {
+        self.0 -= 1;
+        return Err(io::Error::from(io::ErrorKind::Interrupted));
+      }
+      self.1.read(buffer)
+    }
+  }
+
+  impl Seek for Interrupting {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+      self.1.seek(position)
+    }
+  }
+
+  /// The serial number is read after a header skipped by `--serial-offset`, but not beyond the end of the file.
+  #[test]
+  fn serial_number_at_offset() {
+    let mut file = io::Cursor::new(b"hdr:23421337\n");
+    assert_eq!(read_serial_number_at("serial", &mut file, 4).unwrap(), SerialNumber(*b"23421337"));
+    let error = read_serial_number_at("serial", &mut file, 6).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+  }
+
+  /// The serial numbers for testing purposes are only refused with `--forbid-test-serials`,
+  ///   so the built-in ones work out of the box.
+  #[test]
+  fn test_serials_are_refused_on_request() {
+    let read = |arguments: &[&str]| {
+      let options = Options::parse(arguments.iter().map(|&argument| argument.to_owned())).unwrap();
+      let serial_numbers = serial_source(&options).read_serials();
+      serial_numbers
+    };
+    assert_eq!(read(&[]), Ok(TEST_SERIAL_NUMBERS.to_vec()));
+    assert_eq!(read(&["--forbid-test-serials"]), Err(Error::ForbiddenSerial));
+    assert_eq!(read(&["--forbid-serial", "12345678"]), Ok(TEST_SERIAL_NUMBERS.to_vec()));
+  }
+
+  /// PINs of every length survive the round trip through a number, also with leading zeros.
+  #[test]
+  fn pin_number_round_trip() {
+    let pins = [
+      Pin::default(),
+      Pin::new(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x07]),
+      Pin::new(&[0x00, 0x12, 0x00, 0x34, 0x99, 0x90]),
+      Pin::new(&[0x09, 0x87, 0x65, 0x43, 0x21, 0x00]).truncated(5, Pin::FILLER),
+      Pin::new(&[0x01, 0x23, 0x45, 0x67, 0x89, 0x01]).truncated(6, Pin::FILLER).with_check_digit(),
+    ];
+    pins
+    .into_iter()
+    .for_each(|pin| assert_eq!(Pin::from_u64(pin.to_u64().unwrap(), pin.1), Ok(pin)));
+    assert_eq!(Pin::default().to_u64(), Some(123_456_789_123));
+    assert_eq!(pins[1].to_u64(), Some(7));
+
+    assert_eq!(Pin::from_u64(1_000_000, 6), Err(Error::InvalidPinNumber));
+    assert_eq!(Pin::from_u64(1, 0), Err(Error::InvalidPinNumber));
+    assert_eq!(Pin::from_u64(1, Pin::LENGTH + 1), Err(Error::InvalidPinNumber));
+    assert_eq!(Pin::new(&[0xab; Pin::DIGIT_PAIRS]).in_alphabet(Alphabet::Hex).to_u64(), None);
+  }
+
+  /// Every parameter the PINs depend on is listed by `--help-derivation` and changes the provenance, no other does.
+  #[test]
+  fn every_input_changes_provenance() {
+    let base = Parameters::default();
+    let variants = [
+      Parameters { sort_serials: true, ..base },
+      Parameters { dedup_serials: true, ..base },
+      Parameters { algorithm: Algorithm::XorPerSerialSha512, ..base },
+      Parameters { argon2_cost: "32,3,4".parse().unwrap(), ..base },
+      Parameters { length_bind: true, ..base },
+      Parameters { epoch: 1, ..base },
+      Parameters { machine_id: Some([0; 0x10]), ..base },
+      Parameters { hash_iterations: 2, ..base },
+      Parameters { per_index: true, ..base },
+      Parameters { hash_stages: HashStages::Single, ..base },
+      Parameters { alphabet: "custom:abc".parse().unwrap(), ..base },
+      Parameters { forbid_run: Some(3), ..base },
+      Parameters { pin_digits: 6, ..base },
+      Parameters { pin_filler: 0xe, ..base },
+      Parameters { check_digit: true, ..base },
+    ];
+    let inputs = base.derivation_inputs();
+    assert_eq!(variants.len(), inputs.len());
+    let help = derivation_inputs_help();
+    inputs
+    .iter()
+    .for_each(|input| assert!(help.contains(input.option), "{} is not explained", input.option));
+
+    let provenance = |parameters| provenance_hash(&DerivationMeta::new(parameters, NUMBER_OF_PINS), &[0; 32]);
+    let mut hashes: Vec<String> = iter::once(base).chain(variants).map(provenance).collect();
+    hashes.sort();
+    hashes.dedup();
+    assert_eq!(hashes.len(), inputs.len() + 1);
+    assert_eq!(provenance(Parameters { auto_extend: true, check_entropy: true, explain: true, ..base }), provenance(base));
+  }
+
+  /// A missing serial number contributes nothing but its length of 0 to the hashes, like an empty one.
+  #[test]
+  fn missing_serial_is_hashed_as_empty() {
+    let parameters = Parameters::default();
+    let [first, _, last] = TEST_SERIAL_NUMBERS;
+    let with_missing = SerialNumbers::new(vec![first, SerialNumber::MISSING, last], parameters).unwrap();
+    let without = SerialNumbers::new(vec![first, last], parameters).unwrap();
+    let first_block = |prng: Random| prng.buffer[..SHA512_HASH_LENGTH].to_vec();
+
+    let expected = Sha512::new()
+    .chain_update(first.0)
+    .chain_update(last.0)
+    .finalize();
+    assert_eq!(first_block(try_derive_prng(&with_missing, HashStages::default(), false, 0, None).unwrap()), &expected[..]);
+    assert_eq!(first_block(try_derive_prng(&without, HashStages::default(), false, 0, None).unwrap()), &expected[..]);
+
+    let expected = Sha512::new()
+    .chain_update(8u64.to_le_bytes())
+    .chain_update(first.0)
+    .chain_update(0u64.to_le_bytes())
+    .chain_update(8u64.to_le_bytes())
+    .chain_update(last.0)
+    .finalize();
+    assert_eq!(first_block(try_derive_prng(&with_missing, HashStages::default(), true, 0, None).unwrap()), &expected[..]);
+
+    let (first, empty, last) = (Sha512::digest(first.0), Sha512::digest([]), Sha512::digest(last.0));
+    let expected: Vec<u8> = (0..SHA512_HASH_LENGTH).map(|index| first[index] ^ empty[index] ^ last[index]).collect();
+    assert_eq!(first_block(try_derive_xor_prng(&with_missing, HashStages::default(), 0, None).unwrap()), expected);
+  }
+
+  /// Interrupted reads of a serial number are retried, they do not fail.
+  #[test]
+  fn interrupted_read_is_retried() {
+    let mut reader = Interrupting(3, io::Cursor::new(b"hdr23421337\n"));
+    assert_eq!(read_serial_number_at("serial", &mut reader, 3).unwrap(), SerialNumber(*b"23421337"));
+    assert_eq!(reader.0, 0);
+  }
+
+  /// A serial number with a control byte or with more bytes after it is still read, but warned about,
+  ///   while the line feed sysfs terminates it with is not.
+  #[test]
+  fn suspicious_serial_is_warned_about() {
+    assert!(!check_serial_number("serial", b"23421337", &mut &b"\n"[..]));
+    assert!(!check_serial_number("serial", b"23421337", &mut &b""[..]));
+    assert!(check_serial_number("serial", b"2342133\x07", &mut &b"\n"[..]));
+    assert!(check_serial_number("serial", b"23421337", &mut &b"9\n"[..]));
+
+    let mut file = io::Cursor::new(b"2342133\x00\n");
+    assert_eq!(read_serial_number_at("serial", &mut file, 0).unwrap(), SerialNumber(*b"2342133\x00"));
+  }
+
+  /// Crafted bytes give known digits: Bytes of at least `Alphabet::REJECT_THRESHOLD` are skipped,
+  ///   the others are taken modulo `Alphabet::MODULO`.
+  #[test]
+  fn pin_from_seed_bytes() {
+    let mut prng = Random::from_seed_bytes(&[12, 234, 34, 56, 255, 78, 91, 200, 123]);
+    let pin = Pin::from_prng(&mut prng).unwrap();
+    assert_eq!(pin.frame_bytes(), [0x2c, 0x12, 0x34, 0x56, 0x78, 0x91, 0x23, 0xff]);
+    assert_eq!((prng.position, prng.rejected_bytes), (9, 3));
+    assert_eq!(Pin::from_prng(&mut prng).unwrap().to_numeric_string(), "000000000000");
+  }
+
+  /// The copies of the randomness `for_index` and `max_pins` work on are locked like the original one,
+  ///   and with `--require-mlock` they fail rather than be swappable.
+  #[test]
+  fn copies_are_locked_like_original() {
+    [MemoryLock::Off, MemoryLock::Try, MemoryLock::Require]
+    .into_iter()
+    .for_each(|policy| match Random::from_seed_bytes(&[1; 0x80]).with_memory_lock(policy) {
+      Ok(prng)
+      =>  {
+            let copy = prng.for_index(3).unwrap();
+            assert_eq!((copy.locked, copy.memory_lock), (prng.locked, policy));
+            assert_eq!(copy.buffer.capacity(), prng.buffer.capacity());
+            assert_eq!(prng.try_clone().unwrap().buffer, prng.buffer);
+          },
+      Err(error)
+      =>  assert_eq!((policy, error), (MemoryLock::Require, Error::CannotLockMemory)),
+    });
+    let prng = Random::from_seed_bytes(&[1; 0x80]).with_forbid_run(Some(3)).with_memory_lock(MemoryLock::Try).unwrap();
+    assert!(prng.max_pins().is_ok());
+  }
+
+  /// With `--explain`, each PIN reports the randomness it consumed, also those of `--range` and `--index`,
+  ///   which are the same as those of the whole list.
+  #[test]
+  fn range_is_explained() {
+    let parameters = Parameters { explain: true, ..Parameters::default() };
+    let serial_numbers = test_serial_numbers(parameters);
+    let all = try_calculate_all_pins(&serial_numbers, parameters).unwrap();
+    let range = try_calculate_pin_range(&serial_numbers, parameters, 2..4).unwrap();
+    assert_eq!(range, vec![(2, all[2]), (3, all[3])]);
+
+    let mut report = Vec::new();
+    let bytes = [0, 0, 0, 0, 0, 0, 200, 255, 12, 34, 56, 78, 91, 23];
+    let mut prng = Random::from_seed_bytes(&bytes).with_explain(true).with_forbid_run(Some(4));
+    explained_pin(&mut prng, 7, &mut report).unwrap();
+    assert_eq!(String::from_utf8(report).unwrap(), "PIN 7 consumed 14 bytes, 2 of them rejected, and 1 rejected PINs\n");
+  }
+
+  /// The fingerprint identifies the set of serial numbers, so permuting them does not change it, but another one does.
+  #[test]
+  fn fingerprint_ignores_order() {
+    let serials = [SerialNumber(*b"12345678"), SerialNumber(*b"87654321"), SerialNumber(*b"11111111")];
+    let fingerprint = serials_fingerprint(&serials);
+    [[0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]]
+    .into_iter()
+    .for_each(|order| assert_eq!(serials_fingerprint(&order.map(|index| serials[index])), fingerprint, "{:?}", order));
+    assert_ne!(serials_fingerprint(&serials[..2]), fingerprint);
+    assert_ne!(serials_fingerprint(&[serials[0], serials[1], SerialNumber(*b"11111112")]), fingerprint);
+  }
+
+  /// A sink which records the messages instead of sending them, see `parse_run_and_log`.
+  struct RecordingSink<'a>(&'a RefCell<Vec<(syslog::Severity, String)>>);
+
+  impl LogSink for RecordingSink<'_> {
+    fn send(&self, severity: syslog::Severity, message: &str) -> io::Result<()> {
+      self.0.borrow_mut().push((severity, message.to_owned()));
+      Ok(())
+    }
+  }
+
+  /// Options which cannot be parsed are logged with `--syslog`, but syslog is not connected without it.
+  #[test]
+  fn parse_errors_are_logged() {
+    let messages = RefCell::new(Vec::new());
+    let arguments = ["--syslog", "--no-such-option"].map(String::from);
+    assert_eq!(parse_run_and_log(&arguments, || Ok(RecordingSink(&messages))), Err(Error::InvalidArguments));
+    let messages = messages.into_inner();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].0, syslog::Severity::Error);
+    assert!(messages[0].1.ends_with(&format!("failed: {}", Error::InvalidArguments)), "{}", messages[0].1);
+
+    let arguments = ["--no-such-option".to_owned()];
+    assert_eq!(
+      parse_run_and_log(&arguments, || -> io::Result<RecordingSink> { panic!("syslog connected without --syslog") }),
+      Err(Error::InvalidArguments)
+    );
+  }
+
+  /// The margin is computed from the randomness the PINs are taken from:
+  ///   The warning fires for more than 80 % of the maximum number of PINs, but not for fewer or when extended anyway.
+  #[test]
+  fn pin_margin_warning_above_threshold() {
+    let prng = Random::from_seed_bytes(&[1; 0x80]);
+    let max_pins = prng.max_pins().unwrap();
+    let base = Parameters::default();
+    assert!(!warn_about_pin_margin(&prng, Parameters { count: max_pins * PIN_MARGIN_PERCENT / 100, ..base }).unwrap());
+    assert!(warn_about_pin_margin(&prng, Parameters { count: max_pins * PIN_MARGIN_PERCENT / 100 + 1, ..base }).unwrap());
+    assert!(warn_about_pin_margin(&prng, Parameters { count: max_pins, ..base }).unwrap());
+    assert!(!warn_about_pin_margin(&prng, Parameters { count: max_pins, auto_extend: true, ..base }).unwrap());
+  }
+
+  /// With `--forbid-run`, a PIN with a run of identical digits is redrawn from the next bytes,
+  ///   skipping those the decimal rejection sampling refuses, i.e. 200 and above.
+  #[test]
+  fn forbidden_run_is_redrawn() {
+    let bytes = [0, 0, 0, 0, 0, 0, 200, 255, 12, 34, 56, 78, 91, 23];
+    assert_eq!(Pin::from_prng(&mut Random::from_seed_bytes(&bytes)).unwrap().to_numeric_string(), "000000000000");
+
+    let mut prng = Random::from_seed_bytes(&bytes).with_forbid_run(Some(4));
+    assert_eq!(Pin::from_prng(&mut prng).unwrap().to_numeric_string(), "123456789123");
+    assert_eq!((prng.position, prng.rejected_bytes, prng.rejected_pins), (bytes.len(), 2, 1));
+  }
+
+  /// With `--require-pipe`, PINs are refused for a terminal, but written to anything else.
+  #[test]
+  fn require_pipe_refuses_terminal() {
+    assert!(matches!(check_pipe(true, true), Err(Error::StdoutIsTerminal)));
+    assert!(check_pipe(true, false).is_ok());
+    assert!(check_pipe(false, true).is_ok());
+  }
+
+  /// A custom alphabet of symbols which are special in JSON or CSV is refused.
+  #[test]
+  fn custom_alphabet_is_alphanumeric() {
+    assert!("custom:abcXYZ789".parse::<Alphabet>().is_ok());
+    ["custom:01\"", "custom:0,1", "custom:01\\", "custom:a b"]
+    .into_iter()
+    .for_each(|name| assert!(name.parse::<Alphabet>().is_err(), "{}", name));
+  }
+
+  /// Each digit pair of a custom alphabet of 10 or 16 symbols is hit by the same number of bytes.
+  #[test]
+  fn custom_digit_pairs_are_uniform() {
+    ["custom:0123456789", "custom:0123456789ABCDEF"]
+    .into_iter()
+    .for_each(|name| {
+      let alphabet: Alphabet = name.parse().unwrap();
+      let radix = usize::from(alphabet.radix());
+      let mut counts = vec![0usize; 0x100];
+      (0..=u8::MAX)
+      .filter_map(|byte| alphabet.digit_pair(byte))
+      .for_each(|digit_pair| counts[usize::from(digit_pair.packed())] += 1);
+      let hits: Vec<usize> = counts.into_iter().filter(|&count| count > 0).collect();
+      assert_eq!(hits.len(), radix * radix, "{}", name);
+      assert!(hits.iter().all(|&count| count == 0x100 / (radix * radix)), "{}", name);
+    });
+  }
+
+  /// Each decimal digit pair is hit by exactly two bytes, the others are rejected, so there is no modulo bias.
+  #[test]
+  fn decimal_digit_pairs_are_uniform() {
+    let mut counts = [0usize; 0x100];
+    (0..=u8::MAX)
+    .filter_map(|byte| Alphabet::Decimal.digit_pair(byte))
+    .for_each(|digit_pair| counts[usize::from(digit_pair.packed())] += 1);
+    (0..=u8::MAX)
+    .filter(|&packed| DigitPair::new(packed, Alphabet::Decimal).is_some())
+    .for_each(|packed| assert_eq!(counts[usize::from(packed)], 2, "digit pair {:02x}", packed));
+    assert_eq!(counts.iter().sum::<usize>(), usize::from(Alphabet::REJECT_THRESHOLD));
+  }
+
+  /// Decimal PINs reject bytes from 200 on and have the digits 0–9, hexadecimal PINs take every byte and have 0–9 and a–f.
+  #[test]
+  fn both_alphabets_reject_and_emit() {
+    assert_eq!(Alphabet::Decimal.digit_pair(199), DigitPair::new(0x99, Alphabet::Decimal));
+    assert!((200..=u8::MAX).all(|byte| Alphabet::Decimal.digit_pair(byte).is_none()));
+    assert!((0..=u8::MAX).all(|byte| Alphabet::Hex.digit_pair(byte).map(DigitPair::packed) == Some(byte)));
+
+    let bytes = [0xab, 0xcd, 0xef, 0x01, 0x23, 0x45];
+    let mut prng = Random::from_seed_bytes(&bytes).with_alphabet(Alphabet::Hex);
+    assert_eq!(Pin::from_prng(&mut prng).unwrap().to_numeric_string(), "abcdef012345");
+    assert_eq!((prng.position, prng.rejected_bytes), (6, 0));
+  }
+
+  /// The digits of the PINs of many derivations are about equally frequent, by a chi-square test.
+  #[test]
+  fn digits_of_many_pins_are_uniform() {
+    let parameters = Parameters::default();
+    let mut counts = [0usize; 10];
+    (0..2000u32)
+    .map(|number| SerialNumber(format!("{:08}", number).into_bytes().try_into().unwrap()))
+    .flat_map(|serial_number|
+      try_calculate_all_pins(&SerialNumbers::new(vec![serial_number], parameters).unwrap(), parameters).unwrap()
+    )
+    .flat_map(|pin| pin.digits())
+    .for_each(|digit| counts[usize::from(digit)] += 1);
+    let expected = counts.iter().sum::<usize>() as f64 / counts.len() as f64;
+    let chi_square = counts
+    .iter()
+    .map(|&count| (count as f64 - expected).powi(2) / expected)
+    .sum::<f64>();
+    assert!(chi_square <= Random::MAX_CHI_SQUARE[counts.len() - 2], "chi-square {:.2}, counts {:?}", chi_square, counts);
+  }
+
+  /// The header of a shorter PIN has its number of digits, not the one of the full length.
+  #[test]
+  fn truncated_header_has_length() {
+    let pin = Pin::default().truncated(6, Pin::FILLER);
+    assert_eq!(pin.frame_bytes(), [0x26, 0x12, 0x34, 0x56, 0xff, 0xff, 0xff, 0xff]);
+    assert_eq!(Pin::default().truncated(Pin::LENGTH, Pin::FILLER).frame_bytes()[0], 0x2c);
+  }
+
+  /// The check digit is counted in the header, e.g. `0794` gets the check digit `5`.
+  #[test]
+  fn check_digit_header_has_length() {
+    let pin = Pin::new(&[0x07, 0x94, 0, 0, 0, 0]).truncated(4, Pin::FILLER).with_check_digit();
+    assert_eq!(pin.frame_bytes(), [0x25, 0x07, 0x94, 0x5f, 0xff, 0xff, 0xff, 0xff]);
+  }
+
+  /// `--auto-extend` continues the hash chain in place, the buffer is moved at most once for all extensions.
+  #[test]
+  fn auto_extend_never_reallocates() {
+    let parameters = Parameters { auto_extend: true, ..Parameters::default() };
+    let mut prng = try_derive_prng_with_parameters(&test_serial_numbers(parameters), parameters).unwrap();
+    prng.extend().unwrap();
+    let (address, length) = (prng.buffer.as_ptr(), prng.buffer.len());
+    (1..Random::MAX_EXTENSIONS).for_each(|_| prng.extend().unwrap());
+    assert_eq!(prng.buffer.as_ptr(), address);
+    assert_eq!(prng.buffer.len(), length + (Random::MAX_EXTENSIONS - 1) * SHA512_HASH_LENGTH);
+    assert!(matches!(prng.extend(), Err(Error::EndOfRandomness)));
+    let last = &prng.buffer[prng.buffer.len() - 2 * SHA512_HASH_LENGTH..];
+    assert_eq!(Sha512::digest(&last[..SHA512_HASH_LENGTH]).as_slice(), &last[SHA512_HASH_LENGTH..]);
+  }
+
+  /// One PIN more than the randomness suffices for fails, but with `--auto-extend` it takes exactly one more hash round,
+  ///   and the PINs before are the same.
+  #[test]
+  fn one_more_pin_extends_once() {
+    let parameters = Parameters::default();
+    let serial_numbers = test_serial_numbers(parameters);
+    let prng = try_derive_prng_with_parameters(&serial_numbers, parameters).unwrap();
+    let max_pins = prng.max_pins().unwrap();
+    let pins = try_take_pins(&mut prng.try_clone().unwrap(), max_pins).unwrap();
+    assert_eq!(try_take_pins(&mut prng.try_clone().unwrap(), max_pins + 1), Err(Error::EndOfRandomness));
+
+    let mut prng = prng.with_auto_extend(true);
+    let extended = try_take_pins(&mut prng, max_pins + 1).unwrap();
+    assert_eq!(prng.extensions, 1);
+    assert_eq!(extended[..max_pins], pins[..]);
+  }
+
+  /// More PINs than the randomness suffices for fail as `Error::EndOfRandomness`, which says what to do about it,
+  ///   not as a failure to get a connector ident number.
+  #[test]
+  fn exhausted_randomness_is_reported() {
+    let parameters = Parameters::default();
+    let serial_numbers = test_serial_numbers(parameters);
+    let count = try_max_pins(&serial_numbers, parameters).unwrap() + 1;
+    let error = try_calculate_all_pins(&serial_numbers, Parameters { count, ..parameters }).unwrap_err();
+    assert_eq!(error, Error::EndOfRandomness);
+    assert!(error.to_string().contains("--auto-extend"), "{}", error);
+  }
+
+  /// Two groups of readers give two distinct sets of PINs, each as if derived from the serial numbers of its group alone,
+  ///   and a group with a reader out of range fails on its own.
+  #[test]
+  fn groups_derive_distinct_pins() {
+    let parameters = Parameters::default();
+    let groups = [vec![0, 1], vec![2], vec![1, 5]];
+    let results = try_calculate_grouped_pins(&TEST_SERIAL_NUMBERS, &groups, parameters);
+    let alone = |serials: &[SerialNumber]| try_calculate_all_pins(&SerialNumbers::new(serials.to_vec(), parameters).unwrap(), parameters).unwrap();
+    let first = results[0].as_ref().unwrap();
+    let second = results[1].as_ref().unwrap();
+    assert_eq!(first.pins, alone(&TEST_SERIAL_NUMBERS[..2]));
+    assert_eq!(second.pins, alone(&TEST_SERIAL_NUMBERS[2..]));
+    assert_ne!(first.pins, second.pins);
+    assert_eq!((first.meta.card, second.meta.card), (Some(0), Some(1)));
+    assert_eq!(results[2].as_ref().unwrap_err(), &Error::ReaderIndexOutOfRange);
+  }
+
+  /// `--range 2..5` gets the PINs 2, 3 and 4 of the whole list in order, and a range beyond the count or an empty one fails.
+  #[test]
+  fn range_matches_indices_of_all_pins() {
+    let options = Options::parse(["--range", "2..5"].map(String::from).into_iter()).unwrap();
+    assert_eq!(options.range, Some(2..5));
+    let parameters = options.parameters;
+    let serial_numbers = test_serial_numbers(parameters);
+    let all = try_calculate_all_pins(&serial_numbers, parameters).unwrap();
+    assert_eq!(
+      try_calculate_pin_range(&serial_numbers, parameters, 2..5).unwrap(),
+      vec![(2, all[2]), (3, all[3]), (4, all[4])]
+    );
+    [4..parameters.count + 1, 3..3]
+    .into_iter()
+    .for_each(|range| assert_eq!(try_calculate_pin_range(&serial_numbers, parameters, range.clone()), Err(Error::PinIndexOutOfRange), "{:?}", range));
+  }
+
+  /// The PINs and PUKs of the serial numbers for testing purposes are pinned, so a change of the stream order is noticed:
+  ///   The PUKs follow all PINs, which are the same as without PUKs.
+  #[test]
+  fn pins_and_puks_are_pinned() {
+    let parameters = Parameters::default();
+    let serial_numbers = test_serial_numbers(parameters);
+    let (pins, puks) = try_calculate_pins_and_puks(&serial_numbers, parameters, Puk::DEFAULT_LENGTH).unwrap();
+    assert_eq!(
+      pins.iter().map(|pin| pin.to_numeric_string()).collect::<Vec<String>>(),
+      ["794158702577", "238642119782", "050346386081", "645226165500", "245183706555", "179740829935"]
+    );
+    assert_eq!(
+      puks.iter().map(Puk::to_numeric_string).collect::<Vec<String>>(),
+      ["93935047", "60305761", "26432522", "93106563", "41390827", "29310277"]
+    );
+    assert_eq!(pins, try_calculate_all_pins(&serial_numbers, parameters).unwrap());
+  }
+
+  /// The result of a calculation carries the serial numbers read from the source, together with the PINs derived from them.
+  #[test]
+  fn result_carries_source_serials() {
+    let parameters = Parameters::default();
+    let read = FixedSource(TEST_SERIAL_NUMBERS.to_vec()).read_serials().unwrap();
+    let result = try_calculate(SerialNumbers::new(read.clone(), parameters).unwrap(), parameters).unwrap();
+    assert_eq!(result.serials.iter().copied().collect::<Vec<SerialNumber>>(), read);
+    assert_eq!(result.meta.serials, read);
+    assert_eq!(result.meta.serials_fp, Some(serials_fingerprint(&read)));
+    assert_eq!(result.pins, try_calculate_all_pins(&test_serial_numbers(parameters), parameters).unwrap());
+  }
+
+  /// Indices are checked at the bounds: 0 and `count - 1` are valid, `count` is not, and no PIN has an index at all.
+  /// Negative indices count from the end, down to `-count`.
+  #[test]
+  fn index_bounds() {
+    let count = 6;
+    assert_eq!(check_index(0, count), Ok(()));
+    assert_eq!(check_index(count - 1, count), Ok(()));
+    assert_eq!(check_index(count, count), Err(Error::PinIndexOutOfRange));
+    assert_eq!(check_index(usize::MAX, count), Err(Error::PinIndexOutOfRange));
+    assert_eq!(check_index(0, 0), Err(Error::PinIndexOutOfRange));
+
+    assert_eq!(resolve_index(-1, count), Ok(count - 1));
+    assert_eq!(resolve_index(-(count as isize), count), Ok(0));
+    assert_eq!(resolve_index(-(count as isize) - 1, count), Err(Error::PinIndexOutOfRange));
+    assert_eq!(resolve_index(isize::MIN, count), Err(Error::PinIndexOutOfRange));
+    assert_eq!(resolve_index(count as isize, count), Err(Error::PinIndexOutOfRange));
+  }
+
+  /// An empty set of serial numbers is refused, as it gives no entropy, unless `--allow-empty-serials` asks for its hash.
+  #[test]
+  fn empty_serials_are_refused() {
+    let parameters = Parameters::default();
+    assert_eq!(SerialNumbers::new(Vec::new(), parameters), Err(Error::NoSerials));
+    let parameters = Parameters { allow_empty_serials: true, ..parameters };
+    let empty = SerialNumbers::new(Vec::new(), parameters).unwrap();
+    assert!(empty.is_empty());
+    assert!(try_calculate_all_pins(&empty, parameters).is_ok());
+  }
+
+  /// `--hash-stages 2` is the default and reproduces the pinned PINs, see `pins_and_puks_are_pinned`.
+  /// `--hash-stages 1` is the first half of that buffer, so it starts with the same PINs,
+  ///   but suffices for fewer of them and differs from the full list.
+  #[test]
+  fn hash_stages_change_buffer() {
+    let stages = |stages: &str| Options::parse(["--hash-stages", stages].map(String::from).into_iter()).unwrap().parameters;
+    let (single, double) = (stages("1"), stages("2"));
+    let serial_numbers = test_serial_numbers(double);
+    let pins = try_calculate_all_pins(&serial_numbers, double).unwrap();
+    assert_eq!(pins, try_calculate_all_pins(&serial_numbers, Parameters::default()).unwrap());
+    assert_eq!(pins[0].to_numeric_string(), "794158702577");
+
+    let (single_max, double_max) = (try_max_pins(&serial_numbers, single).unwrap(), try_max_pins(&serial_numbers, double).unwrap());
+    assert!(single_max < double_max, "{} < {}", single_max, double_max);
+    let count = single_max + 1;
+    assert_eq!(try_calculate_all_pins(&serial_numbers, Parameters { count, ..single }), Err(Error::EndOfRandomness));
+    let double_pins = try_calculate_all_pins(&serial_numbers, Parameters { count, ..double }).unwrap();
+    let single_pins = try_calculate_all_pins(&serial_numbers, Parameters { count: single_max, ..single }).unwrap();
+    assert_eq!(single_pins[..], double_pins[..single_max]);
+    assert_ne!(single_pins, double_pins);
+  }
+
+  /// The serial numbers are accepted as read, sorted with `--sort-serials` or without duplicates with `--dedup-serials`,
+  ///   but refused with fewer distinct ones than `--min-distinct-serials`.
+  #[test]
+  fn serial_numbers_are_validated() {
+    let [first, second, third] = TEST_SERIAL_NUMBERS;
+    let read = vec![second, first, second, third];
+    let base = Parameters::default();
+    let validated = |parameters| SerialNumbers::new(read.clone(), parameters).map(|serials| serials.iter().copied().collect::<Vec<_>>());
+    assert_eq!(validated(base), Ok(read.clone()));
+    assert_eq!(validated(Parameters { sort_serials: true, ..base }), Ok(vec![third, first, second, second]));
+    assert_eq!(validated(Parameters { dedup_serials: true, ..base }), Ok(vec![second, first, third]));
+    assert_eq!(validated(Parameters { sort_serials: true, dedup_serials: true, ..base }), Ok(vec![third, first, second]));
+    assert!(validated(Parameters { min_distinct_serials: Some(3), ..base }).is_ok());
+    assert_eq!(validated(Parameters { min_distinct_serials: Some(4), ..base }), Err(Error::InsufficientDistinctSerials));
+  }
+
+  /// The buffer is a chain of hashes: The second half is the SHA512 of the first, which is the hash of the input,
+  ///   and a buffer of another size, e.g. one and a half hashes, is the same chain truncated.
+  #[test]
+  fn buffer_is_hash_chain() {
+    let mut buffer = [0u8; 2 * SHA512_HASH_LENGTH];
+    fill_random_buffer(Sha512::new().chain_update(b"abc"), &mut buffer);
+    let (first, second) = buffer.split_at(SHA512_HASH_LENGTH);
+    assert_eq!(first, Sha512::digest(b"abc").as_slice());
+    assert_eq!(&first[..8], [0xdd, 0xaf, 0x35, 0xa1, 0x93, 0x61, 0x7a, 0xba]);
+    assert_eq!(second, Sha512::digest(first).as_slice());
+
+    let mut shorter = [0u8; SHA512_HASH_LENGTH * 3 / 2];
+    fill_random_buffer(Sha512::new().chain_update(b"abc"), &mut shorter);
+    assert_eq!(shorter[..], buffer[..shorter.len()]);
+  }
+
+  /// Reordering the serial numbers does not change the PINs of `Algorithm::XorPerSerialSha512`,
+  ///   but those of the default algorithm, which hashes them concatenated.
+  #[test]
+  fn xor_is_order_independent() {
+    let [first, second, third] = TEST_SERIAL_NUMBERS;
+    let pins = |algorithm, serials: [SerialNumber; 3]| {
+      let parameters = Parameters { algorithm, ..Parameters::default() };
+      try_calculate_all_pins(&SerialNumbers::new(serials.to_vec(), parameters).unwrap(), parameters).unwrap()
+    };
+    let xor = pins(Algorithm::XorPerSerialSha512, TEST_SERIAL_NUMBERS);
+    [[first, third, second], [third, second, first], [second, third, first]]
+    .into_iter()
+    .for_each(|serials| {
+      assert_eq!(pins(Algorithm::XorPerSerialSha512, serials), xor, "{:?}", serials);
+      assert_ne!(pins(Algorithm::DoubleSHA512, serials), pins(Algorithm::DoubleSHA512, TEST_SERIAL_NUMBERS), "{:?}", serials);
+    });
+    assert_ne!(xor, pins(Algorithm::DoubleSHA512, TEST_SERIAL_NUMBERS));
+  }
+
+  /// A uniform stream of digits passes `--check-entropy`, as does the randomness of real serial numbers,
+  ///   but a degenerate one of a single digit pair or without any accepted byte fails.
+  #[test]
+  fn skewed_digits_are_weak() {
+    let uniform: Vec<u8> = (0..Alphabet::MODULO).chain(iter::repeat(u8::MAX)).take(2 * SHA512_HASH_LENGTH).collect();
+    assert_eq!(Random::from_seed_bytes(&uniform).check_entropy(), Ok(()));
+    let every_nibble: Vec<u8> = (0..0x80u8).map(|index| (index * 2) | (index >> 6)).collect();
+    assert_eq!(Random::from_seed_bytes(&every_nibble).with_alphabet(Alphabet::Hex).check_entropy(), Ok(()));
+    let parameters = Parameters::default();
+    assert_eq!(try_derive_prng_with_parameters(&test_serial_numbers(parameters), parameters).unwrap().check_entropy(), Ok(()));
+
+    assert_eq!(Random::from_seed_bytes(&[0x42; 0x80]).check_entropy(), Err(Error::WeakRandomness));
+    assert_eq!(Random::from_seed_bytes(&[0xff; 0x80]).check_entropy(), Err(Error::WeakRandomness));
+  }
+
+  /// `--index -1` gets the last PIN and `--index -2` the one before, but a negative index beyond the count fails.
+  #[test]
+  fn negative_index_counts_from_end() {
+    let index = |index: &str| Options::parse(["--index", index].map(String::from).into_iter()).unwrap().index.unwrap();
+    let parameters = Parameters::default();
+    let serial_numbers = test_serial_numbers(parameters);
+    let all = try_calculate_all_pins(&serial_numbers, parameters).unwrap();
+    let pin = |index| resolve_index(index, parameters.count).and_then(|index| try_get_pin_by_id(&serial_numbers, parameters, index));
+    assert_eq!(pin(index("-1")), Ok(all[parameters.count - 1]));
+    assert_eq!(pin(index("-2")), Ok(all[parameters.count - 2]));
+    assert_eq!(pin(index(&format!("-{}", parameters.count))), Ok(all[0]));
+    assert_eq!(pin(index(&format!("-{}", parameters.count + 1))), Err(Error::PinIndexOutOfRange));
+  }
+
+  /// The fingerprint of `--version-info` is stable while the constants are unchanged, but changes with any of them,
+  ///   including the version, so it is pinned anew with each release.
+  #[test]
+  fn version_fingerprint_is_stable() {
+    let constants = algorithm_constants();
+    assert_eq!(
+      constants,
+      format!(
+        "version = {}\nhash = SHA-512\nhash_length = 64\ncontrol = 0x20\nstop = 0xff\nlength = 12\nmodulo = 100\nreject_threshold = 200\nmax_extensions = 64\n",
+        env!("CARGO_PKG_VERSION")
+      )
+    );
+    assert_eq!(constants_fingerprint(&constants), constants_fingerprint(&algorithm_constants()));
+    assert_eq!(constants_fingerprint(&constants), "bb01bf1b195bb089");
+    assert_ne!(constants_fingerprint(&constants.replace("stop = 0xff", "stop = 0xfe")), constants_fingerprint(&constants));
+  }
+
+  /// With one good and one failing group, the PINs of the good one are written and the run fails with a non-zero exit code,
+  ///   but with `--all-or-nothing` none are written.
+  #[test]
+  fn failing_group_gives_partial_output() {
+    let output = |arguments: &[&str]| {
+      let options = Options::parse(arguments.iter().map(|argument| argument.to_string())).unwrap();
+      let results = try_calculate_grouped_pins(&TEST_SERIAL_NUMBERS, &options.groups, options.parameters);
+      let mut writer = Vec::new();
+      let outcome = write_grouped_results(options.format.formatter(options.render).as_ref(), results, &options, &mut writer);
+      (outcome, String::from_utf8(writer).unwrap())
+    };
+    let (outcome, written) = output(&["--group", "0,1", "--group", "7"]);
+    assert_eq!(outcome, Err(Error::SomeGroupsFailed));
+    assert_ne!(Error::SomeGroupsFailed.exit_code(), 0);
+    assert!(written.starts_with("Card 0:\nPIN 0: "), "{}", written);
+    assert!(!written.contains("Card 1:"), "{}", written);
+    assert_eq!(written.lines().count(), 1 + Parameters::default().count);
+
+    let (outcome, written) = output(&["--group", "0,1", "--group", "7", "--all-or-nothing"]);
+    assert_eq!(outcome, Err(Error::ReaderIndexOutOfRange));
+    assert!(written.is_empty(), "{}", written);
+  }
+
+  /// Serial numbers which concatenate to the same bytes, here because a missing one is hashed as empty,
+  ///   give the same PINs without `--length-bind`, but different ones with it.
+  #[test]
+  fn length_bind_separates_colliding_serials() {
+    let [first, _, last] = TEST_SERIAL_NUMBERS;
+    let pins = |serials: Vec<SerialNumber>, length_bind| {
+      let parameters = Parameters { length_bind, ..Parameters::default() };
+      try_calculate_all_pins(&SerialNumbers::new(serials, parameters).unwrap(), parameters).unwrap()
+    };
+    let (missing_between, missing_last) = (vec![first, SerialNumber::MISSING, last], vec![first, last, SerialNumber::MISSING]);
+    assert_eq!(pins(missing_between.clone(), false), pins(missing_last.clone(), false));
+    assert_ne!(pins(missing_between.clone(), true), pins(missing_last.clone(), true));
+    assert_ne!(pins(missing_between, true), pins(vec![first, last], true));
+  }
+
+  /// A decimal digit pair has two nibbles of 0–9, so 0x0a and 0xf0 are refused, but a hexadecimal one may have any.
+  #[test]
+  fn digit_pair_is_checked() {
+    [0x0a, 0xf0, 0x9a, 0xa9, 0xff]
+    .into_iter()
+    .for_each(|packed| assert_eq!(DigitPair::new(packed, Alphabet::Decimal), None, "{:02x}", packed));
+    [0x00, 0x09, 0x90, 0x42, 0x99]
+    .into_iter()
+    .for_each(|packed| assert_eq!(DigitPair::new(packed, Alphabet::Decimal).map(DigitPair::packed), Some(packed), "{:02x}", packed));
+    assert_eq!(DigitPair::new(0xf0, Alphabet::Hex).map(DigitPair::digits), Some([0x0f, 0x00]));
+  }
+
+  /// `--what-if 1=swapped!` replaces only the serial number of card reader 1, so of one PIN set per card reader,
+  ///   only the one of reader 1 changes, the same way each time, and a card reader which does not exist is refused.
+  #[test]
+  fn what_if_changes_only_overridden_reader() {
+    let what_if = |overrides: &str| WhatIfSource {
+      source:     Box::new(FixedSource(TEST_SERIAL_NUMBERS.to_vec())),
+      overrides:  Options::parse(["--what-if", overrides].map(String::from).into_iter()).unwrap().what_if,
+    };
+    let read = what_if("1=swapped!").read_serials().unwrap();
+    assert_eq!(read, [TEST_SERIAL_NUMBERS[0], SerialNumber(*b"swapped!"), TEST_SERIAL_NUMBERS[2]]);
+
+    let groups = [vec![0], vec![1], vec![2]];
+    let pins = |serials: &[SerialNumber]| -> Vec<ListOfPins> {
+      try_calculate_grouped_pins(serials, &groups, Parameters::default())
+      .into_iter()
+      .map(|result| result.unwrap().pins)
+      .collect()
+    };
+    let (original, changed) = (pins(&TEST_SERIAL_NUMBERS), pins(&read));
+    assert_eq!((changed[0] == original[0], changed[1] == original[1], changed[2] == original[2]), (true, false, true));
+    assert_eq!(pins(&what_if("1=swapped!").read_serials().unwrap()), changed);
+    assert_eq!(what_if("3=swapped!").read_serials(), Err(Error::ReaderIndexOutOfRange));
+  }
+
+  /// The frame of the default PIN is control and length, the six digit pairs and the stop byte, as sent to the card,
+  ///   and `--format apdu` writes exactly these bytes.
+  #[test]
+  fn default_frame_layout() {
+    let frame = Pin::default().frame_bytes();
+    assert_eq!(frame, [0x2c, 0x12, 0x34, 0x56, 0x78, 0x91, 0x23, 0xff]);
+    assert_eq!(frame.len(), Pin::SIZE);
+    assert_eq!((frame[0], frame[Pin::SIZE - 1]), (Pin::CONTROL | Pin::LENGTH, Pin::STOP));
+
+    let mut output = Vec::new();
+    format::ApduFormatter(format::RenderOptions::default())
+    .render(&[(0, Pin::default())], &[], &DerivationMeta::new(Parameters::default(), 1), &mut output)
+    .unwrap();
+    assert_eq!(output, b"0: 2c 12 34 56 78 91 23 ff\n");
+  }
+
+  /// Each epoch gives another set of PINs, the same each time, and epoch 0 gives those without any epoch.
+  #[test]
+  fn epochs_rotate_pins() {
+    let pins = |epoch: u64| {
+      let parameters = Options::parse(["--epoch".to_owned(), epoch.to_string()].into_iter()).unwrap().parameters;
+      try_calculate_all_pins(&test_serial_numbers(parameters), parameters).unwrap()
+    };
+    let epochs: Vec<ListOfPins> = (0..4).map(pins).collect();
+    assert_eq!(epochs[0], try_calculate_all_pins(&test_serial_numbers(Parameters::default()), Parameters::default()).unwrap());
+    assert_eq!(epochs[0][0].to_numeric_string(), "794158702577");
+    (0..epochs.len())
+    .flat_map(|first| (first + 1..epochs.len()).map(move |second| (first, second)))
+    .for_each(|(first, second)| assert_ne!(epochs[first], epochs[second], "epochs {} and {}", first, second));
+    assert_eq!(pins(3), epochs[3]);
+  }
+
+  /// With a serial number read twice, `--dedup-serials` derives the PINs of the distinct serial numbers,
+  ///   while without it the duplicate is hashed again and gives other PINs.
+  #[test]
+  fn duplicate_serial_is_deduped_on_request() {
+    let [first, second, _] = TEST_SERIAL_NUMBERS;
+    let pins = |serials: &[SerialNumber], parameters| {
+      try_calculate_all_pins(&SerialNumbers::new(serials.to_vec(), parameters).unwrap(), parameters).unwrap()
+    };
+    let base = Parameters::default();
+    let deduped = Parameters { dedup_serials: true, ..base };
+    let distinct = pins(&[first, second], base);
+    assert_eq!(pins(&[first, second, first], deduped), pins(&[first, second], deduped));
+    assert_eq!(pins(&[first, second], deduped), distinct);
+    assert_ne!(pins(&[first, second, first], base), distinct);
+    assert_eq!(pins(&[first, second, first], base), pins(&[first, second, first], base));
+  }
+
+  /// `--compare-algorithms default-pin,double-sha512` lists the PINs of each algorithm under their index,
+  ///   each the same as derived with that algorithm alone.
+  #[test]
+  fn comparison_lists_pins_of_each_algorithm() {
+    let parameters = Parameters::default();
+    let serial_numbers = test_serial_numbers(parameters);
+    let algorithms = [Algorithm::DefaultPin, Algorithm::DoubleSHA512];
+    let comparison = try_compare_algorithms(&serial_numbers, parameters, &algorithms).unwrap();
+    let mut output = Vec::new();
+    write_comparison(&comparison, format::RenderOptions::default(), &mut output).unwrap();
+
+    let [default, double] = algorithms.map(|algorithm|
+      try_calculate_all_pins_with_algorithm(&serial_numbers, parameters, algorithm).unwrap()
+    );
+    assert_eq!(default, vec![Pin::default(); parameters.count]);
+    let expected: String = default
+    .iter()
+    .zip(&double)
+    .enumerate()
+    .map(|(index, (default, double))| format!("PIN {}:\n  default-pin   {}\n  double-sha512 {}\n", index, default, double))
+    .collect();
+    assert_eq!(String::from_utf8(output).unwrap(), expected);
+  }
+
+  /// With `--by-serial`, the PINs of each of two cards are headed by the masked serial numbers of that card,
+  ///   instead of the number of the card.
+  #[test]
+  fn by_serial_labels_each_card() {
+    let parameters = Parameters::default();
+    let redaction = Redaction::KeepLast(2);
+    let formatter = format::TextFormatter(format::RenderOptions { redaction, ..format::RenderOptions::default() });
+    let mut output = Vec::new();
+    let mut expected = String::new();
+    try_calculate_grouped_pins(&TEST_SERIAL_NUMBERS, &[vec![0], vec![1]], parameters)
+    .into_iter()
+    .zip(["******37", "******ow"])
+    .for_each(|(result, masked)| {
+      let result = result.unwrap();
+      expected.push_str(&format!("Serial numbers {}:\n", masked));
+      result
+      .pins
+      .iter()
+      .enumerate()
+      .for_each(|(index, pin)| expected.push_str(&format!("PIN {}: {}\n", index, redaction.apply(pin))));
+      write_result(&formatter, result, true, redaction, &mut output).unwrap();
+    });
+    assert_eq!(String::from_utf8(output).unwrap(), expected);
+  }
+
+  /// The digit pairs follow the header, whatever its length: With a header of two bytes they start at the third byte,
+  ///   and with the header and trailer of the card they are at `Pin::FRAME_DIGITS`, as in the frame of the default PIN.
+  #[test]
+  fn digits_follow_longer_header() {
+    let digit_pairs = [0x12, 0x34, 0x56, 0x78, 0x91, 0x23];
+    let mut frame = [0; 2 + Pin::DIGIT_PAIRS + 1];
+    Pin::lay_out(&mut frame, &[0x00, Pin::CONTROL | Pin::LENGTH], &digit_pairs, &Pin::TRAILER);
+    assert_eq!(frame, [0x00, 0x2c, 0x12, 0x34, 0x56, 0x78, 0x91, 0x23, 0xff]);
+    assert_eq!(frame[2..2 + Pin::DIGIT_PAIRS], digit_pairs);
+
+    let mut frame = [0; Pin::SIZE];
+    Pin::lay_out(&mut frame, &Pin::HEADER, &digit_pairs, &Pin::TRAILER);
+    assert_eq!(frame, Pin::default().frame_bytes());
+    assert_eq!(frame[Pin::FRAME_DIGITS], digit_pairs);
+  }
+
+  /// `--seed-from-file` takes the PINs from the bytes of the file as they are, rejecting those from 200 on,
+  ///   so consecutive bytes give consecutive digit pairs, but a file shorter than the buffer is refused.
+  #[test]
+  fn seed_file_gives_known_pins() {
+    let parameters = Parameters::default();
+    let file_name = env::temp_dir().join(format!("seed-{}", process::id()));
+    fs::write(&file_name, iter::once(250).chain(0..2 * SHA512_HASH_LENGTH as u8 - 1).collect::<Vec<u8>>()).unwrap();
+    let result = try_calculate_from_seed_file(&file_name, parameters);
+    fs::write(&file_name, [0u8; 2 * SHA512_HASH_LENGTH - 1]).unwrap();
+    let short = try_calculate_from_seed_file(&file_name, parameters);
+    fs::remove_file(&file_name).unwrap();
+
+    let expected: Vec<String> = (0..parameters.count)
+    .map(|index| (6 * index..6 * index + 6).map(|pair| format!("{:02}", pair)).collect())
+    .collect();
+    let result = result.unwrap();
+    assert_eq!(result.pins.iter().map(|pin| pin.to_numeric_string()).collect::<Vec<_>>(), expected);
+    assert!(result.serials.iter().next().is_none());
+    assert_eq!(short.map(|result| result.pins), Err(Error::SeedTooShort));
+  }
+
+  /// The digest of `--print-serials-digest` is pinned for the serial numbers for testing purposes, in order and sorted:
+  ///   Sorted, it is the same for any order, but in order, it is not.
+  #[test]
+  fn serials_digest_is_pinned() {
+    let [first, second, third] = TEST_SERIAL_NUMBERS;
+    let permuted = [third, first, second];
+    assert_eq!(serials_digest(&TEST_SERIAL_NUMBERS, false)[..8], [0x55, 0x9a, 0x71, 0xbf, 0xd9, 0x20, 0xb2, 0x12]);
+    assert_eq!(serials_digest(&TEST_SERIAL_NUMBERS, true)[..8], [0xf9, 0x3e, 0x6a, 0x92, 0x5c, 0xba, 0x71, 0x3e]);
+    assert_eq!(serials_digest(&permuted, true), serials_digest(&TEST_SERIAL_NUMBERS, true));
+    assert_eq!(serials_digest(&permuted, false), serials_digest(&permuted, true));
+    assert_ne!(serials_digest(&permuted, false), serials_digest(&TEST_SERIAL_NUMBERS, false));
+  }
+
+  /// An injected machine ID is hashed right after the serial numbers, so each one gives other PINs, the same each time,
+  ///   while without `--bind-machine-id` the PINs are unchanged.
+  #[test]
+  fn machine_id_binds_pins() {
+    let base = Parameters::default();
+    let serial_numbers = test_serial_numbers(base);
+    let machine = |machine_id| Parameters { machine_id, ..base };
+    let pins = |parameters| try_calculate_all_pins(&serial_numbers, parameters).unwrap();
+    let first = [0x23; 0x10];
+    let second = [0x42; 0x10];
+    assert_eq!(pins(machine(None)), pins(base));
+    assert_eq!(pins(machine(Some(first))), pins(machine(Some(first))));
+    assert_ne!(pins(machine(Some(first))), pins(base));
+    assert_ne!(pins(machine(Some(first))), pins(machine(Some(second))));
+
+    let prng = try_derive_prng_with_parameters(&serial_numbers, machine(Some(first))).unwrap();
+    let hash = Sha512::new()
+    .chain_update(b"23421337meowmeow*squeak*")
+    .chain_update(first)
+    .finalize();
+    assert_eq!(prng.buffer[..SHA512_HASH_LENGTH], hash[..]);
+  }
+
+  /// A PIN of odd length, e.g. 11 digits, is half-packed: Its last digit shares a pair with the filler nibble,
+  ///   so no digit is dropped, and `--digits 11` derives the first 11 digits of the full PINs.
+  #[test]
+  fn odd_length_is_half_packed() {
+    let pin = Pin::default().truncated(11, Pin::FILLER);
+    assert_eq!(pin.frame_bytes(), [0x2b, 0x12, 0x34, 0x56, 0x78, 0x91, 0x2f, 0xff]);
+    assert_eq!(pin.to_numeric_string(), "12345678912");
+
+    let parameters = Options::parse(["--digits", "11"].map(String::from).into_iter()).unwrap().parameters;
+    let full = try_calculate_all_pins(&test_serial_numbers(Parameters::default()), Parameters::default()).unwrap();
+    try_calculate_all_pins(&test_serial_numbers(parameters), parameters)
+    .unwrap()
+    .iter()
+    .zip(&full)
+    .for_each(|(pin, full)| {
+      assert_eq!(pin.to_numeric_string(), full.to_numeric_string()[..11]);
+      assert_eq!(pin.frame_bytes()[Pin::FRAME_DIGITS.end - 1] & 0x0f, Pin::FILLER);
+    });
+  }
+
+  /// `--hash-iterations 1` is the baseline, the PINs without this option, and each higher count gives other PINs,
+  ///   the same each time: With 2, the buffer starts with the hash of the first hash, i.e. the second hash of the baseline.
+  #[test]
+  fn hash_iterations_stretch_deterministically() {
+    let base = Parameters::default();
+    let serial_numbers = test_serial_numbers(base);
+    let iterated = |hash_iterations| Parameters { hash_iterations, ..base };
+    let pins = |parameters| try_calculate_all_pins(&serial_numbers, parameters).unwrap();
+    assert_eq!(pins(iterated(1)), pins(base));
+    assert_eq!(pins(iterated(1))[0].to_numeric_string(), "794158702577");
+    (2..5)
+    .for_each(|iterations| {
+      assert_eq!(pins(iterated(iterations)), pins(iterated(iterations)));
+      assert_ne!(pins(iterated(iterations)), pins(iterated(iterations - 1)), "{} iterations", iterations);
+    });
+
+    let buffer = |parameters| try_derive_prng_with_parameters(&serial_numbers, parameters).unwrap().buffer.clone();
+    assert_eq!(buffer(iterated(2))[..SHA512_HASH_LENGTH], buffer(base)[SHA512_HASH_LENGTH..]);
+  }
+
+  /// The histogram of `--rng-histogram` counts each byte of the buffer once, so its totals equal the size of the buffer:
+  ///   For crafted bytes each accepted pair by its value and each byte from 200 on as rejected,
+  ///   and for the serial numbers for testing purposes the rejected bytes are those at or above the threshold.
+  #[test]
+  fn histogram_totals_buffer_size() {
+    let mut bytes = vec![12, 112, 34, 200, 255, 99];
+    bytes.resize(2 * SHA512_HASH_LENGTH, 0);
+    let (counts, rejected) = Random::from_seed_bytes(&bytes).histogram();
+    assert_eq!(counts.len(), usize::from(Alphabet::MODULO));
+    assert_eq!((counts[12], counts[34], counts[99], counts[0], rejected), (2, 1, 1, 2 * SHA512_HASH_LENGTH - 6, 2));
+    assert_eq!(counts.iter().sum::<usize>() + rejected, 2 * SHA512_HASH_LENGTH);
+
+    let parameters = Parameters::default();
+    let prng = try_derive_prng_with_parameters(&test_serial_numbers(parameters), parameters).unwrap();
+    let (counts, rejected) = prng.histogram();
+    assert_eq!(counts.iter().sum::<usize>() + rejected, prng.buffer.len());
+    assert_eq!(rejected, prng.buffer.iter().filter(|&&byte| byte >= Alphabet::REJECT_THRESHOLD).count());
+  }
+
+  /// Three card readers sharing one serial number fail `--min-distinct-serials 2` as `Error::InsufficientDistinctSerials`,
+  ///   but two distinct ones among them suffice, and without the option they are accepted.
+  #[test]
+  fn shared_serial_fails_minimum() {
+    let [first, second, _] = TEST_SERIAL_NUMBERS;
+    let parameters = Options::parse(["--min-distinct-serials", "2"].map(String::from).into_iter()).unwrap().parameters;
+    assert_eq!(parameters.min_distinct_serials, Some(2));
+    assert_eq!(SerialNumbers::new(vec![first; 3], parameters), Err(Error::InsufficientDistinctSerials));
+    assert!(SerialNumbers::new(vec![first, second, first], parameters).is_ok());
+    assert!(SerialNumbers::new(vec![first; 3], Parameters::default()).is_ok());
+  }
+
+  /// The PINs of `--algorithm argon2id` are pinned for the serial numbers for testing purposes with a small cost,
+  ///   and differ from those of SHA512 and with another cost, epoch or `--length-bind`.
+  #[test]
+  fn argon2id_pins_are_pinned() {
+    let parameters = Options::parse(["--algorithm", "argon2id", "--argon2-cost", "32,3,4"].map(String::from).into_iter()).unwrap().parameters;
+    let pins = |parameters| try_calculate_all_pins(&test_serial_numbers(parameters), parameters).unwrap();
+    let argon2id = pins(parameters);
+    assert_eq!(
+      argon2id.iter().map(|pin| pin.to_numeric_string()).collect::<Vec<String>>(),
+      ["076130697969", "068231780825", "731521239374", "394851237964", "395319789750", "069712305651"]
+    );
+    assert_eq!(pins(parameters), argon2id);
+    [
+      Parameters { algorithm: Algorithm::DoubleSHA512, ..parameters },
+      Parameters { argon2_cost: "32,2,4".parse().unwrap(), ..parameters },
+      Parameters { epoch: 1, ..parameters },
+      Parameters { length_bind: true, ..parameters },
+    ]
+    .into_iter()
+    .for_each(|other| assert_ne!(pins(other), argon2id, "{:?}", other));
+  }
+
+  /// `--index` and `--range` get the same PINs as the whole list, with and without `--per-index`.
+  #[test]
+  fn index_and_range_match_all_pins() {
+    [false, true]
+    .into_iter()
+    .for_each(|per_index| {
+      let parameters = Parameters { per_index, ..Parameters::default() };
+      let serial_numbers = test_serial_numbers(parameters);
+      let all = try_calculate_all_pins(&serial_numbers, parameters).unwrap();
+      assert_eq!(try_get_pin_by_id(&serial_numbers, parameters, 2).unwrap(), all[2]);
+      let range = try_calculate_pin_range(&serial_numbers, parameters, 1..3).unwrap();
+      assert_eq!(range, vec![(1, all[1]), (2, all[2])]);
+    });
+  }
 }
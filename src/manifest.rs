@@ -0,0 +1,150 @@
+use {
+  crate::{
+    Error,
+    ListOfSerialNumbers,
+    SerialNumber,
+    NUMBER_OF_CARD_READERS,
+  },
+  std::{
+    fs,
+    path::Path,
+  },
+};
+
+/// Read the serial numbers of all reader slots from a manifest,
+///   so PINs can be calculated on another machine without access to the card readers.
+/// The manifest is a TOML table mapping each slot index to its serial number:
+/// ```toml
+/// # Konnektor 1, recorded 2022-10-01
+/// 0 = "23421337"
+/// 1 = "meowmeow"
+/// 2 = "*squeak*"
+/// ```
+/// Every slot must be listed exactly once and each serial number must have the exact length.
+pub fn try_read_manifest(file_name: &Path) -> Result<ListOfSerialNumbers, Error> {
+  let mut slots: [Option<SerialNumber>; NUMBER_OF_CARD_READERS] = [None; NUMBER_OF_CARD_READERS];
+
+  fs::read_to_string(file_name)
+  .inspect_err(|error|
+    eprintln!(
+      "Cannot read manifest {}: {}",
+      file_name.display(),
+      error
+    )
+  )
+  .map_err(|_| Error::CannotReadFile)?
+  .lines()
+  .enumerate()
+  .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+  .try_for_each(|(number, line)| {
+    let (slot, serial_number) = parse_line(line)
+    .inspect_err(|error|
+      eprintln!(
+        "Line {} of manifest {}: {}",
+        number + 1,
+        file_name.display(),
+        error
+      )
+    )
+    .map_err(|_| Error::InvalidManifest)?;
+
+    slots
+    .get_mut(slot)
+    .ok_or("Reader slot out of range")
+    .and_then(|entry| entry.replace(serial_number).map_or(Ok(()), |_| Err("Reader slot listed twice")))
+    .inspect_err(|error|
+      eprintln!(
+        "Line {} of manifest {}: {} (slot {}, expected 0–{})",
+        number + 1,
+        file_name.display(),
+        error,
+        slot,
+        NUMBER_OF_CARD_READERS - 1
+      )
+    )
+    .map_err(|_| Error::InvalidManifest)
+  })?;
+
+  slots
+  .try_map(|slot| slot)
+  .ok_or(Error::InvalidManifest)
+  .inspect_err(|_|
+    eprintln!(
+      "Manifest {} must list the serial numbers of all {} reader slots",
+      file_name.display(),
+      NUMBER_OF_CARD_READERS
+    )
+  )
+}
+
+/// Parse a single line of the form `slot = "serial number"`.
+/// On failure, describe what is wrong with the line.
+fn parse_line(line: &str) -> Result<(usize, SerialNumber), &'static str> {
+  let (slot, value) = line.split_once('=').ok_or("Expected `slot = \"serial number\"`")?;
+  let slot = slot.trim().parse().map_err(|_| "Reader slot is not a number")?;
+  value
+  .trim()
+  .strip_prefix('"')
+  .and_then(|value| value.strip_suffix('"'))
+  .ok_or("Serial number must be quoted")?
+  .as_bytes()
+  .try_into()
+  .map(|serial_number| (slot, SerialNumber(serial_number)))
+  .map_err(|_| "Serial number has the wrong length")
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::try_read_manifest,
+    crate::{
+      source::{
+        DeviceSource,
+        ManifestSource,
+        SerialSource,
+      },
+      try_calculate_all_pins,
+      Error,
+      Parameters,
+      SerialNumbers,
+      TEST_SERIAL_NUMBERS,
+    },
+    std::{
+      env,
+      fs,
+      process,
+    },
+  };
+
+  /// A manifest of the serial numbers of the card readers, recorded by hand, gives the same PINs as the card readers,
+  ///   but a serial number of the wrong length is refused.
+  #[test]
+  fn manifest_reproduces_device_pins() {
+    let directory = env::temp_dir().join(format!("manifest-{}", process::id()));
+    let readers = TEST_SERIAL_NUMBERS
+    .iter()
+    .enumerate()
+    .map(|(slot, serial_number)| {
+      let device = directory.join(format!("reader{}", slot));
+      fs::create_dir_all(&device).unwrap();
+      fs::write(device.join("serial"), [&serial_number.0[..], b"\n"].concat()).unwrap();
+      &*String::leak(device.join("serial").display().to_string())
+    })
+    .collect::<Vec<&'static str>>();
+    let manifest = directory.join("manifest.toml");
+    fs::write(&manifest, "# Konnektor 1\n2 = \"*squeak*\"\n0 = \"23421337\"\n1 = \"meowmeow\"\n").unwrap();
+
+    let parameters = Parameters::default();
+    let pins = |source: &dyn SerialSource| {
+      let serial_numbers = SerialNumbers::new(source.read_serials().unwrap(), parameters).unwrap();
+      try_calculate_all_pins(&serial_numbers, parameters).unwrap()
+    };
+    let from_devices = pins(&DeviceSource(readers.try_into().unwrap(), 0, false));
+    let from_manifest = pins(&ManifestSource(manifest.clone()));
+    fs::write(&manifest, "0 = \"23421337\"\n1 = \"meowmeo\"\n2 = \"*squeak*\"\n").unwrap();
+    let short = try_read_manifest(&manifest);
+    fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(from_manifest, from_devices);
+    assert_eq!(short, Err(Error::InvalidManifest));
+  }
+}
@@ -0,0 +1,126 @@
+use {
+  crate::Error,
+  std::{
+    ffi::{
+      c_int,
+      c_void,
+    },
+    fmt::{
+      Debug,
+      Formatter,
+      Result as FormatResult,
+    },
+    io,
+    ptr,
+    sync::atomic::{
+      compiler_fence,
+      Ordering,
+    },
+  },
+};
+
+extern "C" {
+  fn mlock(address: *const c_void, length: usize) -> c_int;
+  fn munlock(address: *const c_void, length: usize) -> c_int;
+}
+
+/// Whether to lock the randomness into memory, so it is never swapped to disk, see `--mlock`.
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub enum MemoryLock {
+  /// Leave the randomness swappable.
+  #[default]
+  Off,
+  /// Lock the randomness, but only warn if not permitted, e.g. without `CAP_IPC_LOCK`.
+  Try,
+  /// Refuse to calculate PINs if the randomness cannot be locked.
+  Require,
+}
+
+impl MemoryLock {
+  /// Lock `length` bytes starting at `start` or report why this failed, according to the policy.
+  /// Returns whether the memory is locked and must be unlocked with `unlock`.
+  pub fn lock(self, start: *const u8, length: usize) -> Result<bool, Error> {
+    match self {
+      Self::Off => Ok(false),
+      Self::Try | Self::Require
+      =>  match unsafe { mlock(start.cast(), length) } {
+            0 => Ok(true),
+            _ => {
+              let error = io::Error::last_os_error();
+              eprintln!("Cannot lock {} bytes of randomness into memory: {}", length, error);
+              (self == Self::Try)
+              .then_some(false)
+              .ok_or(Error::CannotLockMemory)
+              .inspect(|_| eprintln!("Warning: the randomness might be swapped to disk, see --require-mlock"))
+            },
+          },
+    }
+  }
+}
+
+/// Unlock memory locked with `MemoryLock::lock`.
+pub fn unlock(start: *const u8, length: usize) {
+  unsafe { munlock(start.cast(), length) };
+}
+
+/// Overwrite the bytes with zeros, in a way the compiler does not optimise away before they are freed.
+pub fn zeroize(bytes: &mut [u8]) {
+  bytes
+  .iter_mut()
+  .for_each(|byte| unsafe { ptr::write_volatile(byte, 0) });
+  compiler_fence(Ordering::SeqCst);
+}
+
+/// A guard which zeroes the bytes it holds when dropped, even on an early return or a panic.
+/// Take the bytes out with `mem::take` to keep them, e.g. to move them into a `Random`.
+pub struct Wiped<T: AsMut<[u8]>>(pub T);
+
+impl<T: AsMut<[u8]>> Drop for Wiped<T> {
+  fn drop(&mut self) {
+    zeroize(self.0.as_mut());
+  }
+}
+
+/// The bytes are secret, e.g. a key, so they are never printed.
+impl<T: AsMut<[u8]>> Debug for Wiped<T> {
+  fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+    formatter.write_str("Wiped(..)")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::Wiped,
+    crate::{
+      fill_hash_chain,
+      Error,
+    },
+    sha2::{
+      Digest,
+      Sha512,
+    },
+    std::mem,
+  };
+
+  /// Fill the buffer with a hash chain like the derivation, but fail halfway, before it is taken over.
+  fn fill_then_fail(buffer: &mut [u8]) -> Result<Vec<u8>, Error> {
+    let guard = Wiped(buffer);
+    let half = guard.0.len() / 2;
+    fill_hash_chain(Sha512::digest(b"23421337"), &mut guard.0[..half]);
+    assert!(guard.0.iter().any(|&byte| byte != 0));
+    Err(Error::EndOfRandomness)?;
+    Ok(guard.0.to_vec())
+  }
+
+  /// A failure after a partial fill still leaves the buffer zeroed, while bytes taken out of the guard are kept.
+  #[test]
+  fn early_return_zeroes_buffer() {
+    let mut buffer = [0u8; 0x80];
+    assert_eq!(fill_then_fail(&mut buffer), Err(Error::EndOfRandomness));
+    assert_eq!(buffer, [0u8; 0x80]);
+
+    let mut kept = Wiped(vec![0x42u8; 0x10]);
+    assert_eq!(mem::take(&mut kept.0), vec![0x42u8; 0x10]);
+  }
+}
@@ -0,0 +1,740 @@
+use {
+  crate::{
+    format::{
+      Color,
+      Format,
+      Redaction,
+      RenderOptions,
+    },
+    hex,
+    machine,
+    memory::{
+      MemoryLock,
+      Wiped,
+    },
+    Algorithm,
+    Alphabet,
+    Error,
+    Parameters,
+    Pin,
+    Puk,
+    SerialNumber,
+  },
+  std::{
+    fs::File,
+    io::Read,
+    ops::{
+      Range,
+      RangeInclusive,
+    },
+    os::unix::fs::PermissionsExt,
+    path::PathBuf,
+    str::{
+      self,
+      FromStr,
+    },
+    time::Duration,
+  },
+};
+
+/// Options given on the command line.
+#[derive(Debug, Default)]
+pub struct Options {
+  /// Print no PINs at all, if those of any card cannot be calculated.
+  pub all_or_nothing: bool,
+
+  /// Run as root without a warning, see `privileges::decide`.
+  pub allow_root: bool,
+
+  /// Refuse to run as root, see `privileges::decide`.
+  pub no_root: bool,
+
+  /// Head the PINs of each card with the serial numbers they were derived from instead of the number of the card.
+  pub by_serial: bool,
+
+  /// Cache the serial numbers read in this file, see `CachedSource`.
+  pub cache: Option<PathBuf>,
+
+  /// Read the serial numbers again, if the cache is older than this, see `CachedSource::DEFAULT_TTL`.
+  pub cache_ttl: Option<Duration>,
+
+  /// Print an order-independent fingerprint of the serial numbers instead of the PINs.
+  pub fingerprint: bool,
+
+  /// Print a digest of the serial numbers, in the order they are hashed, instead of the PINs, see `serials_digest`.
+  pub print_serials_digest: bool,
+
+  /// Refuse to calculate PINs if any of these serial numbers is read.
+  pub forbidden_serials: Vec<SerialNumber>,
+
+  /// Refuse to calculate PINs from the serial numbers for testing purposes, too, e.g. on production machines.
+  pub forbid_test_serials: bool,
+
+  /// Derive an independent set of PINs for each of these groups of card readers.
+  pub groups: Vec<Vec<usize>>,
+
+  /// Explain which inputs the PINs depend on instead of calculating them.
+  pub help_derivation: bool,
+
+  /// Format of the printed PINs.
+  pub format: Format,
+
+  /// Print the PINs derived with each of these algorithms side by side, e.g. during a migration.
+  pub compare_algorithms: Vec<Algorithm>,
+
+  /// Leave out the line feed at the end of the output.
+  pub no_trailing_newline: bool,
+
+  /// Show one PIN at a time and clear the screen before the next one.
+  pub paginate: bool,
+
+  /// Print a hash identifying the parameters of the derivation instead of the PINs, see `provenance_hash`.
+  pub provenance: bool,
+
+  /// Also derive a PUK with this many digits for each PIN.
+  pub puk_length: Option<usize>,
+
+  /// Only calculate the PIN with this index, counted from the end if negative.
+  pub index: Option<isize>,
+
+  /// Only calculate the PINs with indices in this range.
+  pub range: Option<Range<usize>>,
+
+  /// Options of the formatter, e.g. redaction.
+  pub render: RenderOptions,
+
+  /// Refuse to print PINs, if the standard output is a terminal.
+  pub require_pipe: bool,
+
+  /// The secret key to authenticate messages with, given as hex digits or read from a file, see `key_file_value`.
+  /// It is zeroed when dropped, e.g. when replaced by another `--key`.
+  pub key: Option<Wiped<Vec<u8>>>,
+
+  /// Print the status of the card readers instead of calculating PINs.
+  pub list_readers: bool,
+
+  /// Print the number of PINs the randomness suffices for instead of the PINs.
+  pub max_pins: bool,
+
+  /// Print how often each digit pair occurs in the randomness instead of the PINs, see `Random::histogram`.
+  pub rng_histogram: bool,
+
+  /// Read the serial numbers from this manifest instead of the card readers.
+  pub manifest: Option<PathBuf>,
+
+  /// Read the serial numbers of all cards from this file, one per line, instead of the card readers.
+  pub multi_serial_file: Option<PathBuf>,
+
+  /// Read the serial numbers from all files matching this pattern instead of the configured card readers.
+  pub reader_glob: Option<String>,
+
+  /// Parameters of the derivation of the PINs.
+  pub parameters: Parameters,
+
+  /// Calculate the PINs from the randomness in this file instead of the serial numbers, see `Random::from_bytes`.
+  pub seed_file: Option<PathBuf>,
+
+  /// Run the known answer test instead of calculating PINs.
+  pub self_test: bool,
+
+  /// Skip this many bytes of the file of each card reader before its serial number.
+  pub serial_offset: u64,
+
+  /// Substitute `SerialNumber::MISSING` for each card reader that cannot be read instead of failing.
+  /// This weakens the PINs, so it is warned about every time.
+  pub missing_as_empty: bool,
+
+  /// Derive the PINs only from the serial numbers starting with this prefix, see `PrefixSource`.
+  pub serial_prefix: Option<String>,
+
+  /// Read the serial numbers from environment variables instead of the card readers, see `EnvSource`.
+  pub serial_env: bool,
+
+  /// Receive the serial numbers from this address instead of reading the card readers.
+  pub serial_socket: Option<String>,
+
+  /// Print the version and the constants of the derivation instead of calculating PINs.
+  pub version_info: bool,
+
+  /// Read the serial number of each card reader without its sysfs attribute from this key of its `uevent` file,
+  ///   see `UeventSource`.
+  pub uevent_key: Option<String>,
+
+  /// Verify the `index pin` pairs of this file instead of printing the PINs.
+  pub verify_all: Option<PathBuf>,
+
+  /// Verify the `index pin` pairs read from stdin instead of printing the PINs.
+  pub verify_stdin: bool,
+
+  /// Replace the serial numbers of these card readers, to plan which PINs other cards would yield.
+  pub what_if: Vec<(usize, SerialNumber)>,
+
+  /// Write a record of the run to this file on exit, for monitoring, see `summary::Summary`.
+  pub summary_json: Option<PathBuf>,
+
+  /// Log who ran this tool and whether it succeeded to syslog, but never any PIN.
+  pub syslog: bool,
+
+  /// Recalculate the PINs in this interval and print those which changed.
+  pub watch: Option<Duration>,
+}
+
+impl Options {
+  /// Number of digits left visible by `--redact`.
+  const DEFAULT_REDACT_KEEP: usize = 2;
+
+  /// Number of bytes of a key: at least 16, so it cannot be guessed,
+  ///   and at most 64, the block size of HMAC-SHA256, as longer keys would be hashed anyway.
+  const KEY_LENGTHS: RangeInclusive<usize> = 0x10..=0x40;
+
+  /// Parse the command line arguments, not including the name of the program.
+  pub fn parse(mut arguments: impl Iterator<Item = String>) -> Result<Self, Error> {
+    let mut options = Self::default();
+    let mut no_color = false;
+    while let Some(argument) = arguments.next() {
+      match argument.as_str() {
+        "--all-or-nothing"
+        =>  options.all_or_nothing = true,
+        "--allow-empty-serials"
+        =>  options.parameters.allow_empty_serials = true,
+        "--allow-root"
+        =>  options.allow_root = true,
+        "--algorithm"
+        =>  options.parameters.algorithm = Self::parsed_value(&mut arguments, &argument)?,
+        "--alphabet"
+        =>  options.parameters.alphabet = Self::parsed_value(&mut arguments, &argument)?,
+        "--argon2-cost"
+        =>  options.parameters.argon2_cost = Self::parsed_value(&mut arguments, &argument)?,
+        "--auto-extend"
+        =>  options.parameters.auto_extend = true,
+        "--bind-machine-id"
+        =>  options.parameters.machine_id = Some(machine::read()?),
+        "--by-serial"
+        =>  options.by_serial = true,
+        "--cache-ttl"
+        =>  options.cache_ttl = Some(Duration::from_secs(Self::parsed_value(&mut arguments, &argument)?)),
+        "--check-digit"
+        =>  options.parameters.check_digit = true,
+        "--check-entropy"
+        =>  options.parameters.check_entropy = true,
+        "--compare-algorithms"
+        =>  options.compare_algorithms = Self::parsed_list(&mut arguments, &argument)?,
+        "--count"
+        =>  options.parameters.count = Self::parsed_value(&mut arguments, &argument)?,
+        "--dedup-serials"
+        =>  options.parameters.dedup_serials = true,
+        "--digits"
+        =>  options.parameters.pin_digits = Self::parsed_value(&mut arguments, &argument)?,
+        "--epoch"
+        =>  options.parameters.epoch = Self::parsed_value(&mut arguments, &argument)?,
+        "--explain"
+        =>  options.parameters.explain = true,
+        "--fingerprint"
+        =>  options.fingerprint = true,
+        "--forbid-serial"
+        =>  options.forbidden_serials.push(Self::parsed_value(&mut arguments, &argument)?),
+        "--forbid-test-serials"
+        =>  options.forbid_test_serials = true,
+        "--forbid-run"
+        =>  options.parameters.forbid_run = Some(Self::parsed_value(&mut arguments, &argument)?),
+        "--format"
+        =>  options.format = Self::parsed_value(&mut arguments, &argument)?,
+        "--group"
+        =>  options.groups.push(Self::parsed_list(&mut arguments, &argument)?),
+        "--hash-iterations"
+        =>  options.parameters.hash_iterations = Self::parsed_value(&mut arguments, &argument)?,
+        "--hash-stages"
+        =>  options.parameters.hash_stages = Self::parsed_value(&mut arguments, &argument)?,
+        "--help-derivation"
+        =>  options.help_derivation = true,
+        "--index-width"
+        =>  options.render.index_width = Some(Self::parsed_value(&mut arguments, &argument)?),
+        "--index"
+        =>  options.index = Some(Self::parsed_value(&mut arguments, &argument)?),
+        "--key"
+        =>  options.key = Some(Self::hex_key_value(&mut arguments, &argument)?),
+        "--keyfile"
+        =>  options.key = Some(Self::key_file_value(&mut arguments, &argument)?),
+        "--length-bind"
+        =>  options.parameters.length_bind = true,
+        "--list-readers"
+        =>  options.list_readers = true,
+        "--manifest"
+        =>  options.manifest = Some(Self::value(&mut arguments, &argument)?.into()),
+        "--max-pins"
+        =>  options.max_pins = true,
+        "--min-distinct-serials"
+        =>  options.parameters.min_distinct_serials = Some(Self::parsed_value(&mut arguments, &argument)?),
+        "--missing-as-empty"
+        =>  options.missing_as_empty = true,
+        "--mlock"
+        =>  options.parameters.memory_lock = options.parameters.memory_lock.max(MemoryLock::Try),
+        "--multi-serial-file"
+        =>  options.multi_serial_file = Some(Self::value(&mut arguments, &argument)?.into()),
+        "--no-color"
+        =>  no_color = true,
+        "--no-root"
+        =>  options.no_root = true,
+        "--no-trailing-newline"
+        =>  options.no_trailing_newline = true,
+        "--paginate"
+        =>  options.paginate = true,
+        "--per-index"
+        =>  options.parameters.per_index = true,
+        "--pin-filler"
+        =>  options.parameters.pin_filler = Self::nibble_value(&mut arguments, &argument)?,
+        "--preview"
+        =>  options.render.redaction = Redaction::Preview,
+        "--print-serials-digest"
+        =>  options.print_serials_digest = true,
+        "--provenance"
+        =>  options.provenance = true,
+        "--puk"
+        =>  options.puk_length = options.puk_length.or(Some(Puk::DEFAULT_LENGTH)),
+        "--puk-length"
+        =>  options.puk_length = Some(Self::parsed_value(&mut arguments, &argument)?),
+        "--range"
+        =>  options.range = Some(Self::range_value(&mut arguments, &argument)?),
+        "--reader-glob"
+        =>  options.reader_glob = Some(Self::value(&mut arguments, &argument)?),
+        "--redact"
+        =>  options.render.redaction = Redaction::KeepLast(Self::DEFAULT_REDACT_KEEP),
+        "--redact-keep"
+        =>  options.render.redaction = Redaction::KeepLast(Self::parsed_value(&mut arguments, &argument)?),
+        "--require-mlock"
+        =>  options.parameters.memory_lock = MemoryLock::Require,
+        "--require-pipe"
+        =>  options.require_pipe = true,
+        "--reverse"
+        =>  options.render.reverse = true,
+        "--rng-histogram"
+        =>  options.rng_histogram = true,
+        "--seed-from-file"
+        =>  options.seed_file = Some(Self::value(&mut arguments, &argument)?.into()),
+        "--self-test"
+        =>  options.self_test = true,
+        "--serial-env"
+        =>  options.serial_env = true,
+        "--serial-offset"
+        =>  options.serial_offset = Self::parsed_value(&mut arguments, &argument)?,
+        "--serial-prefix"
+        =>  options.serial_prefix = Some(Self::value(&mut arguments, &argument)?),
+        "--serial-socket"
+        =>  options.serial_socket = Some(Self::value(&mut arguments, &argument)?),
+        "--sort-by-pin"
+        =>  options.render.sort_by_pin = true,
+        "--sort-serials"
+        =>  options.parameters.sort_serials = true,
+        "--summary-json"
+        =>  options.summary_json = Some(Self::value(&mut arguments, &argument)?.into()),
+        "--syslog"
+        =>  options.syslog = true,
+        "--template"
+        =>  options.format = Format::Template(Self::parsed_value(&mut arguments, &argument)?),
+        "--uevent-key"
+        =>  options.uevent_key = Some(Self::value(&mut arguments, &argument)?),
+        "--use-cache"
+        =>  options.cache = Some(Self::value(&mut arguments, &argument)?.into()),
+        "--verify-all"
+        =>  options.verify_all = Some(Self::value(&mut arguments, &argument)?.into()),
+        "--verify-stdin"
+        =>  options.verify_stdin = true,
+        "--version-info"
+        =>  options.version_info = true,
+        "--what-if"
+        =>  options.what_if.push(Self::override_value(&mut arguments, &argument)?),
+        "--watch"
+        =>  options.watch = Some(Duration::from_secs(Self::parsed_value(&mut arguments, &argument)?)),
+        _
+        =>  {
+              eprintln!("Unknown option {}", argument);
+              return Err(Error::InvalidArguments);
+            },
+      }
+    }
+    options.render.color = Color::enabled(no_color);
+    options.check()
+  }
+
+  /// Whether the PINs are verified instead of printed.
+  fn verifies(&self) -> bool {
+    self.verify_all.is_some() || self.verify_stdin
+  }
+
+  /// Reject combinations of options which do not work together.
+  /// Every check is evaluated and all problems are returned together, so they can be fixed at once.
+  fn check(self) -> Result<Self, Error> {
+    let checks = [
+      (
+        !self.paginate
+        || (
+          self.groups.is_empty()
+          && self.index.is_none()
+          && self.puk_length.is_none()
+          && self.range.is_none()
+          && !self.verifies()
+          && self.watch.is_none()
+        )
+      )
+      .then_some(())
+      .ok_or_else(|| "Option --paginate cannot be combined with --group, --index, --puk, --range, --verify-all, --verify-stdin or --watch".to_owned()),
+
+      (!self.render.reverse || (!self.paginate && !self.verifies()))
+      .then_some(())
+      .ok_or_else(|| "Option --reverse cannot be combined with --paginate, --verify-all or --verify-stdin".to_owned()),
+
+      (!self.max_pins || !self.parameters.per_index)
+      .then_some(())
+      .ok_or_else(|| "Option --max-pins cannot be combined with --per-index, as each PIN has its own randomness".to_owned()),
+
+      (!self.render.sort_by_pin || (!self.paginate && !self.verifies()))
+      .then_some(())
+      .ok_or_else(|| "Option --sort-by-pin cannot be combined with --paginate, --verify-all or --verify-stdin".to_owned()),
+
+      (!self.allow_root || !self.no_root)
+      .then_some(())
+      .ok_or_else(|| "Option --allow-root cannot be combined with --no-root".to_owned()),
+
+      (self.verify_all.is_none() || !self.verify_stdin)
+      .then_some(())
+      .ok_or_else(|| "Option --verify-all cannot be combined with --verify-stdin".to_owned()),
+
+      (self.groups.is_empty() || (!self.verifies() && self.watch.is_none()))
+      .then_some(())
+      .ok_or_else(|| "Option --group cannot be combined with --verify-all, --verify-stdin or --watch".to_owned()),
+
+      (self.range.is_none() || (self.groups.is_empty() && !self.verifies() && self.watch.is_none()))
+      .then_some(())
+      .ok_or_else(|| "Option --range cannot be combined with --group, --verify-all, --verify-stdin or --watch".to_owned()),
+
+      (
+        self.index.is_none()
+        || (
+          self.groups.is_empty()
+          && self.puk_length.is_none()
+          && self.range.is_none()
+          && !self.verifies()
+          && self.watch.is_none()
+        )
+      )
+      .then_some(())
+      .ok_or_else(|| "Option --index cannot be combined with --group, --puk, --range, --verify-all, --verify-stdin or --watch".to_owned()),
+
+      (
+        self.puk_length.is_none()
+        || (self.groups.is_empty() && self.range.is_none() && !self.verifies() && self.watch.is_none())
+      )
+      .then_some(())
+      .ok_or_else(|| "Option --puk cannot be combined with --group, --range, --verify-all, --verify-stdin or --watch".to_owned()),
+
+      (
+        self.compare_algorithms.is_empty()
+        || (
+          self.compare_algorithms.len() >= 2
+          && matches!(self.format, Format::Text)
+          && self.groups.is_empty()
+          && self.index.is_none()
+          && !self.paginate
+          && self.puk_length.is_none()
+          && self.range.is_none()
+          && !self.verifies()
+          && self.watch.is_none()
+        )
+      )
+      .then_some(())
+      .ok_or_else(|| "Option --compare-algorithms requires at least two algorithms and cannot be combined with --format, --template, --group, --index, --paginate, --puk, --range, --verify-all, --verify-stdin or --watch".to_owned()),
+
+      (
+        !matches!(self.format, Format::RawDigits)
+        || (
+          matches!(self.render.redaction, Redaction::None)
+          && self.groups.is_empty()
+          && !self.paginate
+          && self.puk_length.is_none()
+        )
+      )
+      .then_some(())
+      .ok_or_else(|| "Option --format raw-digits cannot be combined with --group, --paginate, --preview, --puk, --redact or --redact-keep".to_owned()),
+
+      (
+        !self.by_serial
+        || (
+          matches!(self.format, Format::Text)
+          && self.compare_algorithms.is_empty()
+          && self.index.is_none()
+          && !self.paginate
+          && self.puk_length.is_none()
+          && self.range.is_none()
+          && !self.verifies()
+          && self.watch.is_none()
+        )
+      )
+      .then_some(())
+      .ok_or_else(|| "Option --by-serial cannot be combined with --format, --template, --compare-algorithms, --index, --paginate, --puk, --range, --verify-all, --verify-stdin or --watch".to_owned()),
+
+      (
+        self.seed_file.is_none()
+        || (
+          !self.by_serial
+          && self.compare_algorithms.is_empty()
+          && !self.fingerprint
+          && self.groups.is_empty()
+          && self.index.is_none()
+          && !self.max_pins
+          && !self.print_serials_digest
+          && !self.rng_histogram
+          && !self.provenance
+          && self.puk_length.is_none()
+          && self.range.is_none()
+          && self.watch.is_none()
+        )
+      )
+      .then_some(())
+      .ok_or_else(|| "Option --seed-from-file cannot be combined with --by-serial, --compare-algorithms, --fingerprint, --group, --index, --max-pins, --print-serials-digest, --provenance, --puk, --range, --rng-histogram or --watch".to_owned()),
+
+      (self.parameters.forbid_run.is_none_or(|run| run >= 2))
+      .then_some(())
+      .ok_or_else(|| "Option --forbid-run requires a run of at least two digits".to_owned()),
+
+      (
+        self.what_if
+        .iter()
+        .enumerate()
+        .all(|(position, (index, _))| self.what_if[..position].iter().all(|(other, _)| other != index))
+      )
+      .then_some(())
+      .ok_or_else(|| "Option --what-if cannot replace the same card reader twice".to_owned()),
+
+      (1..=Pin::LENGTH).contains(&self.parameters.pin_digits)
+      .then_some(())
+      .ok_or_else(|| format!("Option --digits must be between 1 and {}, the length of the frame", Pin::LENGTH)),
+
+      (self.parameters.hash_iterations >= 1)
+      .then_some(())
+      .ok_or_else(|| "Option --hash-iterations must be at least 1, the hash of the serial numbers itself".to_owned()),
+
+      (!self.parameters.check_digit || (self.parameters.pin_digits < Pin::LENGTH && matches!(self.parameters.alphabet, Alphabet::Decimal)))
+      .then_some(())
+      .ok_or_else(|| format!("Option --check-digit requires decimal digits and fewer than {} --digits, as it appends another digit", Pin::LENGTH)),
+
+      (self.puk_length != Some(0))
+      .then_some(())
+      .ok_or_else(|| "A PUK must have at least one digit".to_owned()),
+
+      (self.key.as_ref().is_none_or(|key| Self::KEY_LENGTHS.contains(&key.0.len())))
+      .then_some(())
+      .ok_or_else(||
+        format!(
+          "Option --key must have {} to {} bytes, i.e. twice as many hex digits",
+          Self::KEY_LENGTHS.start(),
+          Self::KEY_LENGTHS.end()
+        )
+      ),
+
+      (self.cache.is_none() || self.key.is_some())
+      .then_some(())
+      .ok_or_else(|| "Option --use-cache requires the key to authenticate the cache with".to_owned()),
+
+      (self.serial_socket.is_none() || self.key.is_some())
+      .then_some(())
+      .ok_or_else(|| "Option --serial-socket requires the key to authenticate messages with".to_owned()),
+    ];
+    let problems: Vec<String> = checks
+    .into_iter()
+    .filter_map(Result::err)
+    .collect();
+    problems
+    .is_empty()
+    .then_some(self)
+    .ok_or(Error::InvalidConfig { problems })
+  }
+
+  /// Take the value of an option from the remaining arguments.
+  fn value(
+    arguments: &mut impl Iterator<Item = String>,
+    option: &str,
+  ) -> Result<String, Error> {
+    arguments
+    .next()
+    .ok_or(Error::InvalidArguments)
+    .inspect_err(|_| eprintln!("Option {} requires a value", option))
+  }
+
+  /// Take the value of an option from the remaining arguments and parse it.
+  fn parsed_value<T: FromStr>(
+    arguments: &mut impl Iterator<Item = String>,
+    option: &str,
+  ) -> Result<T, Error> {
+    let value = Self::value(arguments, option)?;
+    value
+    .parse()
+    .map_err(|_| Error::InvalidArguments)
+    .inspect_err(|_| eprintln!("Invalid value {} of option {}", value, option))
+  }
+
+  /// Take a key from the remaining arguments and decode it from hex digits.
+  /// Both the digits and the key are zeroed when dropped.
+  fn hex_key_value(
+    arguments: &mut impl Iterator<Item = String>,
+    option: &str,
+  ) -> Result<Wiped<Vec<u8>>, Error> {
+    let value = Wiped(Self::value(arguments, option)?.into_bytes());
+    str::from_utf8(&value.0)
+    .ok()
+    .and_then(hex::decode)
+    .map(Wiped)
+    .ok_or(Error::InvalidArguments)
+    .inspect_err(|_| eprintln!("Value of option {} must be hex digits", option))
+  }
+
+  /// Take the path of a file from the remaining arguments and read a key from it, as raw bytes,
+  ///   so it does not show up in the list of processes as with `--key`.
+  /// The key must have 16 to 64 bytes, see `KEY_LENGTHS`.
+  /// A file anyone can read is warned about; its permissions are taken from the file opened, not from the path again.
+  /// The bytes read are zeroed when dropped, even if they are refused.
+  fn key_file_value(
+    arguments: &mut impl Iterator<Item = String>,
+    option: &str,
+  ) -> Result<Wiped<Vec<u8>>, Error> {
+    let file_name = Self::value(arguments, option)?;
+    let mut file = File::open(&file_name)
+    .inspect_err(|error| eprintln!("Cannot open file {}: {}", file_name, error))
+    .map_err(|_| Error::CannotReadFile)?;
+    if file.metadata().is_ok_and(|metadata| metadata.permissions().mode() & 0o004 != 0) {
+      eprintln!("Warning: Key file {} can be read by anyone, restrict it with chmod o-r", file_name);
+    }
+    let mut key = Wiped(Vec::with_capacity(*Self::KEY_LENGTHS.end() + 1));
+    file
+    .by_ref()
+    .take(*Self::KEY_LENGTHS.end() as u64 + 1)
+    .read_to_end(&mut key.0)
+    .inspect_err(|error| eprintln!("Cannot read file {}: {}", file_name, error))
+    .map_err(|_| Error::CannotReadFile)?;
+    Self::KEY_LENGTHS
+    .contains(&key.0.len())
+    .then_some(key)
+    .ok_or(Error::InvalidArguments)
+    .inspect_err(|_|
+      eprintln!(
+        "Key file {} must have {} to {} bytes",
+        file_name,
+        Self::KEY_LENGTHS.start(),
+        Self::KEY_LENGTHS.end()
+      )
+    )
+  }
+
+  /// Take the value of an option from the remaining arguments and parse it as a single hex digit.
+  fn nibble_value(
+    arguments: &mut impl Iterator<Item = String>,
+    option: &str,
+  ) -> Result<u8, Error> {
+    let value = Self::value(arguments, option)?;
+    (value.len() == 1)
+    .then(|| u8::from_str_radix(&value, 16).ok())
+    .flatten()
+    .ok_or(Error::InvalidArguments)
+    .inspect_err(|_| eprintln!("Value of option {} must be a single hex digit", option))
+  }
+
+  /// Take the value of an option from the remaining arguments and parse it as a range `start..end`.
+  fn range_value(
+    arguments: &mut impl Iterator<Item = String>,
+    option: &str,
+  ) -> Result<Range<usize>, Error> {
+    let value = Self::value(arguments, option)?;
+    value
+    .split_once("..")
+    .and_then(|(start, end)| Some(start.trim().parse().ok()?..end.trim().parse().ok()?))
+    .ok_or(Error::InvalidArguments)
+    .inspect_err(|_| eprintln!("Invalid value {} of option {}, expected start..end", value, option))
+  }
+
+  /// Take the value of an option from the remaining arguments and parse it as `index=serial number`.
+  fn override_value(
+    arguments: &mut impl Iterator<Item = String>,
+    option: &str,
+  ) -> Result<(usize, SerialNumber), Error> {
+    let value = Self::value(arguments, option)?;
+    value
+    .split_once('=')
+    .and_then(|(index, serial_number)| Some((index.trim().parse().ok()?, serial_number.parse().ok()?)))
+    .ok_or(Error::InvalidArguments)
+    .inspect_err(|_|
+      eprintln!(
+        "Invalid value {} of option {}, expected index=serial number of {} bytes",
+        value,
+        option,
+        SerialNumber::LENGTH
+      )
+    )
+  }
+
+  /// Take the value of an option from the remaining arguments and parse it as a comma separated list.
+  fn parsed_list<T: FromStr>(
+    arguments: &mut impl Iterator<Item = String>,
+    option: &str,
+  ) -> Result<Vec<T>, Error> {
+    let value = Self::value(arguments, option)?;
+    value
+    .split(',')
+    .map(|element| element.trim().parse())
+    .collect::<Result<Vec<T>, _>>()
+    .map_err(|_| Error::InvalidArguments)
+    .inspect_err(|_| eprintln!("Invalid value {} of option {}", value, option))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::Options,
+    crate::Error,
+    std::{
+      env,
+      fs,
+      process,
+    },
+  };
+
+  /// All problems of the options are reported together, not just the first one.
+  #[test]
+  fn all_problems_are_reported() {
+    let arguments = ["--digits", "0", "--allow-root", "--no-root", "--forbid-run", "1", "--puk-length", "0"];
+    match Options::parse(arguments.into_iter().map(str::to_owned)) {
+      Err(Error::InvalidConfig { problems })
+      =>  {
+            assert_eq!(problems.len(), 4, "{:?}", problems);
+            ["--digits", "--allow-root", "--forbid-run", "PUK"]
+            .into_iter()
+            .for_each(|option| assert!(problems.iter().any(|problem| problem.contains(option)), "{}", option));
+          },
+      other
+      =>  panic!("expected all problems, got {:?}", other.map(|_| ())),
+    }
+  }
+
+  /// An empty or short key is refused, as it is easily guessed.
+  #[test]
+  fn short_key_is_refused() {
+    ["", "00", "000102030405060708090a0b0c0d0e"]
+    .into_iter()
+    .for_each(|key| assert!(Options::parse(["--key".to_owned(), key.to_owned()].into_iter()).is_err()));
+  }
+
+  /// The key read by `--keyfile` is the same as the one given by `--key` as hex digits.
+  #[test]
+  fn keyfile_equals_inline_key() {
+    let key = b"0123456789abcdef0123456789abcdef";
+    let path = env::temp_dir().join(format!("keyfile-{}", process::id()));
+    fs::write(&path, key).unwrap();
+    let from_file = Options::parse(
+      ["--keyfile".to_owned(), path.display().to_string()].into_iter()
+    );
+    fs::remove_file(&path).unwrap();
+    let inline = Options::parse(
+      ["--key".to_owned(), key.iter().map(|byte| format!("{:02x}", byte)).collect()].into_iter()
+    );
+    assert_eq!(from_file.unwrap().key.map(|key| key.0.clone()), inline.unwrap().key.map(|key| key.0.clone()));
+  }
+}
@@ -0,0 +1,94 @@
+use {
+  crate::{
+    format::{
+      DerivationMeta,
+      IndexedPin,
+      PinFormatter,
+    },
+    Error,
+  },
+  std::io::{
+    self,
+    BufRead,
+    IsTerminal,
+    Write,
+  },
+};
+
+/// Clear the screen and move the cursor to its top left corner.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// Check that both stdin and stdout are terminals, as pagination requires someone to press Enter.
+pub fn check_terminal() -> Result<(), Error> {
+  (io::stdin().is_terminal() && io::stdout().is_terminal())
+  .then_some(())
+  .ok_or(Error::InvalidArguments)
+  .inspect_err(|_| eprintln!("Option --paginate requires a terminal"))
+}
+
+/// Show one PIN at a time, wait for Enter and clear the screen before showing the next one,
+///   so never more than a single PIN is visible on a shared screen.
+pub fn paginate(
+  formatter: &dyn PinFormatter,
+  pins: &[IndexedPin],
+  meta: &DerivationMeta,
+  input: &mut impl BufRead,
+  writer: &mut impl Write,
+) -> Result<(), Error> {
+  pins
+  .iter()
+  .try_for_each(|pin| {
+    formatter.render(&[*pin], &[], meta, writer)?;
+    write!(writer, "Press Enter to continue")?;
+    writer.flush()?;
+    input.read_line(&mut String::new())?;
+    write!(writer, "{}", CLEAR_SCREEN)?;
+    writer.flush()
+  })
+  .inspect_err(|error| eprintln!("Cannot paginate PINs: {}", error))
+  .map_err(|_| Error::CannotWritePins)
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{
+      paginate,
+      CLEAR_SCREEN,
+    },
+    crate::{
+      format::{
+        DerivationMeta,
+        Format,
+        RenderOptions,
+      },
+      Parameters,
+      Pin,
+    },
+  };
+
+  /// Each PIN is shown on its own and the screen is cleared after Enter, before the next one.
+  #[test]
+  fn one_pin_per_page() {
+    let pins = [(0, Pin::default()), (1, Pin::new(&[0x00, 0x11, 0x22, 0x33, 0x44, 0x05]))];
+    let formatter = Format::Text.formatter(RenderOptions::default());
+    let mut output = Vec::new();
+    paginate(
+      formatter.as_ref(),
+      &pins,
+      &DerivationMeta::new(Parameters::default(), pins.len()),
+      &mut &b"\n\n"[..],
+      &mut output,
+    )
+    .unwrap();
+    assert_eq!(
+      String::from_utf8(output).unwrap(),
+      format!(
+        "PIN 0: [2c, 12, 34, 56, 78, 91, 23, ff]: 1 2 3 4 5 6 7 8 9 1 2 3\nPress Enter to continue{}\
+        PIN 1: [2c, 00, 11, 22, 33, 44, 05, ff]: 0 0 1 1 2 2 3 3 4 4 0 5\nPress Enter to continue{}",
+        CLEAR_SCREEN,
+        CLEAR_SCREEN
+      )
+    );
+  }
+}
@@ -0,0 +1,77 @@
+use crate::Error;
+
+extern "C" {
+  fn geteuid() -> u32;
+}
+
+/// What to do about the effective user this tool runs as, see `decide`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Decision {
+  /// Run without further ado.
+  Proceed,
+  /// Run, but warn about the unnecessary privileges.
+  Warn,
+  /// Refuse to run.
+  Refuse,
+}
+
+/// Decide what to do when running as the effective user `euid`:
+///   Reading the sysfs attributes of the card readers does not require root,
+///   so running as root is refused with `--no-root`, tolerated with `--allow-root` and warned about otherwise.
+pub fn decide(euid: u32, allow_root: bool, no_root: bool) -> Decision {
+  match (euid, allow_root, no_root) {
+    (0, _, true)      => Decision::Refuse,
+    (0, false, false) => Decision::Warn,
+    _                 => Decision::Proceed,
+  }
+}
+
+/// Get the effective user id this tool runs as.
+pub fn euid() -> u32 {
+  unsafe { geteuid() }
+}
+
+/// Warn about or refuse running as root, see `decide`.
+pub fn check(allow_root: bool, no_root: bool) -> Result<(), Error> {
+  match decide(euid(), allow_root, no_root) {
+    Decision::Proceed => Ok(()),
+    Decision::Warn
+    =>  {
+          eprintln!("Warning: Running as root is not necessary to read the card readers, see --allow-root and --no-root");
+          Ok(())
+        },
+    Decision::Refuse
+    =>  {
+          eprintln!("Refusing to run as root, see --no-root");
+          Err(Error::RunningAsRoot)
+        },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{
+    decide,
+    Decision,
+  };
+
+  /// Root is warned about, tolerated with `--allow-root` and refused with `--no-root`, even with both.
+  /// Any other user just runs, whatever the options.
+  #[test]
+  fn root_is_decided_by_options() {
+    [
+      (0,     false,  false,  Decision::Warn),
+      (0,     true,   false,  Decision::Proceed),
+      (0,     false,  true,   Decision::Refuse),
+      (0,     true,   true,   Decision::Refuse),
+      (1000,  false,  false,  Decision::Proceed),
+      (1000,  true,   false,  Decision::Proceed),
+      (1000,  false,  true,   Decision::Proceed),
+      (1000,  true,   true,   Decision::Proceed),
+    ]
+    .into_iter()
+    .for_each(|(euid, allow_root, no_root, decision)|
+      assert_eq!(decide(euid, allow_root, no_root), decision, "euid {}, allow_root {}, no_root {}", euid, allow_root, no_root)
+    );
+  }
+}
@@ -0,0 +1,190 @@
+use {
+  crate::{
+    glob,
+    Error,
+    CARD_READERS,
+  },
+  std::{
+    fmt::{
+      Display,
+      Formatter,
+      Result as FormatResult,
+    },
+    fs,
+    io::{
+      self,
+      ErrorKind,
+    },
+    path::{
+      Path,
+      PathBuf,
+    },
+  },
+};
+
+/// Whether a card reader is connected, as reported by sysfs.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReaderStatus {
+  /// The device is connected and may be used.
+  Present,
+  /// The device is connected, but not authorised to be used.
+  Disabled,
+  /// There is no such device, e.g. because the reader is unplugged.
+  Absent,
+}
+
+impl Display for ReaderStatus {
+  fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+    formatter.write_str(
+      match self {
+        Self::Present   => "present",
+        Self::Disabled  => "disabled",
+        Self::Absent    => "absent",
+      }
+    )
+  }
+}
+
+/// Probe the card reader a serial number attribute belongs to, before reading it:
+///   The reader is absent, if the device directory does not exist,
+///   and disabled, if its `authorized` attribute is `0`.
+/// The device directory of a relative path of a single component, e.g. `serial`, is the current directory.
+pub fn probe_reader(path: &Path) -> ReaderStatus {
+  match path.parent().map(|device| if device.as_os_str().is_empty() { Path::new(".") } else { device }) {
+    Some(device) if device.is_dir()
+    =>  match fs::read_to_string(device.join("authorized")) {
+          Ok(authorized) if authorized.trim() == "0" => ReaderStatus::Disabled,
+          _                                          => ReaderStatus::Present,
+        },
+    _   => ReaderStatus::Absent,
+  }
+}
+
+/// Resolve the path of the serial number attribute of a card reader to the regular file it names.
+/// The kernel follows a bounded number of symbolic links, so a loop fails instead of hanging;
+///   anything but a regular file, e.g. a FIFO or a device, is refused, as opening or reading it might block.
+pub fn resolve_reader_path(path: &Path) -> Result<PathBuf, Error> {
+  fs::canonicalize(path)
+  .and_then(|resolved| fs::metadata(&resolved).map(|metadata| (resolved, metadata)))
+  .inspect_err(|error| eprintln!("Cannot resolve path {} of card reader: {}", path.display(), error))
+  .map_err(|_| Error::BadReaderPath)
+  .and_then(|(resolved, metadata)|
+    metadata
+    .is_file()
+    .then_some(resolved)
+    .ok_or(Error::BadReaderPath)
+    .inspect_err(|_| eprintln!("Path {} of card reader is no regular file", path.display()))
+  )
+}
+
+/// Tell why a card reader cannot be opened:
+///   A missing file means the reader is gone, see `probe_reader`, a lack of permission needs a change of the setup.
+pub fn open_error(error: &io::Error) -> Error {
+  match error.kind() {
+    ErrorKind::NotFound         => Error::CardReaderUnavailable,
+    ErrorKind::PermissionDenied => Error::CardReaderPermissionDenied,
+    _                           => Error::CannotOpenCardReader,
+  }
+}
+
+/// Print the status of each card reader, either the configured ones or those matching a pattern.
+pub fn list_readers(pattern: Option<&str>) -> Result<(), Error> {
+  let paths = match pattern {
+    Some(pattern)
+    =>  glob::expand(pattern)
+        .inspect_err(|error| eprintln!("Cannot expand pattern {}: {}", pattern, error))
+        .map_err(|_| Error::CannotOpenCardReader)?,
+    None
+    =>  CARD_READERS.iter().map(PathBuf::from).collect(),
+  };
+  paths
+  .iter()
+  .for_each(|path| println!("{}: {}", path.display(), probe_reader(path)));
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{
+      open_error,
+      probe_reader,
+      resolve_reader_path,
+      ReaderStatus,
+    },
+    crate::Error,
+    std::{
+      env,
+      fs::{
+        self,
+        File,
+      },
+      io,
+      os::unix::fs::symlink,
+      path::Path,
+      process,
+    },
+  };
+
+  /// A relative path without a directory belongs to the current directory, which exists.
+  #[test]
+  fn single_component_is_present() {
+    assert_eq!(probe_reader(Path::new("serial")), ReaderStatus::Present);
+    assert_eq!(probe_reader(Path::new("/nonexistent/serial")), ReaderStatus::Absent);
+  }
+
+  /// Fixture directories of card readers: One present, one present and authorised, one disabled and one unplugged.
+  #[test]
+  fn fixture_readers_are_probed() {
+    let directory = env::temp_dir().join(format!("readers-{}", process::id()));
+    [("present", None), ("authorised", Some("1\n")), ("disabled", Some("0\n"))]
+    .into_iter()
+    .for_each(|(name, authorized)| {
+      let device = directory.join(name);
+      fs::create_dir_all(&device).unwrap();
+      fs::write(device.join("serial"), "23421337\n").unwrap();
+      if let Some(authorized) = authorized {
+        fs::write(device.join("authorized"), authorized).unwrap();
+      }
+    });
+    let status: Vec<ReaderStatus> = ["present", "authorised", "disabled", "unplugged"]
+    .into_iter()
+    .map(|name| probe_reader(&directory.join(name).join("serial")))
+    .collect();
+    fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(status, [ReaderStatus::Present, ReaderStatus::Present, ReaderStatus::Disabled, ReaderStatus::Absent]);
+  }
+
+  /// A card reader path of a symbolic link loop or pointing at a directory is refused as `Error::BadReaderPath`,
+  ///   instead of hanging, while a link to a regular file is resolved to it.
+  #[test]
+  fn link_loop_is_refused() {
+    let directory = env::temp_dir().join(format!("links-{}", process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let serial = directory.join("serial");
+    fs::write(&serial, "23421337\n").unwrap();
+    symlink(directory.join("ping"), directory.join("pong")).unwrap();
+    symlink(directory.join("pong"), directory.join("ping")).unwrap();
+    symlink(&directory, directory.join("device")).unwrap();
+    symlink(&serial, directory.join("link")).unwrap();
+    let resolved: Vec<_> = ["ping", "device", "link"]
+    .into_iter()
+    .map(|name| resolve_reader_path(&directory.join(name)))
+    .collect();
+    let serial = fs::canonicalize(&serial).unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(resolved, [Err(Error::BadReaderPath), Err(Error::BadReaderPath), Ok(serial)]);
+  }
+
+  /// A card reader which cannot be opened for lack of permission, i.e. `EACCES`, gets its own error with a hint what to change,
+  ///   distinct from one which is gone, i.e. `ENOENT`, or any other failure.
+  #[test]
+  fn permission_error_has_hint() {
+    let denied = open_error(&io::Error::from_raw_os_error(13));
+    assert_eq!(denied, Error::CardReaderPermissionDenied);
+    assert!(denied.to_string().contains("udev rules"), "{}", denied);
+    let missing = File::open("/nonexistent/serial").unwrap_err();
+    assert_eq!(open_error(&missing), Error::CardReaderUnavailable);
+    assert_eq!(open_error(&io::Error::from_raw_os_error(5)), Error::CannotOpenCardReader);
+  }
+}
@@ -0,0 +1,131 @@
+use crate::{
+  try_calculate_all_pins,
+  Error,
+  Parameters,
+  Pin,
+  SerialNumbers,
+  TEST_SERIAL_NUMBERS,
+};
+
+/// The exact frames of the PINs derived from `TEST_SERIAL_NUMBERS` with the default parameters.
+const EXPECTED_PINS: [[u8; Pin::SIZE]; 6] = [
+  [ 0x2c, 0x79, 0x41, 0x58, 0x70, 0x25, 0x77, 0xff ],
+  [ 0x2c, 0x23, 0x86, 0x42, 0x11, 0x97, 0x82, 0xff ],
+  [ 0x2c, 0x05, 0x03, 0x46, 0x38, 0x60, 0x81, 0xff ],
+  [ 0x2c, 0x64, 0x52, 0x26, 0x16, 0x55, 0x00, 0xff ],
+  [ 0x2c, 0x24, 0x51, 0x83, 0x70, 0x65, 0x55, 0xff ],
+  [ 0x2c, 0x17, 0x97, 0x40, 0x82, 0x99, 0x35, 0xff ],
+];
+
+/// The exact frames of the PINs derived from `TEST_SERIAL_NUMBERS` with `--length-bind`.
+/// The lengths are hashed as little endian integers, so these catch a change to native endianness
+///   on the big endian platforms, and to any other endianness on all platforms.
+const EXPECTED_LENGTH_BOUND_PINS: [[u8; Pin::SIZE]; 6] = [
+  [ 0x2c, 0x12, 0x94, 0x78, 0x62, 0x32, 0x19, 0xff ],
+  [ 0x2c, 0x90, 0x75, 0x83, 0x38, 0x16, 0x61, 0xff ],
+  [ 0x2c, 0x60, 0x29, 0x95, 0x23, 0x26, 0x62, 0xff ],
+  [ 0x2c, 0x78, 0x62, 0x23, 0x57, 0x55, 0x58, 0xff ],
+  [ 0x2c, 0x23, 0x42, 0x92, 0x66, 0x02, 0x82, 0xff ],
+  [ 0x2c, 0x47, 0x43, 0x14, 0x15, 0x75, 0x54, 0xff ],
+];
+
+/// Derive the PINs of fixed serial numbers and compare them byte by byte with the expected ones,
+///   to catch any platform dependent behaviour, e.g. of the order of the serial numbers,
+///   the endianness of hashed integers or of the packing of the digits, before PINs are calculated on a new machine.
+/// This covers the SHA-512 backend sha2 selects for the CPU at runtime as well,
+///   which can be ruled out by building with the feature `force-soft-sha`.
+pub fn run() -> Result<(), Error> {
+  let default = Parameters {
+    count: EXPECTED_PINS.len(),
+    ..Parameters::default()
+  };
+  let length_bound = Parameters {
+    count:        EXPECTED_LENGTH_BOUND_PINS.len(),
+    length_bind:  true,
+    ..Parameters::default()
+  };
+  let failed = check("", default, &EXPECTED_PINS)? + check("length bound ", length_bound, &EXPECTED_LENGTH_BOUND_PINS)?;
+
+  let total = EXPECTED_PINS.len() + EXPECTED_LENGTH_BOUND_PINS.len();
+  println!("Self test: {} of {} PINs as expected", total - failed, total);
+  (failed == 0)
+  .then_some(())
+  .ok_or(Error::SelfTestFailed)
+}
+
+/// Derive the PINs of `TEST_SERIAL_NUMBERS` with these parameters and count those differing from the expected ones.
+fn check(
+  label: &str,
+  parameters: Parameters,
+  expected_pins: &[[u8; Pin::SIZE]],
+) -> Result<usize, Error> {
+  let pins = try_calculate_all_pins(&SerialNumbers::new(TEST_SERIAL_NUMBERS.to_vec(), parameters)?, parameters)?;
+  Ok(
+    pins
+    .iter()
+    .zip(expected_pins)
+    .enumerate()
+    .filter(|(_, (pin, expected))| pin.0 != **expected)
+    .inspect(|(index, (pin, expected))|
+      eprintln!(
+        "{}PIN {} is {:02x?}, expected {:02x?}",
+        label,
+        index,
+        pin.0,
+        expected
+      )
+    )
+    .count()
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{
+      check,
+      run,
+      EXPECTED_LENGTH_BOUND_PINS,
+      EXPECTED_PINS,
+    },
+    crate::Parameters,
+    sha2::{
+      Digest,
+      Sha512,
+    },
+  };
+
+  /// The PINs of this build are the ones expected, as checked by `--self-test`.
+  #[test]
+  fn self_test_passes() {
+    run().unwrap();
+  }
+
+  /// The backend of sha2 yields the SHA-512 of FIPS 180-2 for `abc`.
+  /// Run with and without `--features force-soft-sha`, so the portable and the SIMD backends are both checked,
+  ///   together with the PINs of `self_test_passes`.
+  #[test]
+  fn sha512_backend_known_answer() {
+    assert_eq!(
+      Sha512::digest(b"abc")[..],
+      [
+        0xdd, 0xaf, 0x35, 0xa1, 0x93, 0x61, 0x7a, 0xba, 0xcc, 0x41, 0x73, 0x49, 0xae, 0x20, 0x41, 0x31,
+        0x12, 0xe6, 0xfa, 0x4e, 0x89, 0xa9, 0x7e, 0xa2, 0x0a, 0x9e, 0xee, 0xe6, 0x4b, 0x55, 0xd3, 0x9a,
+        0x21, 0x92, 0x99, 0x2a, 0x27, 0x4f, 0xc1, 0xa8, 0x36, 0xba, 0x3c, 0x23, 0xa3, 0xfe, 0xeb, 0xbd,
+        0x45, 0x4d, 0x44, 0x23, 0x64, 0x3c, 0xe8, 0x0e, 0x2a, 0x9a, 0xc9, 0x4f, 0xa5, 0x4c, 0xa4, 0x9f,
+      ]
+    );
+  }
+
+  /// The length prefix changes every PIN, so a lost `--length-bind` cannot pass the self test.
+  #[test]
+  fn length_bind_changes_all_pins() {
+    let length_bound = Parameters {
+      count:        EXPECTED_PINS.len(),
+      length_bind:  true,
+      ..Parameters::default()
+    };
+    assert_eq!(check("", length_bound, &EXPECTED_PINS).unwrap(), EXPECTED_PINS.len());
+    assert_eq!(check("", length_bound, &EXPECTED_LENGTH_BOUND_PINS).unwrap(), 0);
+  }
+}
@@ -0,0 +1,176 @@
+use {
+  crate::{
+    hex,
+    hmac::hmac_sha256,
+    source::{collect_all, SerialSource},
+    verify::constant_time_eq,
+    Error,
+    SerialNumber,
+  },
+  std::{
+    fs::File,
+    io::{
+      self,
+      BufRead,
+      BufReader,
+      ErrorKind,
+      Read,
+      Result as IoResult,
+      Write,
+    },
+    net::{
+      TcpStream,
+      ToSocketAddrs,
+    },
+    time::Duration,
+  },
+};
+
+/// The serial numbers sent by a service on the network, e.g. a host the card readers are attached to.
+/// This tool sends a line with a fresh random challenge, hex encoded, and the service answers with a single line
+/// ```text
+/// <challenge> <serial number>,<serial number>,… <mac>
+/// ```
+///   where the serial numbers are hex encoded
+///   and the MAC is the hex encoded HMAC-SHA256 of everything before the last space,
+///   keyed with a secret shared with this tool.
+/// Messages with a wrong MAC are rejected, so nobody without the key can choose the serial numbers,
+///   and so are messages with another challenge than the one just sent,
+///   so a recorded message cannot be replayed, neither in this run nor in a later one.
+/// The connection times out and an answer longer than `MAX_MESSAGE_LENGTH` is refused,
+///   so a stalled or hostile service cannot hang this tool or exhaust its memory.
+pub struct SocketSource<'a> {
+  address:    String,
+  key:        &'a [u8],
+}
+
+impl<'a> SocketSource<'a> {
+  /// Number of random bytes of a challenge.
+  const CHALLENGE_LENGTH: usize = 16;
+
+  /// Give up connecting, sending or receiving after this long.
+  const TIMEOUT: Duration = Duration::from_secs(10);
+
+  /// Refuse answers longer than this, enough for the challenge, the MAC and hundreds of serial numbers.
+  const MAX_MESSAGE_LENGTH: u64 = 0x2000;
+
+  pub fn new(address: String, key: &'a [u8]) -> Self {
+    Self {
+      address,
+      key,
+    }
+  }
+
+  /// Draw a fresh random challenge from the kernel.
+  fn challenge() -> Result<String, Error> {
+    let mut challenge = [0; Self::CHALLENGE_LENGTH];
+    File::open("/dev/urandom")
+    .and_then(|mut random| random.read_exact(&mut challenge))
+    .inspect_err(|error| eprintln!("Cannot draw a random challenge: {}", error))
+    .map_err(|_| Error::CannotReceiveSerialNumbers)?;
+    Ok(hex::encode(&challenge))
+  }
+
+  /// Connect to the first address the service resolves to which accepts a connection in time.
+  fn connect(&self) -> IoResult<TcpStream> {
+    let mut last_error = io::Error::new(ErrorKind::NotFound, "address resolves to nothing");
+    for address in self.address.to_socket_addrs()? {
+      match TcpStream::connect_timeout(&address, Self::TIMEOUT) {
+        Ok(stream)  => return Ok(stream),
+        Err(error)  => last_error = error,
+      }
+    }
+    Err(last_error)
+  }
+
+  /// Send the challenge and receive the single line of the answer, at most `MAX_MESSAGE_LENGTH` bytes.
+  fn exchange(&self, challenge: &str) -> IoResult<String> {
+    let mut stream = self.connect()?;
+    stream.set_read_timeout(Some(Self::TIMEOUT))?;
+    stream.set_write_timeout(Some(Self::TIMEOUT))?;
+    writeln!(stream, "{}", challenge)?;
+    let mut message = String::new();
+    BufReader::new(stream)
+    .take(Self::MAX_MESSAGE_LENGTH)
+    .read_line(&mut message)?;
+    message
+    .ends_with('\n')
+    .then_some(message)
+    .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "answer too long or not terminated"))
+  }
+
+  /// Authenticate a message answering `challenge` and decode the serial numbers.
+  pub fn decode(&self, message: &str, challenge: &str) -> Result<Vec<SerialNumber>, Error> {
+    let message = message.trim_end();
+    let (authenticated, mac) = message.rsplit_once(' ').ok_or(Error::InvalidMessage)?;
+    let mac = hex::decode(mac).ok_or(Error::InvalidMessage)?;
+    constant_time_eq(&hmac_sha256(self.key, authenticated.as_bytes()), &mac)
+    .then_some(())
+    .ok_or(Error::UnauthenticatedMessage)
+    .inspect_err(|_| eprintln!("Message from {} has an invalid MAC", self.address))?;
+
+    let (answered, serial_numbers) = authenticated.split_once(' ').ok_or(Error::InvalidMessage)?;
+    (answered == challenge)
+    .then_some(())
+    .ok_or(Error::ReplayedMessage)
+    .inspect_err(|_|
+      eprintln!(
+        "Message from {} answers challenge {}, not {}",
+        self.address,
+        answered,
+        challenge
+      )
+    )?;
+
+    serial_numbers
+    .split(',')
+    .map(|serial_number|
+      hex::decode(serial_number)
+      .and_then(|bytes| bytes.try_into().ok())
+      .map(SerialNumber)
+    )
+    .collect::<Option<Vec<SerialNumber>>>()
+    .ok_or(Error::InvalidMessage)
+    .inspect_err(|_| eprintln!("Message from {} contains an invalid serial number", self.address))
+  }
+}
+
+impl SerialSource for SocketSource<'_> {
+  fn read_serials(&self) -> Result<Vec<SerialNumber>, Error> {
+    let challenge = Self::challenge()?;
+    let message = self
+    .exchange(&challenge)
+    .inspect_err(|error| eprintln!("Cannot receive serial numbers from {}: {}", self.address, error))
+    .map_err(|_| Error::CannotReceiveSerialNumbers)?;
+    self.decode(&message, &challenge).and_then(collect_all)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::SocketSource,
+    crate::{
+      hex,
+      hmac::hmac_sha256,
+      Error,
+      SerialNumber,
+    },
+  };
+
+  /// Sign the message with the key, the way the service does.
+  fn signed(key: &[u8], message: &str) -> String {
+    format!("{} {}\n", message, hex::encode(&hmac_sha256(key, message.as_bytes())))
+  }
+
+  /// Only the answer to the current challenge is accepted, a recorded one is a replay.
+  #[test]
+  fn recorded_answer_is_refused() {
+    let key = b"0123456789abcdef";
+    let source = SocketSource::new("localhost:0".to_owned(), key);
+    let message = signed(key, &format!("00ff {}", hex::encode(b"23421337")));
+    assert_eq!(source.decode(&message, "00ff").unwrap(), vec![SerialNumber(*b"23421337")]);
+    assert!(matches!(source.decode(&message, "0100"), Err(Error::ReplayedMessage)));
+    assert!(matches!(source.decode(&message.replace("ff ", "fe "), "00fe"), Err(Error::UnauthenticatedMessage)));
+  }
+}
@@ -0,0 +1,479 @@
+use {
+  crate::{
+    glob,
+    summary,
+    manifest,
+    try_read_serial_number,
+    verify::constant_time_eq,
+    Error,
+    ListOfCardReaders,
+    SerialNumber,
+  },
+  std::{
+    env,
+    fs,
+    path::{
+      Path,
+      PathBuf,
+    },
+  },
+};
+
+/// Somewhere the serial numbers of the card readers come from.
+pub trait SerialSource {
+  /// Read the serial numbers of all card readers, ordered by their slots.
+  fn read_serials(&self) -> Result<Vec<SerialNumber>, Error>;
+}
+
+/// Serial numbers known in advance, e.g. for testing purposes.
+pub struct FixedSource(pub Vec<SerialNumber>);
+
+impl SerialSource for FixedSource {
+  fn read_serials(&self) -> Result<Vec<SerialNumber>, Error> {
+    collect_all(self.0.clone())
+  }
+}
+
+/// The serial numbers read from the sysfs attributes of the card readers, at an offset into each of them,
+///   and whether to substitute `SerialNumber::MISSING` for those which cannot be read.
+/// All card readers are read, even if some fail, so all failures are reported at once.
+pub struct DeviceSource(pub ListOfCardReaders, pub u64, pub bool);
+
+impl SerialSource for DeviceSource {
+  fn read_serials(&self) -> Result<Vec<SerialNumber>, Error> {
+    collect_readings(
+      self.0
+      .iter()
+      .map(|&file_name| try_read_serial_number(file_name, self.1).map_err(|error| (file_name.to_string(), error)))
+      .collect(),
+      self.2,
+    )
+  }
+}
+
+/// The serial numbers of the card readers, read from the `uevent` file of each device where its sysfs attribute is absent,
+///   as some drivers only report the serial number there, see `--uevent-key`.
+/// The value of the key must be the serial number itself, so the offset into the attribute does not apply to it.
+pub struct UeventSource {
+  pub readers:          ListOfCardReaders,
+  pub offset:           u64,
+  pub key:              String,
+  pub missing_as_empty: bool,
+}
+
+impl UeventSource {
+  /// Read the serial number of a card reader from its attribute or else from the `uevent` file beside it.
+  fn read_serial(&self, file_name: &str) -> Result<SerialNumber, Error> {
+    let path = Path::new(file_name);
+    if path.exists() {
+      return try_read_serial_number(file_name, self.offset);
+    }
+    let uevent = path.with_file_name("uevent");
+    let content = fs::read_to_string(&uevent)
+    .inspect_err(|error| eprintln!("Cannot read file {}: {}", uevent.display(), error))
+    .map_err(|_| Error::CannotReadSerialNumber)?;
+    parse_uevent(&content, &self.key)
+    .ok_or(Error::CannotReadSerialNumber)
+    .inspect_err(|_| eprintln!("File {} has no {}", uevent.display(), self.key))?
+    .parse()
+    .inspect_err(|_|
+      eprintln!(
+        "Value of {} in file {} is no serial number of {} bytes",
+        self.key,
+        uevent.display(),
+        SerialNumber::LENGTH
+      )
+    )
+    .map_err(|_| Error::CannotReadSerialNumber)
+  }
+}
+
+impl SerialSource for UeventSource {
+  fn read_serials(&self) -> Result<Vec<SerialNumber>, Error> {
+    collect_readings(
+      self.readers
+      .iter()
+      .map(|&file_name| self.read_serial(file_name).map_err(|error| (file_name.to_string(), error)))
+      .collect(),
+      self.missing_as_empty,
+    )
+  }
+}
+
+/// Get the value of a key of a `uevent` file, whose lines are `KEY=value`, e.g. `SERIAL=23421337` for the key `SERIAL`.
+/// Lines without `=` are skipped; if a key occurs several times, the first value counts.
+fn parse_uevent<'a>(content: &'a str, key: &str) -> Option<&'a str> {
+  content
+  .lines()
+  .filter_map(|line| line.split_once('='))
+  .find_map(|(name, value)| (name == key).then_some(value))
+}
+
+/// Collect serial numbers which were all read at once, e.g. from a file, so they are counted like those of card readers.
+pub fn collect_all(serial_numbers: Vec<SerialNumber>) -> Result<Vec<SerialNumber>, Error> {
+  collect_readings(serial_numbers.into_iter().map(Ok).collect(), false)
+}
+
+/// Collect the serial numbers read from card readers or report all failures.
+/// Every source counts its card readers here for `--summary-json`, see `summary::record_readings`.
+/// With `missing_as_empty`, the serial number of each card reader which failed is replaced by `SerialNumber::MISSING`,
+///   see `--missing-as-empty`, unless all of them failed, as no entropy would be left at all.
+fn collect_readings(results: Vec<Result<SerialNumber, (String, Error)>>, missing_as_empty: bool) -> Result<Vec<SerialNumber>, Error> {
+  let failures: Vec<(String, Error)> = results
+  .iter()
+  .filter_map(|result| result.clone().err())
+  .collect();
+  summary::record_readings(results.len(), failures.len());
+  if failures.is_empty() {
+    return Ok(results.into_iter().flatten().collect());
+  }
+  if !missing_as_empty || failures.len() == results.len() {
+    return Err(summarize_failures(&failures, results.len()));
+  }
+
+  eprintln!(
+    "Warning: {} of {} card readers failed, their serial numbers are replaced by zero bytes, so the PINs are weaker, see --missing-as-empty: {}",
+    failures.len(),
+    results.len(),
+    failures
+    .iter()
+    .map(|(file_name, _)| file_name.as_str())
+    .collect::<Vec<&str>>()
+    .join(", ")
+  );
+  Ok(
+    results
+    .into_iter()
+    .map(|result| result.unwrap_or(SerialNumber::MISSING))
+    .collect()
+  )
+}
+
+/// Report which of `count` card readers failed in a single line and tell whether all or only some did.
+fn summarize_failures(failures: &[(String, Error)], count: usize) -> Error {
+  eprintln!(
+    "{} of {} card readers failed: {}",
+    failures.len(),
+    count,
+    failures
+    .iter()
+    .map(|(file_name, error)| format!("{} ({})", file_name, error))
+    .collect::<Vec<String>>()
+    .join(", ")
+  );
+  if failures.len() < count {
+    Error::SomeReadersFailed
+  } else {
+    Error::CannotReadSerialNumber
+  }
+}
+
+/// The serial numbers recorded in a manifest, see `manifest::try_read_manifest`.
+pub struct ManifestSource(pub PathBuf);
+
+impl SerialSource for ManifestSource {
+  fn read_serials(&self) -> Result<Vec<SerialNumber>, Error> {
+    manifest::try_read_manifest(&self.0).map(Vec::from).and_then(collect_all)
+  }
+}
+
+/// The serial numbers of several cards read from a single file, one per line,
+///   as exposed by some aggregating card readers.
+/// Empty lines are ignored, so the usual trailing line feed does not matter.
+pub struct MultiSerialFileSource(pub PathBuf);
+
+impl SerialSource for MultiSerialFileSource {
+  fn read_serials(&self) -> Result<Vec<SerialNumber>, Error> {
+    fs::read(&self.0)
+    .inspect_err(|error|
+      eprintln!(
+        "Cannot read file {}: {}",
+        self.0.display(),
+        error
+      )
+    )
+    .map_err(|_| Error::CannotReadFile)?
+    .split(|&byte| byte == b'\n')
+    .enumerate()
+    .filter(|(_, line)| !line.is_empty())
+    .map(|(number, line)|
+      line
+      .try_into()
+      .map(SerialNumber)
+      .inspect_err(|_|
+        eprintln!(
+          "Line {} of file {} has {} bytes, expected a serial number of {}",
+          number + 1,
+          self.0.display(),
+          line.len(),
+          SerialNumber::LENGTH
+        )
+      )
+      .map_err(|_| Error::CannotReadSerialNumber)
+    )
+    .collect::<Result<Vec<SerialNumber>, Error>>()
+    .and_then(collect_all)
+  }
+}
+
+/// Refuse the serial numbers of another source if any of them is forbidden,
+///   so no PINs are derived from leftover test configuration.
+/// The serial numbers are compared in constant time, as they are somewhat sensitive:
+///   Every serial number read is compared with every forbidden one, even after a match,
+///   and only the number of forbidden ones read is reported, not which they are.
+pub struct CheckedSource<'a> {
+  pub source:     Box<dyn SerialSource + 'a>,
+  pub forbidden:  Vec<SerialNumber>,
+}
+
+impl CheckedSource<'_> {
+  /// Whether the serial number is one of the forbidden ones, without stopping at the first match.
+  fn is_forbidden(&self, serial_number: &SerialNumber) -> bool {
+    self.forbidden
+    .iter()
+    .fold(false, |found, forbidden| found | constant_time_eq(&forbidden.0, &serial_number.0))
+  }
+}
+
+impl SerialSource for CheckedSource<'_> {
+  fn read_serials(&self) -> Result<Vec<SerialNumber>, Error> {
+    let serial_numbers = self.source.read_serials()?;
+    let forbidden = serial_numbers
+    .iter()
+    .fold(0, |forbidden, serial_number| forbidden + usize::from(self.is_forbidden(serial_number)));
+    if forbidden > 0 {
+      eprintln!(
+        "{} of {} serial numbers are forbidden, see --forbid-serial and --forbid-test-serials",
+        forbidden,
+        serial_numbers.len()
+      );
+      return Err(Error::ForbiddenSerial);
+    }
+    Ok(serial_numbers)
+  }
+}
+
+/// The serial numbers of another source with those of some card readers replaced,
+///   to plan which PINs a card reader with another card would yield, see `--what-if`.
+/// The first of each pair is the index of the card reader, the second the serial number it is assumed to have.
+pub struct WhatIfSource<'a> {
+  pub source:     Box<dyn SerialSource + 'a>,
+  pub overrides:  Vec<(usize, SerialNumber)>,
+}
+
+impl SerialSource for WhatIfSource<'_> {
+  fn read_serials(&self) -> Result<Vec<SerialNumber>, Error> {
+    let mut serial_numbers = self.source.read_serials()?;
+    let count = serial_numbers.len();
+    self.overrides
+    .iter()
+    .try_for_each(|&(index, serial_number)|
+      serial_numbers
+      .get_mut(index)
+      .map(|slot| *slot = serial_number)
+      .ok_or(Error::ReaderIndexOutOfRange)
+      .inspect_err(|_| eprintln!("Card reader {} of --what-if does not exist, there are {}", index, count))
+    )?;
+    Ok(serial_numbers)
+  }
+}
+
+/// The serial numbers of another source which start with a prefix, e.g. a vendor code, see `--serial-prefix`.
+/// Leaving out serial numbers changes the set the PINs are derived from, and thus the PINs,
+///   and the card readers given with `--group` refer to the serial numbers left.
+pub struct PrefixSource<'a> {
+  pub source: Box<dyn SerialSource + 'a>,
+  pub prefix: String,
+}
+
+impl SerialSource for PrefixSource<'_> {
+  fn read_serials(&self) -> Result<Vec<SerialNumber>, Error> {
+    let mut serial_numbers = self.source.read_serials()?;
+    let count = serial_numbers.len();
+    serial_numbers.retain(|serial_number| serial_number.0.starts_with(self.prefix.as_bytes()));
+    if serial_numbers.len() < count {
+      eprintln!(
+        "Warning: {} of {} serial numbers do not start with {} and are left out, see --serial-prefix",
+        count - serial_numbers.len(),
+        count,
+        self.prefix
+      );
+    }
+    Ok(serial_numbers)
+  }
+}
+
+/// The serial numbers read from all card readers whose sysfs attributes match a pattern,
+///   ordered by their paths, see `glob::expand`, at an offset into each of them,
+///   and whether to substitute `SerialNumber::MISSING` for those which cannot be read.
+pub struct GlobSource(pub String, pub u64, pub bool);
+
+impl SerialSource for GlobSource {
+  fn read_serials(&self) -> Result<Vec<SerialNumber>, Error> {
+    let file_names = glob::expand(&self.0)
+    .inspect_err(|error| eprintln!("Cannot expand pattern {}: {}", self.0, error))
+    .map_err(|_| Error::CannotOpenCardReader)?;
+    (!file_names.is_empty())
+    .then_some(())
+    .ok_or(Error::NoMatchingCardReaders)
+    .inspect_err(|_| eprintln!("No card reader matches pattern {}", self.0))?;
+
+    collect_readings(
+      file_names
+      .iter()
+      .map(|file_name| file_name.to_string_lossy().into_owned())
+      .map(|file_name| try_read_serial_number(&file_name, self.1).map_err(|error| (file_name, error)))
+      .collect(),
+      self.2,
+    )
+  }
+}
+
+/// The serial numbers given in the environment variables `KONNEKTOR_SERIAL_0`, `KONNEKTOR_SERIAL_1`, …,
+///   e.g. in CI, without creating files.
+/// The variables are read in order up to the first one which is not set.
+pub struct EnvSource;
+
+impl EnvSource {
+  /// Prefix of the names of the variables, followed by the index of the reader.
+  const PREFIX: &'static str = "KONNEKTOR_SERIAL_";
+
+  /// Read the serial numbers from variables looked up by name, e.g. with `env::var`.
+  pub fn read_from(lookup: impl Fn(&str) -> Option<String>) -> Result<Vec<SerialNumber>, Error> {
+    (0..)
+    .map(|index| format!("{}{}", Self::PREFIX, index))
+    .map_while(|name| lookup(&name).map(|value| (name, value)))
+    .map(|(name, value)|
+      value
+      .parse()
+      .inspect_err(|_|
+        eprintln!(
+          "Variable {} has {} bytes, expected a serial number of {}",
+          name,
+          value.len(),
+          SerialNumber::LENGTH
+        )
+      )
+      .map_err(|_| Error::CannotReadSerialNumber)
+    )
+    .collect::<Result<Vec<SerialNumber>, Error>>()
+    .and_then(collect_all)
+  }
+}
+
+impl SerialSource for EnvSource {
+  fn read_serials(&self) -> Result<Vec<SerialNumber>, Error> {
+    Self::read_from(|name| env::var(name).ok())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{
+      parse_uevent,
+      summarize_failures,
+      CheckedSource,
+      EnvSource,
+      FixedSource,
+      MultiSerialFileSource,
+      SerialSource,
+    },
+    crate::{
+      Error,
+      SerialNumber,
+    },
+    std::{
+      collections::HashMap,
+      env,
+      fs,
+      process,
+    },
+  };
+
+  /// A forbidden serial number refuses all of them, other serial numbers are passed on unchanged.
+  #[test]
+  fn forbidden_serial_is_refused() {
+    let checked = |serial_numbers: &[&[u8; 8]]| CheckedSource {
+      source:     Box::new(FixedSource(serial_numbers.iter().map(|&&serial_number| SerialNumber(serial_number)).collect())),
+      forbidden:  vec![SerialNumber(*b"blocked!")],
+    }
+    .read_serials();
+    assert_eq!(checked(&[b"allowed!", b"blocked!"]), Err(Error::ForbiddenSerial));
+    assert_eq!(checked(&[b"allowed!"]), Ok(vec![SerialNumber(*b"allowed!")]));
+  }
+
+  /// Only a serial number equal to a forbidden one matches, also if it is not the first forbidden one.
+  #[test]
+  fn forbidden_serial_matches_exactly() {
+    let checked = CheckedSource {
+      source:     Box::new(FixedSource(Vec::new())),
+      forbidden:  vec![SerialNumber(*b"blocked!"), SerialNumber(*b"23421337")],
+    };
+    assert!(checked.is_forbidden(&SerialNumber(*b"blocked!")));
+    assert!(checked.is_forbidden(&SerialNumber(*b"23421337")));
+    assert!(!checked.is_forbidden(&SerialNumber(*b"blocked?")));
+    assert!(!checked.is_forbidden(&SerialNumber(*b"Blocked!")));
+    assert!(!checked.is_forbidden(&SerialNumber::MISSING));
+  }
+
+  /// Only some card readers failing is told apart from all of them failing, also by the exit status.
+  #[test]
+  fn some_readers_failed() {
+    let failures = [
+      ("/sys/bus/usb/devices/1-4/serial".to_owned(), Error::CardReaderUnavailable),
+      ("/sys/bus/usb/devices/1-6/serial".to_owned(), Error::CannotOpenCardReader),
+    ];
+    let partial = summarize_failures(&failures, 3);
+    assert_eq!(partial, Error::SomeReadersFailed);
+    assert_eq!(partial.exit_code(), 2);
+    let total = summarize_failures(&failures, 2);
+    assert_eq!(total, Error::CannotReadSerialNumber);
+    assert_eq!(total.exit_code(), 1);
+  }
+
+  /// The value of a key is found among the other lines, a missing key is none and malformed lines are skipped.
+  #[test]
+  fn uevent_is_parsed() {
+    let content = "DRIVER=usb\nSERIAL=23421337\nPRODUCT=76b/3021/100\n";
+    assert_eq!(parse_uevent(content, "SERIAL"), Some("23421337"));
+    assert_eq!(parse_uevent(content, "MISSING"), None);
+    assert_eq!(parse_uevent(content, "SERIA"), None);
+    assert_eq!(parse_uevent("SERIAL\nno equals sign\nSERIAL=12345678\nSERIAL=87654321", "SERIAL"), Some("12345678"));
+    assert_eq!(parse_uevent("SERIAL=", "SERIAL"), Some(""));
+  }
+
+  /// The variables are read in order up to the first gap, and a value which is no serial number is refused.
+  #[test]
+  fn env_source_reads_up_to_gap() {
+    let variables: HashMap<&str, &str> = [
+      ("KONNEKTOR_SERIAL_0", "12345678"),
+      ("KONNEKTOR_SERIAL_1", "87654321"),
+      ("KONNEKTOR_SERIAL_3", "11111111"),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(
+      EnvSource::read_from(|name| variables.get(name).map(|value| value.to_string())),
+      Ok(vec![SerialNumber(*b"12345678"), SerialNumber(*b"87654321")])
+    );
+    assert_eq!(EnvSource::read_from(|_| None), Ok(Vec::new()));
+    assert_eq!(
+      EnvSource::read_from(|name| (name == "KONNEKTOR_SERIAL_0").then(|| "1234567".to_owned())),
+      Err(Error::CannotReadSerialNumber)
+    );
+  }
+
+  /// A file of three lines gives three serial numbers, but a line of the wrong length is refused.
+  #[test]
+  fn multi_serial_file_is_split() {
+    let path = env::temp_dir().join(format!("multi-serial-{}", process::id()));
+    fs::write(&path, "23421337\nmeowmeow\n*squeak*\n").unwrap();
+    let read = MultiSerialFileSource(path.clone()).read_serials();
+    fs::write(&path, "23421337\nmeow\n*squeak*\n").unwrap();
+    let short = MultiSerialFileSource(path.clone()).read_serials();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(read, Ok(vec![SerialNumber(*b"23421337"), SerialNumber(*b"meowmeow"), SerialNumber(*b"*squeak*")]));
+    assert_eq!(short, Err(Error::CannotReadSerialNumber));
+  }
+}
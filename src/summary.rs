@@ -0,0 +1,107 @@
+use {
+  crate::{
+    format::json_string,
+    Algorithm,
+    Error,
+  },
+  std::{
+    fs,
+    path::PathBuf,
+    sync::atomic::{
+      AtomicUsize,
+      Ordering,
+    },
+    time::{
+      Duration,
+      Instant,
+    },
+  },
+};
+
+/// Number of card readers read and failed in this run, recorded by every source, see `source::collect_readings`.
+/// The tool calculates the PINs once per process, so counting in statics is enough.
+static READERS_READ:    AtomicUsize = AtomicUsize::new(0);
+static READERS_FAILED:  AtomicUsize = AtomicUsize::new(0);
+
+/// Record that `read` card readers were read, of which `failed` failed.
+pub fn record_readings(read: usize, failed: usize) {
+  READERS_READ.fetch_add(read, Ordering::Relaxed);
+  READERS_FAILED.fetch_add(failed, Ordering::Relaxed);
+}
+
+/// A record of a run for monitoring, written to a file on exit, see `--summary-json`.
+/// It never contains PINs or serial numbers, only counts, the parameters and the outcome.
+pub struct Summary {
+  file:       PathBuf,
+  algorithm:  Algorithm,
+  count:      usize,
+  started:    Instant,
+}
+
+impl Summary {
+  /// Start timing a run deriving `count` PINs with the algorithm.
+  pub fn start(file: PathBuf, algorithm: Algorithm, count: usize) -> Self {
+    Self {
+      file,
+      algorithm,
+      count,
+      started: Instant::now(),
+    }
+  }
+
+  /// Get the record of a run which read `readers` card readers, `failures` of them failed, and took `duration`,
+  ///   as a single JSON object.
+  fn record(&self, readers: usize, failures: usize, duration: Duration, outcome: &Result<(), Error>) -> String {
+    format!(
+      "{{\"readers\":{},\"failures\":{},\"algorithm\":\"{}\",\"count\":{},\"duration_ms\":{},\"success\":{}{}}}\n",
+      readers,
+      failures,
+      self.algorithm,
+      self.count,
+      duration.as_millis(),
+      outcome.is_ok(),
+      outcome.as_ref().err().map_or_else(String::new, |error| format!(",\"error\":{}", json_string(&error.to_string()))),
+    )
+  }
+
+  /// Write the record of the finished run.
+  pub fn finish(self, outcome: &Result<(), Error>) -> Result<(), Error> {
+    let record = self.record(
+      READERS_READ.load(Ordering::Relaxed),
+      READERS_FAILED.load(Ordering::Relaxed),
+      self.started.elapsed(),
+      outcome,
+    );
+    fs::write(&self.file, record)
+    .inspect_err(|error| eprintln!("Cannot write summary {}: {}", self.file.display(), error))
+    .map_err(|_| Error::CannotWriteSummary)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::Summary,
+    crate::{
+      Algorithm,
+      Error,
+    },
+    std::time::Duration,
+  };
+
+  /// The record of a run has all its fields, and the message of an error is escaped, even with line feeds and quotes.
+  #[test]
+  fn record_has_all_fields() {
+    let summary = Summary::start("summary.json".into(), Algorithm::DoubleSHA512, 12);
+    assert_eq!(
+      summary.record(3, 0, Duration::from_millis(42), &Ok(())),
+      "{\"readers\":3,\"failures\":0,\"algorithm\":\"double-sha512\",\"count\":12,\"duration_ms\":42,\"success\":true}\n"
+    );
+    let error = Err(Error::InvalidConfig { problems: vec!["Option --digits must be \"1\" to \\12".to_owned()] });
+    assert_eq!(
+      summary.record(3, 1, Duration::from_millis(7), &error),
+      "{\"readers\":3,\"failures\":1,\"algorithm\":\"double-sha512\",\"count\":12,\"duration_ms\":7,\"success\":false,\
+        \"error\":\"Invalid combination of options:\\n  Option --digits must be \\\"1\\\" to \\\\12\"}\n"
+    );
+  }
+}
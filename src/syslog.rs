@@ -0,0 +1,70 @@
+use {
+  crate::{
+    privileges,
+    Error,
+  },
+  std::{
+    io::Result as IoResult,
+    os::unix::net::UnixDatagram,
+    process,
+  },
+};
+
+/// Severity of a message sent to syslog, see RFC 5424.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Severity {
+  Error   = 3,
+  Info    = 6,
+}
+
+/// Somewhere the messages of `--syslog` go.
+pub trait LogSink {
+  fn send(&self, severity: Severity, message: &str) -> IoResult<()>;
+}
+
+/// The local syslog daemon, listening on `/dev/log`.
+/// Messages are sent with the facility `authpriv`, so only privileged users can read them.
+pub struct DevLog(UnixDatagram);
+
+impl DevLog {
+  const PATH:     &'static str  = "/dev/log";
+  /// The facility `authpriv` of RFC 5424.
+  const FACILITY: u8            = 10;
+  /// The name this tool logs with.
+  const TAG:      &'static str  = "konnektor-pin-calculator";
+
+  pub fn connect() -> IoResult<Self> {
+    let socket = UnixDatagram::unbound()?;
+    socket.connect(Self::PATH)?;
+    Ok(Self(socket))
+  }
+}
+
+impl LogSink for DevLog {
+  fn send(&self, severity: Severity, message: &str) -> IoResult<()> {
+    self.0
+    .send(
+      format!(
+        "<{}>{}[{}]: {}",
+        Self::FACILITY * 8 + severity as u8,
+        Self::TAG,
+        process::id(),
+        message
+      )
+      .as_bytes()
+    )
+    .map(|_| ())
+  }
+}
+
+/// Log who ran this tool and whether it succeeded, but never any PIN.
+pub fn log_outcome(sink: &dyn LogSink, outcome: &Result<(), Error>) {
+  let uid = privileges::euid();
+  let (severity, message) = match outcome {
+    Ok(())      => (Severity::Info, format!("Run by uid {} succeeded", uid)),
+    Err(error)  => (Severity::Error, format!("Run by uid {} failed: {}", uid, error)),
+  };
+  sink
+  .send(severity, &message)
+  .unwrap_or_else(|error| eprintln!("Warning: Cannot log to syslog: {}", error));
+}
@@ -0,0 +1,215 @@
+use {
+  crate::{
+    format::{
+      frame_hex,
+      write_card_heading,
+      DerivationMeta,
+      IndexedPin,
+      PinFormatter,
+      RenderOptions,
+    },
+    hex,
+    Error,
+    Puk,
+  },
+  std::{
+    io::{
+      Result as IoResult,
+      Write,
+    },
+    mem,
+    str::FromStr,
+  },
+};
+
+/// A part of a template, see `Template`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Segment {
+  /// Text copied as is.
+  Literal(String),
+  /// The index of the PIN.
+  Index,
+  /// The digits of the PIN, masked if redacted.
+  Digits,
+  /// The frame of the PIN as space separated hex bytes, see `format::frame_hex`.
+  FrameHex,
+  /// The serial numbers, comma separated and masked like the PINs, as in `TableFormatter`.
+  Serials,
+  /// The fingerprint of the serial numbers, see `serials_fingerprint`.
+  Fingerprint,
+  /// The digits of the PUK, if any, see `--puk`.
+  Puk,
+}
+
+/// A line written for each PIN with placeholders replaced, given with `--template`, e.g.
+/// ```text
+/// {index}: {digits} (serials={serials}, fingerprint={fingerprint})
+/// ```
+/// The placeholders are `{index}`, `{digits}`, `{frame_hex}`, `{serials}`, `{fingerprint}` and `{puk}`,
+///   `{{` and `}}` are literal braces.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Template(Vec<Segment>);
+
+impl FromStr for Template {
+  type Err = Error;
+
+  /// Parse a template, rejecting unknown placeholders and unbalanced braces.
+  fn from_str(template: &str) -> Result<Self, Error> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut characters = template.chars();
+    while let Some(character) = characters.next() {
+      match character {
+        '{' if characters.as_str().starts_with('{')
+        =>  {
+              characters.next();
+              literal.push('{');
+            },
+        '}' if characters.as_str().starts_with('}')
+        =>  {
+              characters.next();
+              literal.push('}');
+            },
+        '{'
+        =>  {
+              let (name, rest) = characters
+              .as_str()
+              .split_once('}')
+              .ok_or(Error::InvalidArguments)
+              .inspect_err(|_| eprintln!("Template {} has an unterminated placeholder", template))?;
+              let segment = match name {
+                "index"       => Segment::Index,
+                "digits"      => Segment::Digits,
+                "frame_hex"   => Segment::FrameHex,
+                "serials"     => Segment::Serials,
+                "fingerprint" => Segment::Fingerprint,
+                "puk"         => Segment::Puk,
+                _
+                =>  {
+                      eprintln!(
+                        "Template {} has an unknown placeholder {{{}}}, expected {{index}}, {{digits}}, {{frame_hex}}, {{serials}}, {{fingerprint}} or {{puk}}",
+                        template,
+                        name
+                      );
+                      return Err(Error::InvalidArguments);
+                    },
+              };
+              if !literal.is_empty() {
+                segments.push(Segment::Literal(mem::take(&mut literal)));
+              }
+              segments.push(segment);
+              characters = rest.chars();
+            },
+        '}'
+        =>  {
+              eprintln!("Template {} has an unmatched }}, write }}}} for a literal one", template);
+              return Err(Error::InvalidArguments);
+            },
+        _
+        =>  literal.push(character),
+      }
+    }
+    if !literal.is_empty() {
+      segments.push(Segment::Literal(literal));
+    }
+    Ok(Self(segments))
+  }
+}
+
+/// One line per PIN according to a template, see `Template`.
+pub struct TemplateFormatter(pub Template, pub RenderOptions);
+
+impl PinFormatter for TemplateFormatter {
+  fn render(&self, pins: &[IndexedPin], puks: &[Puk], meta: &DerivationMeta, writer: &mut dyn Write) -> IoResult<()> {
+    write_card_heading(meta, writer)?;
+    let serials = meta.serials
+    .iter()
+    .map(|serial_number| self.1.redaction.apply_serial(serial_number))
+    .collect::<Vec<String>>()
+    .join(", ");
+    let fingerprint = meta.serials_fp.map_or_else(String::new, |fingerprint| hex::encode(&fingerprint));
+    pins
+    .iter()
+    .enumerate()
+    .try_for_each(|(position, (index, pin))| {
+      let line = self.0.0
+      .iter()
+      .map(|segment|
+        match segment {
+          Segment::Literal(text)  => text.clone(),
+          Segment::Index          => index.to_string(),
+          Segment::Digits         => self.1.redaction.apply(pin),
+          Segment::FrameHex       => frame_hex(pin, self.1.redaction),
+          Segment::Serials        => serials.clone(),
+          Segment::Fingerprint    => fingerprint.clone(),
+          Segment::Puk            => puks.get(position).map_or_else(String::new, |puk| self.1.redaction.apply_puk(puk)),
+        }
+      )
+      .collect::<String>();
+      writeln!(writer, "{}", line)
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{
+      Segment,
+      Template,
+      TemplateFormatter,
+    },
+    crate::{
+      format::{
+        DerivationMeta,
+        PinFormatter,
+        RenderOptions,
+      },
+      Error,
+      Parameters,
+      Pin,
+      SerialNumber,
+    },
+  };
+
+  /// A template is parsed into its placeholders and literals, and rendered with the serial numbers and their fingerprint apart.
+  #[test]
+  fn template_round_trip() {
+    let template: Template = "{index}: {digits} {{{serials}}} {fingerprint}{puk}".parse().unwrap();
+    assert_eq!(
+      template,
+      Template(vec![
+        Segment::Index,
+        Segment::Literal(": ".to_owned()),
+        Segment::Digits,
+        Segment::Literal(" {".to_owned()),
+        Segment::Serials,
+        Segment::Literal("} ".to_owned()),
+        Segment::Fingerprint,
+        Segment::Puk,
+      ])
+    );
+
+    let meta = DerivationMeta {
+      serials_fp: Some([0xab; 32]),
+      serials:    vec![SerialNumber(*b"12345678"), SerialNumber(*b"87654321")],
+      ..DerivationMeta::new(Parameters::default(), 1)
+    };
+    let mut output = Vec::new();
+    TemplateFormatter(template, RenderOptions::default())
+    .render(&[(3, Pin::default())], &[], &meta, &mut output)
+    .unwrap();
+    assert_eq!(
+      String::from_utf8(output).unwrap(),
+      format!("3: 123456789123 {{12345678, 87654321}} {}\n", "ab".repeat(32))
+    );
+  }
+
+  /// Unknown placeholders and unbalanced braces are refused rather than copied.
+  #[test]
+  fn unknown_placeholder_is_refused() {
+    ["{serial}", "{index", "index}", "{}"]
+    .into_iter()
+    .for_each(|template| assert_eq!(template.parse::<Template>(), Err(Error::InvalidArguments), "{}", template));
+  }
+}
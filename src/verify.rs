@@ -0,0 +1,204 @@
+use {
+  crate::{
+    check_index,
+    Error,
+    Pin,
+  },
+  core::hint::black_box,
+  std::{
+    fs,
+    io::{
+      BufRead,
+      Write,
+    },
+    path::Path,
+  },
+};
+
+/// An expected PIN: its index and its symbols, mapped to digits by the alphabet of the PIN, see `verify_entry`.
+type Entry = (usize, String);
+
+/// Compare two byte strings without stopping at the first difference.
+/// Only the lengths may leak through timing, but the length of a PIN is not secret.
+pub fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+  left.len() == right.len()
+  && black_box(
+    left.iter().zip(right)
+    .fold(0u8, |difference, (left, right)| difference | (left ^ right))
+  ) == 0
+}
+
+/// Parse a line of the form `index pin`, where the PIN is given as the symbols of its alphabet,
+///   e.g. decimal or hexadecimal digits.
+pub fn parse_entry(line: &str) -> Option<Entry> {
+  let mut fields = line.split_whitespace();
+  let index = fields.next()?.parse().ok()?;
+  let symbols = fields.next()?.to_string();
+  fields.next().is_none().then_some((index, symbols))
+}
+
+/// Check a single expected PIN against the calculated ones.
+/// Symbols not in the alphabet of the PIN fail the check.
+fn verify_entry(pins: &[Pin], (index, symbols): &Entry) -> bool {
+  check_index(*index, pins.len())
+  .is_ok_and(|_|
+    symbols
+    .chars()
+    .map(|symbol| pins[*index].digit(symbol))
+    .collect::<Option<Vec<u8>>>()
+    .is_some_and(|digits| constant_time_eq(&pins[*index].digits(), &digits))
+  )
+}
+
+/// Verify all entries, without stopping at the first mismatch.
+/// Only the number of matched and failed entries is reported, not which of them failed.
+pub fn verify_entries(pins: &[Pin], entries: &[Entry]) -> Result<(), Error> {
+  let matched = entries
+  .iter()
+  .filter(|entry| verify_entry(pins, entry))
+  .count();
+
+  println!(
+    "Verified {} PINs: {} matched, {} failed",
+    entries.len(),
+    matched,
+    entries.len() - matched,
+  );
+
+  (matched == entries.len())
+  .then_some(())
+  .ok_or(Error::VerificationFailed)
+}
+
+/// Verify the `index pin` pairs listed in a file, one per line.
+/// Empty lines and lines starting with `#` are ignored.
+pub fn verify_all(pins: &[Pin], file_name: &Path) -> Result<(), Error> {
+  fs::read_to_string(file_name)
+  .inspect_err(|error|
+    eprintln!(
+      "Cannot read file {}: {}",
+      file_name.display(),
+      error
+    )
+  )
+  .map_err(|_| Error::CannotReadFile)?
+  .lines()
+  .enumerate()
+  .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+  .map(|(number, line)| parse_entry(line).ok_or(number + 1))
+  .collect::<Result<Vec<Entry>, usize>>()
+  .inspect_err(|number|
+    eprintln!(
+      "Malformed entry in line {} of {}, expected `index pin`",
+      number,
+      file_name.display()
+    )
+  )
+  .map_err(|_| Error::MalformedVerificationFile)
+  .and_then(|entries| verify_entries(pins, &entries))
+}
+
+/// Verify the `index pin` pairs read line by line, e.g. from stdin in a pipeline,
+///   and write `PASS` or `FAIL` with the line number for each of them, followed by the summary.
+/// Malformed lines fail as well, but do not stop the verification.
+/// Empty lines and lines starting with `#` are ignored.
+pub fn verify_stream(pins: &[Pin], reader: impl BufRead, writer: &mut impl Write) -> Result<(), Error> {
+  let mut verified = 0;
+  let mut matched = 0;
+  for (number, line) in reader.lines().enumerate() {
+    let line = line
+    .inspect_err(|error| eprintln!("Cannot read line {} to verify: {}", number + 1, error))
+    .map_err(|_| Error::CannotReadFile)?;
+    if line.trim().is_empty() || line.trim_start().starts_with('#') {
+      continue;
+    }
+
+    let passed = parse_entry(&line).is_some_and(|entry| verify_entry(pins, &entry));
+    verified += 1;
+    matched += passed as usize;
+    writeln!(writer, "{} {}", if passed { "PASS" } else { "FAIL" }, number + 1)
+    .inspect_err(|error| eprintln!("Cannot write verification result: {}", error))
+    .map_err(|_| Error::CannotWritePins)?;
+  }
+
+  writeln!(
+    writer,
+    "Verified {} PINs: {} matched, {} failed",
+    verified,
+    matched,
+    verified - matched,
+  )
+  .inspect_err(|error| eprintln!("Cannot write verification result: {}", error))
+  .map_err(|_| Error::CannotWritePins)?;
+
+  (matched == verified)
+  .then_some(())
+  .ok_or(Error::VerificationFailed)
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{
+      constant_time_eq,
+      parse_entry,
+      verify_entries,
+      verify_stream,
+    },
+    crate::{
+      Error,
+      Pin,
+    },
+  };
+
+  /// The PINs 123456789123 and 001122334405.
+  fn pins() -> [Pin; 2] {
+    [Pin::default(), Pin::new(&[0x00, 0x11, 0x22, 0x33, 0x44, 0x05])]
+  }
+
+  /// Byte strings are equal only with the same length and the same bytes, wherever they differ.
+  #[test]
+  fn constant_time_comparison() {
+    assert!(constant_time_eq(b"123456", b"123456"));
+    assert!(!constant_time_eq(b"123456", b"023456"));
+    assert!(!constant_time_eq(b"123456", b"123450"));
+    assert!(!constant_time_eq(b"123456", b"12345"));
+  }
+
+  /// A mix of correct and incorrect entries fails as a whole, but only if any entry is incorrect,
+  ///   including an index out of range and symbols not in the alphabet.
+  #[test]
+  fn mixed_entries_fail() {
+    let pins = pins();
+    let entries = |lines: &[&str]| lines.iter().map(|line| parse_entry(line).unwrap()).collect::<Vec<_>>();
+    assert_eq!(verify_entries(&pins, &entries(&["0 123456789123", "1 001122334405"])), Ok(()));
+    [
+      ["0 123456789123", "1 001122334406"],
+      ["0 123456789123", "2 001122334405"],
+      ["0 12345678912x", "1 001122334405"],
+      ["0 12345678912",  "1 001122334405"],
+    ]
+    .into_iter()
+    .for_each(|lines| assert_eq!(verify_entries(&pins, &entries(&lines)), Err(Error::VerificationFailed), "{:?}", lines));
+    assert_eq!(parse_entry("0 123456789123 extra"), None);
+    assert_eq!(parse_entry("zero 123456789123"), None);
+  }
+
+  /// Each line of a stream is reported on its own, malformed lines fail without stopping the verification,
+  ///   and comments and empty lines are skipped, but still counted as lines.
+  #[test]
+  fn stream_is_verified_line_by_line() {
+    let pins = pins();
+    let mut output = Vec::new();
+    let input: &[u8] = b"# index pin\n0 123456789123\n\n1 001122334406\nmalformed\n1 001122334405\n";
+    assert_eq!(verify_stream(&pins, input, &mut output), Err(Error::VerificationFailed));
+    assert_eq!(
+      String::from_utf8(output).unwrap(),
+      "PASS 2\nFAIL 4\nFAIL 5\nPASS 6\nVerified 4 PINs: 2 matched, 2 failed\n"
+    );
+
+    let mut output = Vec::new();
+    assert_eq!(verify_stream(&pins, &b"1 001122334405\n"[..], &mut output), Ok(()));
+    assert_eq!(String::from_utf8(output).unwrap(), "PASS 1\nVerified 1 PINs: 1 matched, 0 failed\n");
+  }
+}
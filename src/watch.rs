@@ -0,0 +1,114 @@
+use {
+  crate::{
+    format::IndexedPin,
+    hex,
+    options::Options,
+    source::SerialSource,
+    try_calculate,
+    CalculationResult,
+    Error,
+    Pin,
+    SerialNumbers,
+  },
+  std::{
+    io,
+    thread,
+    time::Duration,
+  },
+};
+
+/// Get the PINs which differ from the previous ones at the same index, including new ones.
+pub fn changed_pins(previous: &[Pin], current: &[Pin]) -> Vec<IndexedPin> {
+  current
+  .iter()
+  .copied()
+  .enumerate()
+  .filter(|(index, pin)| previous.get(*index) != Some(pin))
+  .collect()
+}
+
+/// Recalculate the PINs periodically, e.g. while card readers are swapped,
+///   but only print those which changed, so the screen stays stable.
+/// Whenever the serial numbers change, their fingerprint is reported on stderr.
+/// Failed calculations are reported and retried in the next interval.
+pub fn watch(options: &Options, source: &dyn SerialSource, interval: Duration) -> Result<(), Error> {
+  let formatter = options.format.formatter(options.render);
+  let mut previous: Option<CalculationResult> = None;
+  loop {
+    match
+      source
+      .read_serials()
+      .and_then(|serial_numbers| SerialNumbers::new(serial_numbers, options.parameters))
+      .and_then(|serial_numbers| try_calculate(serial_numbers, options.parameters))
+    {
+      Ok(result) => {
+        if previous.as_ref().is_none_or(|previous| previous.serials != result.serials) {
+          eprintln!(
+            "Calculating PINs of {} serial numbers with fingerprint {}",
+            result.serials.len(),
+            hex::encode(&result.serials.fingerprint())
+          );
+        }
+        let changed = changed_pins(previous.as_ref().map_or(&[], |previous| &previous.pins), &result.pins);
+        if !changed.is_empty() {
+          formatter
+          .render(&changed, &[], &result.meta, &mut io::stdout().lock())
+          .inspect_err(|error| eprintln!("Cannot write PINs: {}", error))
+          .map_err(|_| Error::CannotWritePins)?;
+        }
+        previous = Some(result);
+      },
+      Err(error)
+      =>  eprintln!(
+            "Cannot calculate PINs, retrying in {} s: {}",
+            interval.as_secs(),
+            error
+          ),
+    }
+    thread::sleep(interval);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::changed_pins,
+    crate::{
+      try_calculate_all_pins,
+      Parameters,
+      Pin,
+      SerialNumber,
+      SerialNumbers,
+      TEST_SERIAL_NUMBERS,
+    },
+  };
+
+  /// Only the indices whose PIN changed are reported: All of them when a serial number changes,
+  ///   none while the serial numbers stay the same, and those added by a larger count.
+  #[test]
+  fn only_changed_pins_are_reported() {
+    let swapped = [TEST_SERIAL_NUMBERS[0], SerialNumber(*b"swapped!"), TEST_SERIAL_NUMBERS[2]];
+    let pins = |serials: &[SerialNumber], count: usize| {
+      let parameters = Parameters { count, ..Parameters::default() };
+      try_calculate_all_pins(&SerialNumbers::new(serials.to_vec(), parameters).unwrap(), parameters).unwrap()
+    };
+    let sequence = [
+      pins(&TEST_SERIAL_NUMBERS, 6),
+      pins(&TEST_SERIAL_NUMBERS, 6),
+      pins(&swapped, 6),
+      pins(&swapped, 8),
+    ];
+    let changed: Vec<Vec<usize>> = sequence
+    .windows(2)
+    .map(|pair| changed_pins(&pair[0], &pair[1]).into_iter().map(|(index, _)| index).collect())
+    .collect();
+    assert_eq!(changed, [vec![], (0..6).collect(), vec![6, 7]]);
+    assert_eq!(changed_pins(&[], &sequence[0]).len(), 6);
+
+    let other = Pin::new(&[0x00, 0x11, 0x22, 0x33, 0x44, 0x05]);
+    assert_eq!(
+      changed_pins(&[Pin::default(), Pin::default(), Pin::default()], &[Pin::default(), other, Pin::default(), other]),
+      [(1, other), (3, other)]
+    );
+  }
+}